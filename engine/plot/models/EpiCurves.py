@@ -29,7 +29,7 @@ def make_number_of_rows_equal(data_frames):
 
 def calculate_mean_and_standard_dev(data_frames):
     df_equal_rows = make_number_of_rows_equal(data_frames)
-    columns = list(filter(lambda c: c != 'hour', df_equal_rows[0].columns))
+    columns = list(filter(lambda c: c not in ('hour', 'date'), df_equal_rows[0].columns))
     curves = []
     for column in columns:
         collated_columns = np.array(list(map(lambda df: df[column], df_equal_rows)))
@@ -54,7 +54,7 @@ def toggle_visibility(figure, legend_line, plots, ax):
 
 
 def load_collated_csv(data_frame):
-    columns = list(filter(lambda c: c != 'hour' and '_std' not in c, data_frame.columns))
+    columns = list(filter(lambda c: c not in ('hour', 'date') and '_std' not in c, data_frame.columns))
     return list(map(lambda c: Curve(c, data_frame[c], data_frame[f'{c}_std']), columns))
 
 
@@ -28,7 +28,7 @@ def arg_parser():
 
 
 def plot(data_frame, time_column):
-    columns = filter(lambda c: c != time_column, data_frame.columns)
+    columns = filter(lambda c: c not in (time_column, 'date'), data_frame.columns)
     daily_basis = data_frame[data_frame[time_column] % 24 == 1]
 
     for column in columns:
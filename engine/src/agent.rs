@@ -22,28 +22,67 @@ use rand::seq::IteratorRandom;
 use rand::seq::SliceRandom;
 use serde::{de, Deserialize, Deserializer};
 use serde::de::Unexpected;
-use uuid::Uuid;
+use uuid::{Builder, Uuid, Variant, Version};
 
 use crate::allocation_map::AgentLocationMap;
 use crate::config::StartingInfections;
 use crate::constants;
-use crate::disease::Disease;
+use crate::disease::{Disease, DiseaseProfiles};
 use crate::disease_state_machine::DiseaseStateMachine;
 use crate::geography::{Area, Grid, Point};
+use crate::hospital_beds::Bed;
+use crate::schedule::{Activity, Schedule};
 
 use crate::travel_plan::Traveller;
+use crate::vaccine::{Vaccination, Vaccine};
 
 #[derive(Deserialize)]
 pub struct PopulationRecord {
     //TODO move to a better place
     pub ind: i32,
     pub age: String,
+    #[serde(default)]
+    pub sex: Option<String>,
     #[serde(deserialize_with = "bool_from_string")]
     pub working: bool,
     #[serde(deserialize_with = "bool_from_string")]
     pub pub_transport: bool,
 }
 
+impl PopulationRecord {
+    /// The columns disease overrides can match on
+    pub const ATTRIBUTES: [&'static str; 4] = ["age", "sex", "working", "pub_transport"];
+
+    /// Value of the named population column, in the same format as the csv file
+    pub fn get_attribute(&self, name: &str) -> Option<String> {
+        match name {
+            "age" => Some(self.age.clone()),
+            "sex" => self.sex.clone(),
+            "working" => Some(PopulationRecord::bool_to_string(self.working)),
+            "pub_transport" => Some(PopulationRecord::bool_to_string(self.pub_transport)),
+            _ => None
+        }
+    }
+
+    fn bool_to_string(value: bool) -> String {
+        if value { "True".to_string() } else { "False".to_string() }
+    }
+
+    /// The lower bound of an age band like "5-9" or "80+"
+    pub fn age_band_start(&self) -> Option<i32> {
+        self.age.split(|c: char| !c.is_ascii_digit()).next()
+            .and_then(|age| age.parse::<i32>().ok())
+    }
+
+    /// Citizens who don't work, in an age band that starts at school age
+    pub fn is_student(&self) -> bool {
+        match self.age_band_start() {
+            Some(age) => !self.working && constants::SCHOOL_AGE_START <= age && age <= constants::SCHOOL_AGE_END,
+            None => false
+        }
+    }
+}
+
 /// Deserialize bool from String with custom value mapping
 fn bool_from_string<'de, D>(deserializer: D) -> Result<bool, D::Error>
     where
@@ -64,32 +103,64 @@ pub enum WorkStatus {
     Normal,
     Essential,
     HospitalStaff { work_start_at: i32 },
+    Student,
     NA,
 }
 
+/// What a citizen who isn't an essential worker gives up during a lockdown
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum LockdownRestriction {
+    None,
+    SkipsWork,
+    StaysHome,
+}
+
+/// The neighbours a citizen met at `met_at` in an hour: the infectious ones while the citizen was susceptible, the
+/// one who exposed them if any, and everyone if all contacts are being recorded
+#[derive(Default)]
+pub struct Contacts {
+    record_all: bool,
+    pub met_at: Option<Point>,
+    pub neighbours: Vec<Uuid>,
+    pub infectious: Vec<Uuid>,
+    pub infector: Option<Uuid>,
+}
+
+impl Contacts {
+    pub fn new(record_all: bool) -> Contacts {
+        Contacts { record_all, ..Contacts::default() }
+    }
+}
+
 #[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub struct Citizen {
     pub id: Uuid,
     immunity: i32,
     pub home_location: Area,
     pub work_location: Area,
-    vaccinated: bool,
+    vaccination: Option<Vaccination>,
     pub uses_public_transport: bool,
     working: bool,
-    hospitalized: bool,
+    bed: Option<Bed>,
+    /// Needed a hospital bed and did not get one
+    turned_away: bool,
     pub transport_location: Point,
     pub state_machine: DiseaseStateMachine,
-    isolated: bool,
+    lockdown_restriction: LockdownRestriction,
     current_area: Area,
     work_status: WorkStatus,
     work_quarantined: bool,
+    kept_home: bool,
+    quarantined_until: Option<i32>,
+    disease_profile: usize,
+    age_band_start: Option<i32>,
 }
 
 impl Citizen {
     pub fn new(home_location: Area, work_location: Area, transport_location: Point,
                uses_public_transport: bool, working: bool, work_status: WorkStatus, rng: &mut impl rand::RngCore) -> Citizen {
-        Citizen::new_with_id(Uuid::new_v4(), home_location, work_location, transport_location, uses_public_transport,
-                             working, work_status, rng)
+        Citizen::new_with_id(Citizen::generate_id(rng), home_location, work_location, transport_location,
+                             uses_public_transport, working, work_status, rng)
     }
 
     pub fn new_with_id(id: Uuid, home_location: Area, work_location: Area, transport_location: Point,
@@ -102,15 +173,20 @@ impl Citizen {
             home_location,
             work_location,
             transport_location,
-            vaccinated: false,
+            vaccination: None,
             uses_public_transport,
             working,
-            hospitalized: false,
+            bed: None,
+            turned_away: false,
             state_machine: DiseaseStateMachine::new(),
-            isolated: false,
+            lockdown_restriction: LockdownRestriction::None,
             current_area: home_location,
             work_status,
             work_quarantined: false,
+            kept_home: false,
+            quarantined_until: None,
+            disease_profile: DiseaseProfiles::DEFAULT_PROFILE,
+            age_band_start: None,
         }
     }
 
@@ -121,63 +197,115 @@ impl Citizen {
             immunity: traveller.immunity,
             home_location,
             work_location,
-            vaccinated: traveller.vaccinated,
+            vaccination: traveller.vaccination,
             uses_public_transport: traveller.uses_public_transport,
             working: false,
-            hospitalized: false,
+            bed: None,
+            turned_away: false,
             transport_location,
             state_machine: traveller.state_machine,
-            isolated: false,
+            lockdown_restriction: LockdownRestriction::None,
             current_area,
             work_status: WorkStatus::NA {},
             work_quarantined: false,
+            kept_home: false,
+            quarantined_until: None,
+            disease_profile: DiseaseProfiles::DEFAULT_PROFILE,
+            age_band_start: None,
         }
     }
 
     pub fn from_record(record: PopulationRecord, home_location: Area, work_location: Area,
-                       transport_location: Point, rng: &mut impl rand::RngCore) -> Citizen {
+                       transport_location: Point, disease_profiles: &DiseaseProfiles, rng: &mut impl rand::RngCore) -> Citizen {
         let disease_randomness_factor = Citizen::generate_disease_randomness_factor(rng);
-        let work_status = Citizen::derive_work_status(record.working, rng);
+        let work_status = if record.is_student() {
+            WorkStatus::Student {}
+        } else {
+            Citizen::derive_work_status(record.working, rng)
+        };
+        let disease_profile = disease_profiles.profile_for(&record);
+        let age_band_start = record.age_band_start();
 
         Citizen {
-            id: Uuid::new_v4(),
+            id: Citizen::generate_id(rng),
             immunity: disease_randomness_factor,
             home_location,
             work_location,
             transport_location,
-            vaccinated: false,
+            vaccination: None,
             uses_public_transport: record.pub_transport,
             working: record.working,
-            hospitalized: false,
+            bed: None,
+            turned_away: false,
             state_machine: DiseaseStateMachine::new(),
-            isolated: false,
+            lockdown_restriction: LockdownRestriction::None,
             current_area: home_location,
             work_status,
             work_quarantined: false,
+            kept_home: false,
+            quarantined_until: None,
+            disease_profile,
+            age_band_start,
         }
     }
 
-    pub fn get_infection_transmission_rate(&self, disease: &Disease) -> f64 {
-        disease.get_current_transmission_rate(self.state_machine.get_infection_day() + self.immunity)
+    /// The disease as it affects this citizen, after applying any overrides for their population traits
+    pub fn get_disease<'a>(&self, disease_profiles: &'a DiseaseProfiles) -> &'a Disease {
+        disease_profiles.get(self.disease_profile, self.state_machine.get_strain())
     }
 
-    pub fn set_vaccination(&mut self, vaccinated: bool) {
-        self.vaccinated = vaccinated;
+    pub fn get_infection_transmission_rate(&self, disease_profiles: &DiseaseProfiles) -> f64 {
+        if let (Some(model), Some(compartment)) = (disease_profiles.get_model(), self.state_machine.get_compartment()) {
+            return model.get(compartment).transmission_rate;
+        }
+        self.get_disease(disease_profiles).get_current_transmission_rate(self.state_machine.get_infection_day() + self.immunity)
+    }
+
+    /// Gives a dose of the vaccine, the next one of the course if already vaccinated
+    pub fn vaccinate(&mut self, vaccine: Vaccine, hour: i32) {
+        match self.vaccination.as_mut() {
+            Some(vaccination) => vaccination.add_dose(hour),
+            None => self.vaccination = Some(Vaccination::new(vaccine, hour))
+        }
+    }
+
+    pub fn get_vaccination(&self) -> Option<Vaccination> {
+        self.vaccination
     }
 
     pub fn can_move(&self) -> bool {
-        if self.state_machine.is_symptomatic() || self.hospitalized || self.state_machine.is_deceased() || self.isolated {
+        if self.state_machine.is_symptomatic() || self.is_hospitalized() || self.state_machine.is_deceased()
+            || self.lockdown_restriction == LockdownRestriction::StaysHome || self.is_quarantined() {
             return false;
         }
         true
     }
 
-    pub fn set_isolation(&mut self, state: bool) {
-        self.isolated = state;
+    pub fn set_lockdown_restriction(&mut self, restriction: LockdownRestriction) {
+        self.lockdown_restriction = restriction;
     }
 
-    pub fn is_isolated(&self) -> bool {
-        self.isolated
+    /// Stays at home instead of going out, e.g. students and their caregivers while schools are closed
+    pub fn set_kept_home(&mut self, state: bool) {
+        self.kept_home = state;
+    }
+
+    /// Stays at home until `hour`, e.g. after testing positive or being traced as a contact
+    pub fn quarantine(&mut self, until: i32) {
+        self.quarantined_until = Some(until);
+    }
+
+    pub fn is_quarantined(&self) -> bool {
+        self.quarantined_until.is_some()
+    }
+
+    /// Only known for citizens of a csv population
+    pub fn get_age_band_start(&self) -> Option<i32> {
+        self.age_band_start
+    }
+
+    pub fn get_work_status(&self) -> WorkStatus {
+        self.work_status
     }
 
     pub fn is_working(&self) -> bool {
@@ -189,7 +317,17 @@ impl Citizen {
     }
 
     pub fn is_vaccinated(&self) -> bool {
-        self.vaccinated
+        self.vaccination.is_some()
+    }
+
+    /// Builds a random (v4) uuid from the simulation rng instead of the OS, so that ids are reproducible for a seed
+    fn generate_id(rng: &mut impl rand::RngCore) -> Uuid {
+        let mut bytes = [0u8; 16];
+        rng.fill_bytes(&mut bytes);
+        Builder::from_bytes(bytes)
+            .set_variant(Variant::RFC4122)
+            .set_version(Version::Random)
+            .build()
     }
 
     fn generate_disease_randomness_factor(rng: &mut impl rand::RngCore) -> i32 {
@@ -197,188 +335,296 @@ impl Citizen {
         *option.unwrap()
     }
 
+    /// The neighbours the citizen met are added to `contacts`
     pub fn perform_operation(&mut self, cell: Point, simulation_hour: i32, grid: &Grid, map: &AgentLocationMap,
-                             rng: &mut impl rand::RngCore, disease: &Disease) -> Point {
-        self.routine(cell, simulation_hour, grid, map, rng, disease)
+                             rng: &mut impl rand::RngCore, disease_profiles: &DiseaseProfiles, schedule: &Schedule,
+                             contacts: &mut Contacts) -> Point {
+        self.routine(cell, simulation_hour, grid, map, rng, disease_profiles, schedule, contacts)
     }
 
     fn routine(&mut self, cell: Point, simulation_hour: i32, grid: &Grid, map: &AgentLocationMap,
-               rng: &mut impl rand::RngCore, disease: &Disease) -> Point {
-        let mut new_cell = cell;
-
+               rng: &mut impl rand::RngCore, disease_profiles: &DiseaseProfiles, schedule: &Schedule,
+               contacts: &mut Contacts) -> Point {
+        if self.quarantined_until.map_or(false, |until| simulation_hour >= until) {
+            self.quarantined_until = None;
+        }
         let current_hour = simulation_hour % constants::NUMBER_OF_HOURS;
         match current_hour {
             constants::ROUTINE_START_TIME => {
                 self.update_infection_day();
-                new_cell = self.hospitalize(cell, &grid.hospital_area, map, rng,disease);
-            }
-            constants::SLEEP_START_TIME..=constants::SLEEP_END_TIME => {
-                if !self.is_hospital_staff() {
-                    self.current_area = self.home_location;
-                }
+                let new_cell = self.hospitalize(cell, &grid.hospital_area, map, rng, disease_profiles);
+                self.record_neighbours(new_cell, map, contacts);
+                new_cell
             }
             constants::ROUTINE_END_TIME => {
-                new_cell = self.deceased(map, cell, rng, disease)
+                let new_cell = self.deceased(map, cell, rng, disease_profiles);
+                self.record_neighbours(new_cell, map, contacts);
+                new_cell
             }
             _ => {
-                new_cell = self.perform_movements(cell, current_hour, simulation_hour, grid, map, rng, disease);
+                self.perform_movements(cell, simulation_hour, grid, map, rng, disease_profiles, schedule, contacts)
             }
         }
-        new_cell
     }
 
-    fn is_hospital_staff(&self) -> bool {
+    pub fn is_essential_worker(&self) -> bool {
         return match self.work_status {
-            WorkStatus::HospitalStaff { .. } => true,
+            WorkStatus::Essential {} => true,
             _ => false
         };
     }
 
-    pub fn is_essential_worker(&self) -> bool {
+    pub fn is_student(&self) -> bool {
         return match self.work_status {
-            WorkStatus::Essential {} => true,
+            WorkStatus::Student {} => true,
             _ => false
         };
     }
 
-    fn perform_movements(&mut self, cell: Point, hour_of_day: i32, simulation_hr: i32, grid: &Grid,
-                         map: &AgentLocationMap, rng: &mut impl rand::RngCore, disease: &Disease) -> Point {
-        let mut new_cell = cell;
-        match self.work_status {
-            WorkStatus::Normal {} | WorkStatus::Essential {} => {
-                match hour_of_day {
-                    constants::ROUTINE_TRAVEL_START_TIME | constants::ROUTINE_TRAVEL_END_TIME => {
-                        if self.uses_public_transport {
-                            new_cell = self.goto_area(grid.transport_area, map, cell, rng);
-                            self.current_area = grid.transport_area;
-                        } else {
-                            new_cell = self.move_agent_from(map, cell, rng);
-                        }
-                    }
-                    constants::ROUTINE_WORK_TIME => {
-                        new_cell = self.goto_area(self.work_location, map, cell, rng);
-                        self.current_area = self.work_location;
-                    }
-                    constants::ROUTINE_WORK_END_TIME => {
-                        new_cell = self.goto_area(self.home_location, map, cell, rng);
-                        self.current_area = self.home_location;
-                    }
-                    _ => {
-                        new_cell = self.move_agent_from(map, cell, rng);
-                    }
-                }
-                self.update_infection_dynamics(new_cell, &map, simulation_hr, rng, &disease);
+    fn perform_movements(&mut self, cell: Point, simulation_hr: i32, grid: &Grid, map: &AgentLocationMap,
+                         rng: &mut impl rand::RngCore, disease_profiles: &DiseaseProfiles, schedule: &Schedule,
+                         contacts: &mut Contacts) -> Point {
+        let scheduled = schedule.activity_at(&self.work_status, simulation_hr);
+        match scheduled {
+            Some((Activity::Sleep, _)) => {
+                self.current_area = self.home_location;
+                self.record_neighbours(cell, map, contacts);
+                return cell;
+            }
+            Some((Activity::Rest, _)) => {
+                self.record_neighbours(cell, map, contacts);
+                return cell;
+            }
+            _ => {}
+        }
+        if self.is_quarantined() && !self.is_hospitalized() {
+            let new_cell = self.stay_home(cell, map, rng);
+            self.update_infection_dynamics(new_cell, map, simulation_hr, rng, disease_profiles, contacts);
+            return new_cell;
+        }
+        let scheduled = match scheduled {
+            Some((_, starts)) if self.kept_home => Some((Activity::Home, starts)),
+            Some((Activity::Work, starts)) if self.lockdown_restriction == LockdownRestriction::SkipsWork => {
+                Some((Activity::Home, starts))
             }
+            other => other
+        };
 
-            WorkStatus::HospitalStaff { work_start_at } => {
-                if simulation_hr - work_start_at == (constants::HOURS_IN_A_DAY * constants::QUARANTINE_DAYS) {
-                    self.work_quarantined = true;
-                    return new_cell;
-                }
+        if let WorkStatus::HospitalStaff { work_start_at } = self.work_status {
+            if simulation_hr - work_start_at == (constants::HOURS_IN_A_DAY * constants::QUARANTINE_DAYS) {
+                self.work_quarantined = true;
+                return cell;
+            }
 
-                if simulation_hr - work_start_at == (constants::HOURS_IN_A_DAY * constants::QUARANTINE_DAYS * 2) {
-                    new_cell = self.goto_area(self.home_location, map, cell, rng);
-                    self.current_area = self.home_location;
-                    self.work_status = WorkStatus::HospitalStaff { work_start_at: (simulation_hr + constants::HOURS_IN_A_DAY * constants::QUARANTINE_DAYS) };
-                    return new_cell;
-                }
+            if simulation_hr - work_start_at == (constants::HOURS_IN_A_DAY * constants::QUARANTINE_DAYS * 2) {
+                let new_cell = self.goto_area(self.home_location, map, cell, rng);
+                self.current_area = self.home_location;
+                self.work_status = WorkStatus::HospitalStaff { work_start_at: (simulation_hr + constants::HOURS_IN_A_DAY * constants::QUARANTINE_DAYS) };
+                return new_cell;
+            }
+        }
 
-                match hour_of_day {
-                    constants::ROUTINE_WORK_TIME => {
-                        if self.current_area != grid.hospital_area && work_start_at <= simulation_hr {
-                            new_cell = self.goto_area(grid.hospital_area, map, cell, rng);
-                            self.current_area = grid.hospital_area;
-                            self.work_status = WorkStatus::HospitalStaff { work_start_at: simulation_hr };
-                        }
-                        self.work_quarantined = false;
-                    }
-                    constants::ROUTINE_WORK_END_TIME => {
-                        self.work_quarantined = true;
-                    }
-                    _ => {
-                        if !self.work_quarantined && self.can_move() {
-                            new_cell = self.move_agent_from(map, cell, rng);
-                        }
-                    }
+        let new_cell = match scheduled {
+            Some((activity, true)) => self.start_activity(activity, cell, simulation_hr, grid, map, rng),
+            _ => {
+                if !self.work_quarantined {
+                    self.move_agent_from(map, cell, rng)
+                } else {
+                    cell
                 }
-                self.update_infection_dynamics(new_cell, &map, simulation_hr, rng, &disease);
             }
+        };
+        self.update_infection_dynamics(new_cell, &map, simulation_hr, rng, disease_profiles, contacts);
+        new_cell
+    }
 
-            WorkStatus::NA {} => {
-                match hour_of_day {
-                    constants::ROUTINE_WORK_TIME => {
-                        new_cell = self.goto_area(grid.housing_area, map, cell, rng);
-                        self.current_area = grid.housing_area;
-                    }
-                    constants::NON_WORKING_TRAVEL_END_TIME => {
-                        new_cell = self.goto_area(self.home_location, map, cell, rng);
-                        self.current_area = self.home_location;
-                    }
-
-                    _ => {
-                        new_cell = self.move_agent_from(map, cell, rng);
+    fn start_activity(&mut self, activity: Activity, cell: Point, simulation_hr: i32, grid: &Grid, map: &AgentLocationMap,
+                      rng: &mut impl rand::RngCore) -> Point {
+        let mut new_cell = cell;
+        match activity {
+            Activity::Travel => {
+                if self.uses_public_transport {
+                    new_cell = self.goto_area(grid.transport_area, map, cell, rng);
+                    self.current_area = grid.transport_area;
+                } else {
+                    new_cell = self.move_agent_from(map, cell, rng);
+                }
+            }
+            Activity::Work => {
+                new_cell = self.goto_area(self.work_location, map, cell, rng);
+                self.current_area = self.work_location;
+            }
+            Activity::Home => {
+                new_cell = self.goto_area(self.home_location, map, cell, rng);
+                self.current_area = self.home_location;
+            }
+            Activity::School => {
+                new_cell = self.goto_area(self.work_location, map, cell, rng);
+                self.current_area = self.work_location;
+            }
+            Activity::Housing => {
+                new_cell = self.goto_area(grid.housing_area, map, cell, rng);
+                self.current_area = grid.housing_area;
+            }
+            Activity::Shift => {
+                if let WorkStatus::HospitalStaff { work_start_at } = self.work_status {
+                    if self.current_area != grid.hospital_area && work_start_at <= simulation_hr {
+                        new_cell = self.goto_area(grid.hospital_area, map, cell, rng);
+                        self.current_area = grid.hospital_area;
+                        self.work_status = WorkStatus::HospitalStaff { work_start_at: simulation_hr };
                     }
                 }
-                self.update_infection_dynamics(new_cell, &map, simulation_hr, rng, &disease);
+                self.work_quarantined = false;
+            }
+            Activity::OffShift => {
+                self.work_quarantined = true;
             }
+            Activity::Sleep | Activity::Rest => {}
         }
         new_cell
     }
 
-    fn update_infection_dynamics(&mut self, cell: Point, map: &AgentLocationMap,
-                                 sim_hr: i32, rng: &mut impl rand::RngCore, disease: &Disease) {
-        self.update_exposure(cell, map, sim_hr, rng, disease);
-        self.update_infection(sim_hr, rng, &disease);
-        self.update_infection_severity(sim_hr, rng, disease);
+    /// Quarantined citizens go straight home from wherever they are, once there is room, and stay there
+    fn stay_home(&mut self, cell: Point, map: &AgentLocationMap, rng: &mut impl rand::RngCore) -> Point {
+        self.current_area = self.home_location;
+        if self.home_location.contains(&cell) {
+            return cell;
+        }
+        map.move_agent(cell, self.home_location.get_random_point(rng))
+    }
+
+    fn update_infection_dynamics(&mut self, cell: Point, map: &AgentLocationMap, sim_hr: i32,
+                                 rng: &mut impl rand::RngCore, disease_profiles: &DiseaseProfiles, contacts: &mut Contacts) {
+        self.update_exposure(cell, map, sim_hr, rng, disease_profiles, contacts);
+        self.update_infection(sim_hr, rng, disease_profiles);
+        self.update_infection_severity(sim_hr, rng, disease_profiles);
     }
 
     fn update_infection_day(&mut self) {
         if self.state_machine.is_infected() {
             self.state_machine.increment_infection_day();
         }
+        self.state_machine.update_immunity();
     }
 
     fn hospitalize(&mut self, cell: Point, hospital: &Area, map: &AgentLocationMap, rng: &mut impl rand::RngCore,
-                   disease: &Disease) -> Point {
+                   disease_profiles: &DiseaseProfiles) -> Point {
         let mut new_cell = cell;
-        if self.state_machine.is_infected() && !self.hospitalized {
-            let to_be_hospitalized = self.state_machine.hospitalize(disease, self.immunity);
-            if to_be_hospitalized {
-                let (is_hospitalized, new_location) = AgentLocationMap::goto_hospital(map, hospital, cell, self, rng);
-                new_cell = new_location;
-                if is_hospitalized {
-                    self.hospitalized = true;
+        if self.state_machine.is_infected() {
+            let needed = match disease_profiles.get_model() {
+                Some(model) => self.state_machine.needs_bed(model),
+                None if self.state_machine.hospitalize(self.get_disease(disease_profiles), self.immunity) => {
+                    Some(if self.state_machine.is_critical() { Bed::Icu } else { Bed::General })
+                }
+                None => None
+            };
+            if let Some(needed) = needed.filter(|needed| self.bed != Some(*needed)) {
+                if self.is_hospitalized() {
+                    self.bed = Some(needed);
+                } else {
+                    let (is_hospitalized, new_location) = AgentLocationMap::goto_hospital(map, hospital, cell, self, rng);
+                    new_cell = new_location;
+                    if is_hospitalized {
+                        self.bed = Some(needed);
+                    } else {
+                        self.turned_away = true;
+                    }
                 }
             }
         }
         new_cell
     }
 
-    fn update_infection_severity(&mut self, sim_hr: i32, rng: &mut impl rand::RngCore, disease: &Disease) {
-        if self.state_machine.is_pre_symptomatic() {
-            self.state_machine.change_infection_severity(sim_hr, rng, disease);
+    /// Gets the bed asked for when going to hospital, or moving to intensive care
+    pub fn admit(&mut self, bed: Bed) {
+        self.bed = Some(bed);
+        self.turned_away = false;
+    }
+
+    /// Stays in the bed they had, if any, for lack of the one they need
+    pub fn turn_away(&mut self, previous_bed: Option<Bed>) {
+        self.bed = previous_bed;
+        self.turned_away = true;
+    }
+
+    fn update_infection_severity(&mut self, sim_hr: i32, rng: &mut impl rand::RngCore, disease_profiles: &DiseaseProfiles) {
+        if self.state_machine.is_pre_symptomatic() || self.state_machine.is_infected_severe() {
+            let protection = self.vaccination.map_or(0.0, |v| v.severity_protection(sim_hr));
+            self.state_machine.change_infection_severity(sim_hr, rng, self.get_disease(disease_profiles), protection);
         }
     }
 
-    fn update_infection(&mut self, sim_hr: i32, rng: &mut impl rand::RngCore, disease: &Disease) {
-        if self.state_machine.is_exposed() {
-            self.state_machine.infect(rng, sim_hr, &disease);
+    fn update_infection(&mut self, sim_hr: i32, rng: &mut impl rand::RngCore, disease_profiles: &DiseaseProfiles) {
+        if let Some(model) = disease_profiles.get_model() {
+            self.state_machine.progress(model, sim_hr, !self.turned_away, rng);
+        } else if self.state_machine.is_exposed() {
+            self.state_machine.infect(rng, sim_hr, self.get_disease(disease_profiles));
         }
     }
 
+    /// Records everyone next to the citizen when all contacts are being recorded, in the hours they can not be exposed
+    fn record_neighbours(&self, cell: Point, map: &AgentLocationMap, contacts: &mut Contacts) {
+        if !contacts.record_all || self.state_machine.is_deceased() {
+            return;
+        }
+        contacts.neighbours.extend(self.current_area.get_neighbors_of(cell)
+            .filter(|p| map.is_point_in_grid(p))
+            .filter_map(|cell| map.get_agent_for(&cell))
+            .filter(|neighbor| !neighbor.state_machine.is_deceased())
+            .map(|neighbor| neighbor.id));
+        contacts.met_at = Some(cell);
+    }
+
+    /// Every infectious neighbour is a contact, including the ones met after being exposed to another. With variants,
+    /// recovered citizens can be exposed to the strains they are not fully protected against
     fn update_exposure(&mut self, cell: Point, map: &AgentLocationMap, sim_hr: i32, rng: &mut impl rand::RngCore,
-                       disease: &Disease) {
-        if self.state_machine.is_susceptible() && !self.work_quarantined && !self.vaccinated {
+                       disease_profiles: &DiseaseProfiles, contacts: &mut Contacts) {
+        let susceptibility = 1.0 - self.vaccination.map_or(0.0, |v| v.infection_protection(sim_hr));
+        let recovered = self.state_machine.is_recovered() && disease_profiles.has_variants();
+        let can_be_exposed = (self.state_machine.is_susceptible() || recovered) && !self.work_quarantined && susceptibility > 0.0;
+        let mut infector_strain = 0;
+        let recording = contacts.record_all && !self.state_machine.is_deceased();
+        if can_be_exposed || recording {
             let neighbours = self.current_area.get_neighbors_of(cell);
 
-            let neighbor_that_spreads_infection = neighbours
+            neighbours
                 .filter(|p| map.is_point_in_grid(p))
                 .filter_map(|cell| { map.get_agent_for(&cell) })
-                .filter(|agent| agent.state_machine.is_infected() && !agent.hospitalized)
-                .find(|neighbor| rng.gen_bool(neighbor.get_infection_transmission_rate(disease)));
-
-            if neighbor_that_spreads_infection.is_some() {
-                self.state_machine.expose(sim_hr);
+                .for_each(|neighbor| {
+                    if recording && !neighbor.state_machine.is_deceased() {
+                        contacts.neighbours.push(neighbor.id);
+                    }
+                    if can_be_exposed && neighbor.state_machine.is_infected() && !neighbor.is_hospitalized()
+                        && !neighbor.is_quarantined() {
+                        let strain = neighbor.state_machine.get_strain();
+                        let susceptibility = if recovered {
+                            let protection = disease_profiles.protection(self.state_machine.recovered_strains(), strain);
+                            susceptibility * (1.0 - protection)
+                        } else {
+                            susceptibility
+                        };
+                        if susceptibility <= 0.0 {
+                            return;
+                        }
+                        contacts.infectious.push(neighbor.id);
+                        if contacts.infector.is_none()
+                            && rng.gen_bool(neighbor.get_infection_transmission_rate(disease_profiles) * susceptibility) {
+                            contacts.infector = Some(neighbor.id);
+                            infector_strain = strain;
+                        }
+                    }
+                });
+            contacts.met_at = Some(cell);
+
+            if contacts.infector.is_some() {
+                match disease_profiles.get_model() {
+                    Some(model) => {
+                        if !self.state_machine.expose_in(model, sim_hr, rng) {
+                            contacts.infector = None;
+                        }
+                    }
+                    None => self.state_machine.expose_to(sim_hr, infector_strain)
+                }
             }
         }
     }
@@ -389,8 +635,8 @@ impl Citizen {
         let mut override_movement = false;
 
         match self.work_status {
-            WorkStatus::Normal {} | WorkStatus::Essential {} => {
-                if self.work_location.contains(&cell) && target_area == self.home_location && (self.state_machine.is_mild_symptomatic() || self.state_machine.is_infected_severe()) {
+            WorkStatus::Normal {} | WorkStatus::Essential {} | WorkStatus::Student {} => {
+                if self.work_location.contains(&cell) && target_area == self.home_location && (self.state_machine.is_mild_symptomatic() || self.state_machine.is_infected_severe() || self.state_machine.is_critical()) {
                     override_movement = true;
                 }
             }
@@ -399,7 +645,7 @@ impl Citizen {
         if !self.can_move() && !override_movement {
             return cell;
         }
-        if self.working {
+        if self.working || self.is_student() {
             let mut new_cell: Point = target_area.get_random_point(rng);
             if !map.is_cell_vacant(&new_cell) {
                 new_cell = cell;
@@ -411,17 +657,25 @@ impl Citizen {
     }
 
     fn deceased(&mut self, map: &AgentLocationMap, cell: Point, rng: &mut impl rand::RngCore,
-                disease: &Disease) -> Point {
+                disease_profiles: &DiseaseProfiles) -> Point {
         let mut new_cell = cell;
-        if self.state_machine.is_infected() {
-            let result = self.state_machine.decease(rng, disease);
+        if let Some(model) = disease_profiles.get_model() {
+            // citizens move through the model every hour, and leave the hospital at the end of the day
+            if self.state_machine.needs_bed(model).is_none() && (self.is_hospitalized() || self.turned_away) {
+                if self.is_hospitalized() && !self.state_machine.is_deceased() {
+                    new_cell = map.move_agent(cell, self.home_location.get_random_point(rng));
+                }
+                self.bed = None;
+                self.turned_away = false;
+            }
+        } else if self.state_machine.is_infected() {
+            let result = self.state_machine.decease(rng, self.get_disease(disease_profiles), !self.turned_away);
             if result.1 == 1 {
                 new_cell = map.move_agent(cell, self.home_location.get_random_point(rng));
             }
             if result != (0, 0) {
-                if self.hospitalized {
-                    self.hospitalized = false;
-                }
+                self.bed = None;
+                self.turned_away = false;
             }
         }
         new_cell
@@ -458,7 +712,7 @@ impl Citizen {
     fn derive_work_status(is_working: bool, rng: &mut impl rand::RngCore) -> WorkStatus {
         if is_working {
             if rng.gen_bool(constants::HOSPITAL_STAFF_PERCENTAGE) {
-                return WorkStatus::HospitalStaff { work_start_at: constants::ROUTINE_WORK_TIME };
+                return WorkStatus::HospitalStaff { work_start_at: 0 };
             }
             return WorkStatus::Normal {};
         }
@@ -466,7 +720,20 @@ impl Citizen {
     }
 
     pub fn is_hospitalized(&self) -> bool {
-        self.hospitalized
+        self.bed.is_some()
+    }
+
+    pub fn get_bed(&self) -> Option<Bed> {
+        self.bed
+    }
+
+    pub fn is_turned_away(&self) -> bool {
+        self.turned_away
+    }
+
+    #[cfg(test)]
+    pub fn is_kept_home(&self) -> bool {
+        self.kept_home
     }
 
     #[cfg(test)]
@@ -560,6 +827,12 @@ pub fn set_starting_infections(agent_list: &mut Vec<Citizen>, start_infections:
 #[cfg(test)]
 mod tests {
     use rand::thread_rng;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64;
+    use crate::compartment_model::CompartmentModel;
+    use crate::config::LocationStore;
+    use crate::disease::DiseaseOverride;
+    use crate::geography::define_geography;
     use super::*;
 
     fn before_each() -> Vec<Citizen> {
@@ -613,4 +886,169 @@ mod tests {
         assert_eq!(4, actual_severe);
         assert_eq!(5, actual_exposed);
     }
+
+    #[test]
+    fn should_use_disease_override_matching_population_record() {
+        let mut rng = thread_rng();
+        let home_location = Area::new(Point::new(0, 0), Point::new(10, 10));
+        let work_location = Area::new(Point::new(11, 0), Point::new(20, 20));
+        let base = Disease::new(0, 0, 0, 0, 0, 0.0, 0.0, 0.0, 0.0, 0.0, 0, 0);
+        let elderly = Disease::new(0, 0, 0, 0, 0, 0.0, 0.0, 1.0, 0.0, 0.0, 0, 0);
+        let disease_override = DiseaseOverride::new(String::from("age"), vec!["80+".to_string()], elderly.clone());
+        let profiles = DiseaseProfiles::new(base.clone(), vec![disease_override]);
+
+        let old = PopulationRecord { ind: 0, age: "80+".to_string(), sex: None, working: false, pub_transport: false };
+        let young = PopulationRecord { ind: 1, age: "20-24".to_string(), sex: None, working: true, pub_transport: false };
+        let old_citizen = Citizen::from_record(old, home_location, work_location, Point::new(2, 2), &profiles, &mut rng);
+        let young_citizen = Citizen::from_record(young, home_location, work_location, Point::new(2, 2), &profiles, &mut rng);
+
+        assert_eq!(*old_citizen.get_disease(&profiles), elderly);
+        assert_eq!(*young_citizen.get_disease(&profiles), base);
+    }
+
+    #[test]
+    fn should_kill_more_of_the_cohort_with_a_deadlier_override() {
+        let mut rng = Pcg64::seed_from_u64(7);
+        let home_location = Area::new(Point::new(0, 0), Point::new(99, 99));
+        let work_location = Area::new(Point::new(0, 0), Point::new(99, 99));
+        let base = Disease::new(0, 0, 0, 0, 0, 0.0, 0.0, 0.1, 0.0, 0.0, 0, 0);
+        let elderly = Disease::new(0, 0, 0, 0, 0, 0.0, 0.0, 0.6, 0.0, 0.0, 0, 0);
+        let disease_override = DiseaseOverride::new(String::from("age"), vec!["80+".to_string()], elderly);
+        let profiles = DiseaseProfiles::new(base, vec![disease_override]);
+
+        let mut citizens: Vec<Citizen> = (0..400).map(|ind| {
+            let age = if ind % 2 == 0 { "80+" } else { "30-34" };
+            let record = PopulationRecord { ind, age: age.to_string(), sex: None, working: false, pub_transport: false };
+            let mut citizen = Citizen::from_record(record, home_location, work_location, Point::new(0, 0), &profiles, &mut rng);
+            citizen.state_machine.set_severe_infected();
+            citizen
+        }).collect();
+        let cells: Vec<Point> = (0..400).map(|i| Point::new(i % 100, i / 100)).collect();
+        let map = AgentLocationMap::new(100, &citizens, &cells, LocationStore::HashMap);
+
+        for (citizen, cell) in citizens.iter_mut().zip(cells.iter()) {
+            citizen.deceased(&map, *cell, &mut rng, &profiles);
+        }
+
+        let deaths = |elderly: bool| citizens.iter()
+            .filter(|citizen| (citizen.get_age_band_start() == Some(80)) == elderly)
+            .filter(|citizen| citizen.state_machine.is_deceased())
+            .count();
+        assert!(deaths(true) > 2 * deaths(false));
+    }
+
+    #[test]
+    fn should_follow_schedule_of_work_status() {
+        let mut rng = thread_rng();
+        let schedule: Schedule = serde_json::from_str(r#"{"Normal": [{"activities": [
+            {"activity": "Work", "start": 20, "end": 4}, {"activity": "Sleep", "start": 8, "end": 15}]}]}"#).unwrap();
+        let grid = define_geography(100);
+        let home_location = Area::new(Point::new(0, 0), Point::new(2, 2));
+        let work_location = Area::new(Point::new(10, 10), Point::new(12, 12));
+        let profiles = DiseaseProfiles::new(Disease::new(0, 0, 0, 0, 0, 0.0, 0.0, 0.0, 0.0, 0.0, 0, 0), vec![]);
+        let mut citizen = Citizen::new(home_location, work_location, Point::new(1, 1), false, true, WorkStatus::Normal, &mut rng);
+        let map = AgentLocationMap::new(100, &[citizen], &[Point::new(1, 1)], LocationStore::HashMap);
+
+        let at_work = citizen.perform_operation(Point::new(1, 1), 20, &grid, &map, &mut rng, &profiles, &schedule, &mut Contacts::default());
+        assert!(work_location.contains(&at_work));
+
+        let asleep = citizen.perform_operation(at_work, 24 + 9, &grid, &map, &mut rng, &profiles, &schedule, &mut Contacts::default());
+        assert_eq!(asleep, at_work);
+        assert_eq!(citizen.current_area, home_location);
+    }
+
+    #[test]
+    fn should_record_household_contacts_while_asleep() {
+        let mut rng = thread_rng();
+        let schedule: Schedule = serde_json::from_str(r#"{"Normal": [{"activities": [
+            {"activity": "Sleep", "start": 1, "end": 8}]}]}"#).unwrap();
+        let grid = define_geography(100);
+        let home_location = Area::new(Point::new(0, 0), Point::new(2, 2));
+        let work_location = Area::new(Point::new(10, 10), Point::new(12, 12));
+        let profiles = DiseaseProfiles::new(Disease::new(0, 0, 0, 0, 0, 0.0, 0.0, 0.0, 0.0, 0.0, 0, 0), vec![]);
+        let mut citizen = Citizen::new(home_location, work_location, Point::new(1, 1), false, true, WorkStatus::Normal, &mut rng);
+        let partner = Citizen::new(home_location, work_location, Point::new(1, 2), false, true, WorkStatus::Normal, &mut rng);
+        let map = AgentLocationMap::new(100, &[citizen, partner], &[Point::new(1, 1), Point::new(1, 2)], LocationStore::HashMap);
+
+        let mut contacts = Contacts::new(true);
+        citizen.perform_operation(Point::new(1, 1), 3, &grid, &map, &mut rng, &profiles, &schedule, &mut contacts);
+        assert_eq!(contacts.neighbours, vec![partner.id]);
+        assert_eq!(contacts.met_at, Some(Point::new(1, 1)));
+
+        let mut contacts = Contacts::new(true);
+        citizen.perform_operation(Point::new(1, 1), 24, &grid, &map, &mut rng, &profiles, &schedule, &mut contacts);
+        assert_eq!(contacts.neighbours, vec![partner.id]);
+
+        let mut contacts = Contacts::new(false);
+        citizen.perform_operation(Point::new(1, 1), 3, &grid, &map, &mut rng, &profiles, &schedule, &mut contacts);
+        assert!(contacts.neighbours.is_empty());
+    }
+
+    #[test]
+    fn should_not_be_infected_in_a_susceptible_compartment_without_transitions() {
+        let mut rng = thread_rng();
+        let model: CompartmentModel = serde_yaml::from_str(r#"
+compartments:
+  - {name: S, kind: Susceptible}
+  - {name: I, kind: Infected, dwell: 48, transmission_rate: 1.0}
+  - {name: R, kind: Recovered}
+transitions:
+  - {from: I, to: R}
+"#).unwrap();
+        let profiles = DiseaseProfiles::new(Disease::new(0, 0, 0, 0, 0, 0.0, 0.0, 0.0, 0.0, 0.0, 0, 0), vec![])
+            .with_model(Some(model.clone()));
+        let home_location = Area::new(Point::new(0, 0), Point::new(2, 2));
+        let work_location = Area::new(Point::new(10, 10), Point::new(12, 12));
+        let mut citizen = Citizen::new(home_location, work_location, Point::new(1, 1), false, true, WorkStatus::Normal, &mut rng);
+        citizen.state_machine.start_in(&model, &mut rng);
+        let mut infectious = Citizen::new(home_location, work_location, Point::new(1, 2), false, true, WorkStatus::Normal, &mut rng);
+        infectious.state_machine.set_mild_symptomatic();
+        infectious.state_machine.start_in(&model, &mut rng);
+        let map = AgentLocationMap::new(100, &[citizen, infectious], &[Point::new(1, 1), Point::new(1, 2)], LocationStore::HashMap);
+
+        let mut contacts = Contacts::default();
+        citizen.update_exposure(Point::new(1, 1), &map, 10, &mut rng, &profiles, &mut contacts);
+
+        assert_eq!(contacts.infectious, vec![infectious.id]);
+        assert_eq!(contacts.infector, None);
+        assert!(citizen.state_machine.is_susceptible());
+    }
+
+    #[test]
+    fn should_stay_home_instead_of_working_when_skipping_work_in_lockdown() {
+        let mut rng = thread_rng();
+        let grid = define_geography(100);
+        let home_location = Area::new(Point::new(0, 0), Point::new(2, 2));
+        let work_location = Area::new(Point::new(10, 10), Point::new(12, 12));
+        let profiles = DiseaseProfiles::new(Disease::new(0, 0, 0, 0, 0, 0.0, 0.0, 0.0, 0.0, 0.0, 0, 0), vec![]);
+        let mut citizen = Citizen::new(home_location, work_location, Point::new(1, 1), false, true, WorkStatus::Normal, &mut rng);
+        let map = AgentLocationMap::new(100, &[citizen], &[Point::new(1, 1)], LocationStore::HashMap);
+
+        citizen.set_lockdown_restriction(LockdownRestriction::SkipsWork);
+        let new_cell = citizen.perform_operation(Point::new(1, 1), 8, &grid, &map, &mut rng, &profiles, &Schedule::default(), &mut Contacts::default());
+
+        assert!(home_location.contains(&new_cell));
+        assert_eq!(citizen.current_area, home_location);
+    }
+
+    #[test]
+    fn should_make_students_of_school_age_citizens_who_dont_work() {
+        let mut rng = thread_rng();
+        let home_location = Area::new(Point::new(0, 0), Point::new(10, 10));
+        let classroom = Area::new(Point::new(11, 0), Point::new(15, 5));
+        let profiles = DiseaseProfiles::new(Disease::new(0, 0, 0, 0, 0, 0.0, 0.0, 0.0, 0.0, 0.0, 0, 0), vec![]);
+        let record = |age: &str, working: bool| PopulationRecord {
+            ind: 0, age: age.to_string(), sex: None, working, pub_transport: false,
+        };
+
+        assert!(record("5-9", false).is_student());
+        assert!(record("15-19", false).is_student());
+        assert!(!record("15-19", true).is_student());
+        assert!(!record("0-4", false).is_student());
+        assert!(!record("80+", false).is_student());
+
+        let student = Citizen::from_record(record("10-14", false), home_location, classroom, Point::new(2, 2), &profiles, &mut rng);
+        assert!(student.is_student());
+        assert_eq!(student.work_location, classroom);
+    }
 }
@@ -23,31 +23,132 @@ use crate::geography::{Area, Grid};
 use crate::geography::Point;
 
 use crate::agent::Citizen;
+use crate::checkpoint::ordered_map;
+use crate::config::LocationStore;
+use crate::dense_grid::DenseGrid;
 use crate::disease_state_machine::State;
 use crate::listeners::events::counts::Counts;
 use crate::travel_plan::Traveller;
-use std::collections::hash_map::{IterMut, Iter};
+use std::collections::hash_map;
 use std::collections::HashSet;
+use std::slice;
 use fnv::FnvHashMap;
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct AgentLocationMap {
     grid_size: i32,
-    agent_cell: FnvHashMap<Point, agent::Citizen>,
+    agent_cell: CitizenCells,
+}
+
+/// Where the citizens are kept, chosen by `LocationStore` in the config
+#[derive(Clone, Serialize, Deserialize)]
+enum CitizenCells {
+    HashMap(#[serde(with = "ordered_map")] FnvHashMap<Point, Citizen>),
+    Dense(DenseGrid),
+}
+
+impl CitizenCells {
+    fn with_capacity(store: LocationStore, grid_size: i32, capacity: usize) -> CitizenCells {
+        match store {
+            LocationStore::HashMap => CitizenCells::HashMap(FnvHashMap::with_capacity_and_hasher(capacity, Default::default())),
+            LocationStore::Dense => CitizenCells::Dense(DenseGrid::new(grid_size, capacity)),
+        }
+    }
+
+    fn store(&self) -> LocationStore {
+        match self {
+            CitizenCells::HashMap(_) => LocationStore::HashMap,
+            CitizenCells::Dense(_) => LocationStore::Dense,
+        }
+    }
+
+    fn get(&self, point: &Point) -> Option<&Citizen> {
+        match self {
+            CitizenCells::HashMap(map) => map.get(point),
+            CitizenCells::Dense(grid) => grid.get(point),
+        }
+    }
+
+    fn contains_key(&self, point: &Point) -> bool {
+        match self {
+            CitizenCells::HashMap(map) => map.contains_key(point),
+            CitizenCells::Dense(grid) => grid.contains(point),
+        }
+    }
+
+    fn insert(&mut self, point: Point, citizen: Citizen) -> Option<Citizen> {
+        match self {
+            CitizenCells::HashMap(map) => map.insert(point, citizen),
+            CitizenCells::Dense(grid) => grid.insert(point, citizen),
+        }
+    }
+
+    fn remove(&mut self, point: &Point) -> Option<Citizen> {
+        match self {
+            CitizenCells::HashMap(map) => map.remove(point),
+            CitizenCells::Dense(grid) => grid.remove(point),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            CitizenCells::HashMap(map) => map.len(),
+            CitizenCells::Dense(grid) => grid.len(),
+        }
+    }
+
+    fn clear(&mut self) {
+        match self {
+            CitizenCells::HashMap(map) => map.clear(),
+            CitizenCells::Dense(grid) => grid.clear(),
+        }
+    }
+}
+
+pub enum Iter<'a> {
+    HashMap(hash_map::Iter<'a, Point, Citizen>),
+    Dense(slice::Iter<'a, (Point, Citizen)>),
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a Point, &'a Citizen);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Iter::HashMap(iter) => iter.next(),
+            Iter::Dense(iter) => iter.next().map(|(point, citizen)| (point, citizen)),
+        }
+    }
+}
+
+pub enum IterMut<'a> {
+    HashMap(hash_map::IterMut<'a, Point, Citizen>),
+    Dense(slice::IterMut<'a, (Point, Citizen)>),
+}
+
+impl<'a> Iterator for IterMut<'a> {
+    type Item = (&'a Point, &'a mut Citizen);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            IterMut::HashMap(iter) => iter.next(),
+            IterMut::Dense(iter) => iter.next().map(|(point, citizen)| (&*point, citizen)),
+        }
+    }
 }
 
 impl AgentLocationMap {
     pub fn init_with_capacity(&mut self, size: usize) {
-        self.agent_cell = FnvHashMap::with_capacity_and_hasher(size, Default::default());
+        self.agent_cell = CitizenCells::with_capacity(self.agent_cell.store(), self.grid_size, size);
     }
 
-    pub fn new(grid_size: i32, agent_list: &[agent::Citizen], points: &[Point]) -> AgentLocationMap {
+    pub fn new(grid_size: i32, agent_list: &[agent::Citizen], points: &[Point], store: LocationStore) -> AgentLocationMap {
         debug!("{} agents and {} starting points", agent_list.len(), points.len());
-        let mut map: FnvHashMap<Point, agent::Citizen> = FnvHashMap::with_capacity_and_hasher(agent_list.len(), Default::default());
+        let mut agent_cell = CitizenCells::with_capacity(store, grid_size, agent_list.len());
         for i in 0..agent_list.len() {
-            map.insert(points[i], agent_list[i]);
+            agent_cell.insert(points[i], agent_list[i]);
         }
-        AgentLocationMap { grid_size, agent_cell: map }
+        AgentLocationMap { grid_size, agent_cell }
     }
 
     pub fn move_agent(&self, old_cell: Point, new_cell: Point) -> Point {
@@ -98,6 +199,14 @@ impl AgentLocationMap {
                 State::Recovered { .. } => { counts.update_recovered(-1) },
                 State::Deceased { .. } => { panic!("Deceased agent should not travel!") },
             }
+            if traveller.vaccination.is_some() {
+                counts.update_vaccinated(&traveller.state_machine.state, false, -1);
+            }
+            counts.update_reinfections(-traveller.state_machine.get_reinfections());
+            if let Some(compartment) = traveller.state_machine.get_compartment() {
+                counts.update_compartment(compartment, -1);
+            }
+            counts.update_strain(traveller.state_machine.get_strain(), &traveller.state_machine.state, false, -1);
             match self.agent_cell.remove(point) {
                 None => {
                     panic!("Trying to remove citizen {:?} from location {:?}, but no citizen is present at this location!",
@@ -142,6 +251,14 @@ impl AgentLocationMap {
                 State::Recovered { .. } => { counts.update_recovered(1) }
                 State::Deceased { .. } => { panic!("Should not receive deceased agent!") }
             }
+            if c.is_vaccinated() {
+                counts.update_vaccinated(&c.state_machine.state, false, 1);
+            }
+            counts.update_reinfections(c.state_machine.get_reinfections());
+            if let Some(compartment) = c.state_machine.get_compartment() {
+                counts.update_compartment(compartment, 1);
+            }
+            counts.update_strain(c.state_machine.get_strain(), &c.state_machine.state, false, 1);
             let p = self.random_starting_point(&grid.housing_area, rng);
             let result = self.agent_cell.insert(p, c);
             assert!(result.is_none());
@@ -161,20 +278,30 @@ impl AgentLocationMap {
         self.agent_cell.len() as i32
     }
 
-    pub fn iter(&self) -> Iter<'_, Point, Citizen> {
-        self.agent_cell.iter()
+    pub fn iter(&self) -> Iter<'_> {
+        match &self.agent_cell {
+            CitizenCells::HashMap(map) => Iter::HashMap(map.iter()),
+            CitizenCells::Dense(grid) => Iter::Dense(grid.iter()),
+        }
     }
 
-    pub fn iter_mut(&mut self) -> IterMut<'_, Point, Citizen> {
-        self.agent_cell.iter_mut()
+    pub fn iter_mut(&mut self) -> IterMut<'_> {
+        match &mut self.agent_cell {
+            CitizenCells::HashMap(map) => IterMut::HashMap(map.iter_mut()),
+            CitizenCells::Dense(grid) => IterMut::Dense(grid.iter_mut()),
+        }
     }
 
     pub fn clear(&mut self) {
         self.agent_cell.clear();
     }
 
-    pub fn get(&self, point: &Point) -> Option<&Citizen> {
-        self.agent_cell.get(point)
+    /// Rebuilds the map exactly as it would be restored from a checkpoint. A dense grid already iterates in the
+    /// order it is restored in.
+    pub fn rebuild(&mut self) {
+        if let CitizenCells::HashMap(map) = &self.agent_cell {
+            self.agent_cell = CitizenCells::HashMap(ordered_map::rebuild(map));
+        }
     }
 
     pub fn insert(&mut self, point: Point, citizen: Citizen) -> Option<Citizen> {
@@ -199,7 +326,7 @@ mod tests {
 
         let agents = vec![agent::Citizen::new(home_locations[0], work_locations[0], points[0], false, false, work_status, &mut rng),
                           agent::Citizen::new(home_locations[1], work_locations[0], points[0], true, true, work_status, &mut rng)];
-        AgentLocationMap::new(5, &agents, &points)
+        AgentLocationMap::new(5, &agents, &points, LocationStore::HashMap)
     }
 
     #[test]
@@ -220,7 +347,7 @@ mod tests {
         let mut citizen1 = agent::Citizen::new(home_locations[0], work_locations[1], points[0], false, false, work_status, &mut rng);
         let citizen2 = agent::Citizen::new(home_locations[1], work_locations[0], points[0], true, true, work_status, &mut rng);
         let agents = vec![citizen1, citizen2];
-        let map = AgentLocationMap::new(5, &agents, &points);
+        let map = AgentLocationMap::new(5, &agents, &points, LocationStore::HashMap);
         let hospital = Area::new(Point::new(2, 2), Point::new(4, 4));
         let result = map.goto_hospital(&hospital, points[0], &mut citizen1,&mut thread_rng());
 
@@ -241,7 +368,7 @@ mod tests {
         let citizen3 = agent::Citizen::new(home, work, points[0], false, false, work_status, &mut rng);
         let citizen4 = agent::Citizen::new(home, work, points[0], false, false, work_status, &mut rng);
         let agents = vec![citizen1, citizen2, citizen3, citizen4];
-        let map = AgentLocationMap::new(5, &agents, &points);
+        let map = AgentLocationMap::new(5, &agents, &points, LocationStore::HashMap);
         let hospital = Area::new(Point::new(0, 0), Point::new(1, 1));
 
         let result = map.goto_hospital(&hospital, points[0], &mut citizen1,&mut thread_rng());
@@ -250,6 +377,25 @@ mod tests {
         assert_eq!(citizen1.clone().home_location.contains(&result.1), true);
     }
 
+    #[test]
+    fn dense_store_should_find_citizens_like_hash_map() {
+        let mut rng = thread_rng();
+        let points = vec![Point::new(0, 1), Point::new(5, 5), Point::new(3, 2)];
+        let home = Area::new(Point::new(0, 0), Point::new(2, 2));
+        let work = Area::new(Point::new(5, 0), Point::new(6, 2));
+        let agents: Vec<Citizen> = points.iter()
+            .map(|_| agent::Citizen::new(home, work, points[0], false, false, WorkStatus::NA {}, &mut rng))
+            .collect();
+        let hash_map = AgentLocationMap::new(5, &agents, &points, LocationStore::HashMap);
+        let dense = AgentLocationMap::new(5, &agents, &points, LocationStore::Dense);
+
+        assert_eq!(dense.current_population(), 3);
+        for point in vec![Point::new(0, 1), Point::new(5, 5), Point::new(1, 1), Point::new(-1, 0), Point::new(6, 0)] {
+            assert_eq!(hash_map.is_cell_vacant(&point), dense.is_cell_vacant(&point));
+            assert_eq!(hash_map.get_agent_for(&point).map(|c| c.id), dense.get_agent_for(&point).map(|c| c.id));
+        }
+    }
+
     #[test]
     fn should_return_true_when_point_is_in_grid() {
         let map = before_each();
@@ -0,0 +1,67 @@
+/*
+ * EpiRust
+ * Copyright (c) 2020  ThoughtWorks, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+
+use crate::constants;
+
+/// Places the simulation on real dates, with the first simulated day on `start_date`
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct Calendar {
+    start_date: NaiveDate,
+    #[serde(default = "Calendar::weekend")]
+    non_working_days: Vec<Weekday>,
+}
+
+impl Calendar {
+    fn weekend() -> Vec<Weekday> {
+        vec![Weekday::Sat, Weekday::Sun]
+    }
+
+    pub fn date_at(&self, simulation_hour: i32) -> NaiveDate {
+        self.start_date + Duration::days((simulation_hour / constants::HOURS_IN_A_DAY) as i64)
+    }
+
+    pub fn is_working_day(&self, simulation_hour: i32) -> bool {
+        !self.non_working_days.contains(&self.date_at(simulation_hour).weekday())
+    }
+
+    /// Days since the Monday of the first week
+    pub fn days_since_monday(&self, simulation_hour: i32) -> i32 {
+        self.start_date.weekday().num_days_from_monday() as i32 + simulation_hour / constants::HOURS_IN_A_DAY
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_find_date_and_weekday_of_hour() {
+        let calendar: Calendar = serde_json::from_str(r#"{"start_date": "2020-03-06"}"#).unwrap();
+
+        assert_eq!(calendar.date_at(1), NaiveDate::from_ymd_opt(2020, 3, 6).unwrap());
+        assert_eq!(calendar.date_at(24 + 23), NaiveDate::from_ymd_opt(2020, 3, 7).unwrap());
+        assert!(calendar.is_working_day(10));
+        assert!(!calendar.is_working_day(24 + 10));
+        assert!(!calendar.is_working_day(2 * 24 + 10));
+        assert!(calendar.is_working_day(3 * 24 + 10));
+        assert_eq!(calendar.days_since_monday(3 * 24), 7);
+    }
+}
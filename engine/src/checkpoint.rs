@@ -0,0 +1,161 @@
+/*
+ * EpiRust
+ * Copyright (c) 2020  ThoughtWorks, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+//! Saves the state of a simulation at the end of an hour, so that a long run can be resumed after a crash.
+//! The config is not part of a checkpoint - a simulation has to be resumed with the config it was started with.
+//! Neither are the listeners, so the outputs of a resumed run only cover the hours after the checkpoint.
+
+use std::error::Error;
+use std::fs;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+use fnv::FnvHashMap;
+use rand_pcg::Pcg64;
+
+use crate::allocation_map::AgentLocationMap;
+use crate::contact_recorder::ContactRecorder;
+use crate::geography::{Area, Grid};
+use crate::interventions::Interventions;
+use crate::listeners::events::counts::Counts;
+
+#[derive(Serialize)]
+pub struct CheckpointRef<'a> {
+    pub agent_location_map: &'a AgentLocationMap,
+    pub write_agent_location_map: &'a AgentLocationMap,
+    pub grid: &'a Grid,
+    pub houses_occupancy: ordered_map::Ref<'a, Area, i32>,
+    pub offices_occupancy: ordered_map::Ref<'a, Area, i32>,
+    pub counts: &'a Counts,
+    pub interventions: &'a Interventions,
+    pub rng: &'a Pcg64,
+    pub contact_recorder: &'a ContactRecorder,
+}
+
+#[derive(Deserialize)]
+pub struct Checkpoint {
+    pub agent_location_map: AgentLocationMap,
+    pub write_agent_location_map: AgentLocationMap,
+    pub grid: Grid,
+    #[serde(with = "ordered_map")]
+    houses_occupancy: FnvHashMap<Area, i32>,
+    #[serde(with = "ordered_map")]
+    offices_occupancy: FnvHashMap<Area, i32>,
+    pub counts: Counts,
+    pub interventions: Interventions,
+    pub rng: Pcg64,
+    pub contact_recorder: ContactRecorder,
+}
+
+/// Writes to a temporary file first, so a crash while writing leaves the previous checkpoint intact
+pub fn write(path: &Path, checkpoint: &CheckpointRef) -> Result<(), Box<dyn Error>> {
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    {
+        let writer = BufWriter::new(File::create(&temp_path)?);
+        bincode::serialize_into(writer, checkpoint)?;
+    }
+    fs::rename(temp_path, path)?;
+    Ok(())
+}
+
+pub fn read(path: &str) -> Result<Checkpoint, Box<dyn Error>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut checkpoint: Checkpoint = bincode::deserialize_from(reader)?;
+    checkpoint.grid.houses_occupancy = std::mem::take(&mut checkpoint.houses_occupancy);
+    checkpoint.grid.offices_occupancy = std::mem::take(&mut checkpoint.offices_occupancy);
+    Ok(checkpoint)
+}
+
+/// The simulation iterates over hash maps while drawing random numbers, so a restored map has to iterate in the
+/// same order as the saved one. Maps are saved with their capacity and entries in iteration order, and restored by
+/// inserting the entries in that order. A map rebuilt the same way in memory then behaves identically to a restored one.
+pub mod ordered_map {
+    use std::hash::Hash;
+
+    use fnv::FnvHashMap;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub struct Ref<'a, K, V>(pub &'a FnvHashMap<K, V>);
+
+    struct Entries<'a, K, V>(&'a FnvHashMap<K, V>);
+
+    impl<'a, K: Serialize, V: Serialize> Serialize for Ref<'a, K, V> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            (self.0.capacity(), Entries(self.0)).serialize(serializer)
+        }
+    }
+
+    impl<'a, K: Serialize, V: Serialize> Serialize for Entries<'a, K, V> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(self.0.iter())
+        }
+    }
+
+    pub fn serialize<S, K, V>(map: &FnvHashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+        where S: Serializer, K: Serialize, V: Serialize {
+        Ref(map).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D, K, V>(deserializer: D) -> Result<FnvHashMap<K, V>, D::Error>
+        where D: Deserializer<'de>, K: Deserialize<'de> + Eq + Hash, V: Deserialize<'de> {
+        let (capacity, entries): (usize, Vec<(K, V)>) = Deserialize::deserialize(deserializer)?;
+        Ok(from_entries(capacity, entries))
+    }
+
+    pub fn rebuild<K: Eq + Hash + Copy, V: Copy>(map: &FnvHashMap<K, V>) -> FnvHashMap<K, V> {
+        from_entries(map.capacity(), map.iter().map(|(k, v)| (*k, *v)))
+    }
+
+    fn from_entries<K: Eq + Hash, V>(capacity: usize, entries: impl IntoIterator<Item=(K, V)>) -> FnvHashMap<K, V> {
+        let mut map = FnvHashMap::with_capacity_and_hasher(capacity, Default::default());
+        map.extend(entries);
+        map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fnv::FnvHashMap;
+
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Restored(#[serde(with = "ordered_map")] FnvHashMap<i32, i32>);
+
+    #[test]
+    fn restored_map_should_iterate_like_rebuilt_map() {
+        let mut map: FnvHashMap<i32, i32> = FnvHashMap::default();
+        for i in 0..1000 {
+            map.insert(i * 7919 % 1009, i);
+        }
+        for i in 0..300 {
+            map.remove(&(i * 13 % 1009));
+        }
+
+        let bytes = bincode::serialize(&ordered_map::Ref(&map)).unwrap();
+        let restored: Restored = bincode::deserialize(&bytes).unwrap();
+        let restored = restored.0;
+        let rebuilt = ordered_map::rebuild(&map);
+
+        assert_eq!(map, restored);
+        assert_eq!(rebuilt.iter().collect::<Vec<_>>(), restored.iter().collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,293 @@
+/*
+ * EpiRust
+ * Copyright (c) 2020  ThoughtWorks, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use std::fs::File;
+
+use rand::Rng;
+use serde::{de, Deserialize, Deserializer};
+
+use crate::duration::Duration;
+use crate::hospital_beds::Bed;
+
+/// How the rest of the simulation treats the citizens in a compartment
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum CompartmentKind {
+    /// Can be exposed, and then leaves the compartment along one of its transitions
+    Susceptible,
+    /// Infected but not yet infectious
+    Exposed,
+    /// Infects neighbours at the compartment's `transmission_rate`
+    Infected,
+    Recovered,
+    Deceased,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Compartment {
+    pub name: String,
+    pub kind: CompartmentKind,
+    /// Hours spent in the compartment before moving on along one of its transitions; citizens stay for good without
+    /// one. Susceptible compartments are left on exposure instead
+    #[serde(default)]
+    pub dwell: Option<Duration>,
+    /// The chance of infecting each neighbour in an hour
+    #[serde(default)]
+    pub transmission_rate: f64,
+    /// Symptomatic citizens stay at home
+    #[serde(default)]
+    pub symptomatic: bool,
+    /// The hospital bed needed by citizens in the compartment, if any
+    #[serde(default)]
+    pub bed: Option<Bed>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Transition {
+    pub from: String,
+    pub to: String,
+    /// Relative to the other transitions out of `from`
+    #[serde(default = "default_probability")]
+    pub probability: f64,
+    /// Replaces `probability` for citizens who need a hospital bed and did not get one
+    #[serde(default)]
+    pub untreated_probability: Option<f64>,
+}
+
+fn default_probability() -> f64 {
+    1.0
+}
+
+/// A disease course declared in the config, replacing the built-in one. Citizens start in the first compartment of
+/// their kind: the first susceptible one, or the first exposed or infected one for starting infections, falling back
+/// on the other of those two kinds in models without theirs
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct CompartmentModel {
+    pub compartments: Vec<Compartment>,
+    pub transitions: Vec<Transition>,
+}
+
+impl CompartmentModel {
+    pub fn get(&self, compartment: usize) -> &Compartment {
+        &self.compartments[compartment]
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.compartments.iter().map(|compartment| compartment.name.clone()).collect()
+    }
+
+    pub fn first_of(&self, kind: CompartmentKind) -> Option<usize> {
+        self.compartments.iter().position(|compartment| compartment.kind == kind)
+    }
+
+    /// Where citizens who start the simulation in a state of this kind go
+    pub fn start_of(&self, kind: CompartmentKind) -> usize {
+        let fallback = match kind {
+            CompartmentKind::Exposed => CompartmentKind::Infected,
+            CompartmentKind::Infected => CompartmentKind::Exposed,
+            other => other
+        };
+        self.first_of(kind)
+            .or_else(|| self.first_of(fallback))
+            .unwrap_or_else(|| panic!("No {:?} compartment in the compartment model", kind))
+    }
+
+    /// Picks where a citizen leaving `compartment` goes, if anywhere
+    pub fn next(&self, compartment: usize, treated: bool, rng: &mut impl rand::RngCore) -> Option<usize> {
+        let name = &self.compartments[compartment].name;
+        let weight = |transition: &Transition| match transition.untreated_probability {
+            Some(probability) if !treated => probability,
+            _ => transition.probability
+        };
+        let transitions: Vec<&Transition> = self.transitions.iter().filter(|transition| transition.from == *name).collect();
+        let total: f64 = transitions.iter().map(|transition| weight(transition)).sum();
+        if total <= 0.0 {
+            return None;
+        }
+        let mut remaining = rng.gen::<f64>() * total;
+        let mut picked = transitions.last()?;
+        for transition in transitions.iter() {
+            if remaining < weight(transition) {
+                picked = transition;
+                break;
+            }
+            remaining -= weight(transition);
+        }
+        self.position(&picked.to)
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.compartments.iter().position(|compartment| compartment.name == name)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for (i, compartment) in self.compartments.iter().enumerate() {
+            if self.compartments[..i].iter().any(|other| other.name == compartment.name) {
+                return Err(format!("Compartment {} is declared twice", compartment.name));
+            }
+            if !(0.0..=1.0).contains(&compartment.transmission_rate) {
+                return Err(format!("Compartment {} has a transmission rate of {}, not one between 0 and 1",
+                                   compartment.name, compartment.transmission_rate));
+            }
+            let infected = compartment.kind == CompartmentKind::Exposed || compartment.kind == CompartmentKind::Infected;
+            let has_exit = self.transitions.iter().any(|transition| transition.from == compartment.name);
+            if infected && (compartment.dwell.is_none() || !has_exit) {
+                return Err(format!("{:?} compartment {} needs a dwell and a transition out, or its citizens stay \
+                                    infected for good", compartment.kind, compartment.name));
+            }
+        }
+        if self.first_of(CompartmentKind::Susceptible).is_none() {
+            return Err("The compartment model has no Susceptible compartment".to_string());
+        }
+        if self.first_of(CompartmentKind::Exposed).is_none() && self.first_of(CompartmentKind::Infected).is_none() {
+            return Err("The compartment model has no Exposed or Infected compartment for starting infections".to_string());
+        }
+        for transition in self.transitions.iter() {
+            if let Some(name) = [&transition.from, &transition.to].iter().find(|name| self.position(name).is_none()) {
+                return Err(format!("Transition from {} to {} refers to unknown compartment {}", transition.from,
+                                   transition.to, name));
+            }
+            let probabilities = [Some(transition.probability), transition.untreated_probability];
+            if probabilities.iter().flatten().any(|probability| !(*probability >= 0.0 && probability.is_finite())) {
+                return Err(format!("Transition from {} to {} needs probabilities that are finite and not negative",
+                                   transition.from, transition.to));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The model in a config is either inline, or the path of a YAML or JSON file holding it
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CompartmentModelConfig {
+    Inline(CompartmentModel),
+    File(String),
+}
+
+pub fn deserialize_compartment_model<'de, D>(deserializer: D) -> Result<Option<CompartmentModel>, D::Error>
+    where
+        D: Deserializer<'de>,
+{
+    let model = match Option::<CompartmentModelConfig>::deserialize(deserializer)? {
+        None => return Ok(None),
+        Some(CompartmentModelConfig::Inline(model)) => model,
+        Some(CompartmentModelConfig::File(path)) => {
+            let reader = File::open(&path).map_err(|e| de::Error::custom(format!("{}: {}", path, e)))?;
+            serde_yaml::from_reader(reader).map_err(|e| de::Error::custom(format!("{}: {}", path, e)))?
+        }
+    };
+    model.validate().map_err(de::Error::custom)?;
+    Ok(Some(model))
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64;
+
+    use super::*;
+
+    fn sirs() -> CompartmentModel {
+        serde_yaml::from_str(r#"
+compartments:
+  - {name: S, kind: Susceptible}
+  - {name: I, kind: Infected, dwell: 48, transmission_rate: 0.1}
+  - {name: H, kind: Infected, dwell: 96, bed: General}
+  - {name: R, kind: Recovered, dwell: {Uniform: {min: 100, max: 200}}}
+  - {name: D, kind: Deceased}
+transitions:
+  - {from: S, to: I}
+  - {from: I, to: R, probability: 0.8}
+  - {from: I, to: H, probability: 0.2}
+  - {from: H, to: R, probability: 0.9, untreated_probability: 0.5}
+  - {from: H, to: D, probability: 0.1, untreated_probability: 0.5}
+  - {from: R, to: S}
+"#).unwrap()
+    }
+
+    #[test]
+    fn should_pick_transitions_by_probability() {
+        let model = sirs();
+        let mut rng = Pcg64::seed_from_u64(7);
+
+        assert_eq!(model.next(0, true, &mut rng), Some(1));
+        assert_eq!(model.next(4, true, &mut rng), None);
+        let hospitalized = (0..1000).filter(|_| model.next(1, true, &mut rng) == Some(2)).count();
+        assert!(hospitalized > 150 && hospitalized < 250);
+        let deceased = (0..1000).filter(|_| model.next(2, false, &mut rng) == Some(4)).count();
+        assert!(deceased > 450 && deceased < 550);
+    }
+
+    #[test]
+    fn should_find_first_compartment_of_kind() {
+        let model = sirs();
+        assert_eq!(model.first_of(CompartmentKind::Infected), Some(1));
+        assert_eq!(model.first_of(CompartmentKind::Exposed), None);
+        assert_eq!(model.start_of(CompartmentKind::Exposed), 1);
+        assert_eq!(model.start_of(CompartmentKind::Susceptible), 0);
+        assert_eq!(model.names(), vec!["S", "I", "H", "R", "D"]);
+    }
+
+    #[test]
+    fn should_reject_unknown_or_duplicate_compartments() {
+        let mut model = sirs();
+        assert!(model.validate().is_ok());
+
+        model.transitions.push(Transition { from: "I".to_string(), to: "E".to_string(), probability: 1.0, untreated_probability: None });
+        assert!(model.validate().is_err());
+
+        let mut model = sirs();
+        model.compartments.push(model.compartments[0].clone());
+        assert!(model.validate().is_err());
+
+        let mut model = sirs();
+        model.compartments.retain(|compartment| compartment.kind != CompartmentKind::Infected);
+        model.transitions.clear();
+        assert!(model.validate().is_err());
+    }
+
+    #[test]
+    fn should_reject_models_citizens_can_not_move_through() {
+        let error_of = |model: CompartmentModel| model.validate().unwrap_err();
+
+        let mut model = sirs();
+        model.compartments[1].dwell = None;
+        assert!(error_of(model).contains("Infected compartment I needs a dwell and a transition out"));
+
+        let mut model = sirs();
+        model.transitions.retain(|transition| transition.from != "H");
+        assert!(error_of(model).contains("Infected compartment H needs a dwell and a transition out"));
+
+        let mut model = sirs();
+        model.transitions[1].probability = f64::NAN;
+        assert!(error_of(model).contains("finite and not negative"));
+
+        let mut model = sirs();
+        model.transitions[3].untreated_probability = Some(f64::INFINITY);
+        assert!(error_of(model).contains("finite and not negative"));
+
+        let mut model = sirs();
+        model.transitions[2].probability = -0.2;
+        assert!(error_of(model).contains("finite and not negative"));
+
+        let mut model = sirs();
+        model.compartments[1].transmission_rate = 1.5;
+        assert!(error_of(model).contains("transmission rate of 1.5"));
+    }
+}
@@ -17,18 +17,38 @@
  *
  */
 
+use std::collections::HashMap;
 use std::error::Error;
 use std::fs::File;
 
-use crate::disease::{Disease, DiseaseOverride};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de;
+
+use crate::agent::PopulationRecord;
+use crate::calendar::Calendar;
+use crate::compartment_model::{deserialize_compartment_model, CompartmentModel};
+use crate::disease::{deserialize_disease, Disease, DiseaseOverride};
+use crate::hospital_beds::HospitalBeds;
 use crate::interventions::{InterventionConfig};
+use crate::interventions::vaccination_campaign::VaccinationCampaignConfig;
+use crate::schedule::{deserialize_schedule, Schedule};
+use crate::strain::{self, CrossImmunity, Strain};
+use crate::vaccine::{deserialize_vaccines, Vaccine};
 
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(remote = "Self")]
 pub struct Config {
     population: Population,
+    #[serde(deserialize_with = "deserialize_disease")]
     disease: Disease,
     #[serde(default)]
     disease_overrides: Vec<DiseaseOverride>,
+    #[serde(default, deserialize_with = "deserialize_compartment_model")]
+    compartment_model: Option<CompartmentModel>,
+    #[serde(default)]
+    strains: Vec<Strain>,
+    #[serde(default)]
+    cross_immunity: Vec<CrossImmunity>,
     geography_parameters: GeographyParameters,
     hours: i32,
     interventions: Vec<InterventionConfig>,
@@ -36,12 +56,77 @@ pub struct Config {
     #[serde(default)]
     enable_citizen_state_messages: bool,
     #[serde(default)]
+    enable_contact_log: bool,
+    #[serde(default, deserialize_with = "deserialize_hotspot_window_days")]
+    hotspot_window_days: Option<i32>,
+    #[serde(default)]
     starting_infections: StartingInfections,
+    #[serde(default)]
+    seed: Option<u64>,
+    #[serde(default)]
+    checkpoint_interval: Option<i32>,
+    #[serde(default)]
+    location_store: LocationStore,
+    #[serde(default, deserialize_with = "deserialize_schedule")]
+    schedule: Schedule,
+    #[serde(default)]
+    calendar: Option<Calendar>,
+    #[serde(default, deserialize_with = "deserialize_vaccines")]
+    vaccines: HashMap<String, Vaccine>,
+}
+
+/// Fields that depend on each other are checked once the whole config is read
+impl<'de> Deserialize<'de> for Config {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+    {
+        let config = Config::deserialize(deserializer)?;
+        config.validate().map_err(de::Error::custom)?;
+        Ok(config)
+    }
+}
+
+impl Serialize for Config {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+    {
+        Config::serialize(self, serializer)
+    }
 }
 
 impl Config {
+    fn validate(&self) -> Result<(), String> {
+        if !self.strains.is_empty() && self.compartment_model.is_some() {
+            return Err("Strains can not be combined with a compartment model".to_string());
+        }
+        for intervention in &self.interventions {
+            if let InterventionConfig::VaccinationCampaign(VaccinationCampaignConfig { vaccine: Some(name), .. }) =
+                intervention {
+                if !self.vaccines.contains_key(name) {
+                    return Err(format!("No vaccine named {} in vaccines", name));
+                }
+            }
+        }
+        strain::validate(&self.strains, &self.cross_immunity)
+    }
+
     pub fn get_disease(&self) -> Disease {
-        self.disease
+        self.disease.clone()
+    }
+
+    pub fn get_compartment_model(&self) -> Option<CompartmentModel> {
+        self.compartment_model.clone()
+    }
+
+    /// Variants of the disease, besides the wild type
+    pub fn get_strains(&self) -> Vec<Strain> {
+        self.strains.clone()
+    }
+
+    pub fn get_cross_immunity(&self) -> Vec<CrossImmunity> {
+        self.cross_immunity.clone()
     }
 
     pub fn get_starting_infections(&self) -> StartingInfections {
@@ -52,9 +137,9 @@ impl Config {
         self.population.clone()
     }
 
-    // pub fn get_disease_overrides(&self) -> Vec<DiseaseOverride> {
-    //     self.disease_overrides.clone()
-    // }
+    pub fn get_disease_overrides(&self) -> Vec<DiseaseOverride> {
+        self.disease_overrides.clone()
+    }
 
     pub fn get_grid_size(&self) -> i32 {
         self.geography_parameters.grid_size
@@ -76,10 +161,61 @@ impl Config {
         self.enable_citizen_state_messages
     }
 
+    pub fn enable_contact_log(&self) -> bool {
+        self.enable_contact_log
+    }
+
+    pub fn get_hotspot_window_days(&self) -> Option<i32> {
+        self.hotspot_window_days
+    }
+
     pub fn get_geography_parameters(&self) -> GeographyParameters {
         self.geography_parameters.clone()
     }
 
+    pub fn get_seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = Some(seed);
+    }
+
+    pub fn get_checkpoint_interval(&self) -> Option<i32> {
+        self.checkpoint_interval
+    }
+
+    pub fn get_location_store(&self) -> LocationStore {
+        self.location_store
+    }
+
+    /// The schedule, following the calendar if there is one
+    pub fn get_schedule(&self) -> Schedule {
+        self.schedule.clone().with_calendar(self.calendar.clone())
+    }
+
+    pub fn get_calendar(&self) -> Option<Calendar> {
+        self.calendar.clone()
+    }
+
+    /// A vaccine from `vaccines`, or a perfect one if there is no name
+    pub fn get_vaccine(&self, name: Option<&String>) -> Vaccine {
+        match name {
+            Some(name) => *self.vaccines.get(name).expect("Vaccine names are checked when the config is read"),
+            None => Vaccine::perfect()
+        }
+    }
+
+    #[cfg(test)]
+    pub fn set_checkpoint_interval(&mut self, hours: i32) {
+        self.checkpoint_interval = Some(hours);
+    }
+
+    #[cfg(test)]
+    pub fn set_starting_infections(&mut self, starting_infections: StartingInfections) {
+        self.starting_infections = starting_infections;
+    }
+
     #[cfg(test)]
     pub fn new(population: Population, disease: Disease, geography_parameters: GeographyParameters, disease_overrides: Vec<DiseaseOverride>,
                hours: i32, interventions: Vec<InterventionConfig>, output_file: Option<String>)
@@ -88,12 +224,23 @@ impl Config {
             population,
             disease,
             disease_overrides,
+            compartment_model: None,
+            strains: vec![],
+            cross_immunity: vec![],
             geography_parameters,
             hours,
             interventions,
             output_file,
             enable_citizen_state_messages: true,
+            enable_contact_log: false,
+            hotspot_window_days: None,
             starting_infections: StartingInfections::default(),
+            seed: None,
+            checkpoint_interval: None,
+            location_store: LocationStore::HashMap,
+            schedule: Schedule::default(),
+            calendar: None,
+            vaccines: HashMap::new(),
         }
     }
 }
@@ -102,15 +249,40 @@ impl Config {
 pub struct GeographyParameters {
     pub grid_size: i32,
     pub hospital_beds_percentage: f64,
+    /// General beds, instead of `hospital_beds_percentage` of the population
+    #[serde(default)]
+    pub hospital_beds: Option<i32>,
+    #[serde(default)]
+    pub icu_beds: i32,
 }
 
 impl GeographyParameters{
     #[cfg(test)]
     pub fn new(grid_size: i32, hospital_beds_percentage: f64) -> GeographyParameters {
         GeographyParameters{
-            grid_size, hospital_beds_percentage
+            grid_size, hospital_beds_percentage, hospital_beds: None, icu_beds: 0
         }
     }
+
+    pub fn get_hospital_beds(&self, population: usize) -> HospitalBeds {
+        let general = self.hospital_beds
+            .unwrap_or_else(|| (population as f64 * self.hospital_beds_percentage).ceil() as i32);
+        HospitalBeds::new(general, self.icu_beds)
+    }
+}
+
+/// How citizens are looked up by location: a hash map, or a dense array with a slot for every cell of the grid,
+/// which takes 4 bytes per cell
+#[derive(Debug, PartialEq, Serialize, Deserialize, Copy, Clone)]
+pub enum LocationStore {
+    HashMap,
+    Dense,
+}
+
+impl Default for LocationStore {
+    fn default() -> Self {
+        LocationStore::HashMap
+    }
 }
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
@@ -125,6 +297,16 @@ pub struct CsvPopulation {
     pub cols: Vec<String>,
 }
 
+impl CsvPopulation {
+    pub fn read_records(&self) -> Vec<PopulationRecord> {
+        let file = File::open(&self.file).expect("Could not read population file");
+        let mut rdr = csv::Reader::from_reader(file);
+        rdr.deserialize()
+            .map(|result| result.expect("Could not deserialize population line"))
+            .collect()
+    }
+}
+
 #[derive(Debug, PartialEq, Serialize, Deserialize, Copy, Clone)]
 pub struct AutoPopulation {
     pub number_of_agents: i32,
@@ -132,6 +314,16 @@ pub struct AutoPopulation {
     pub working_percentage: f64,
 }
 
+fn deserialize_hotspot_window_days<'de, D>(deserializer: D) -> Result<Option<i32>, D::Error>
+    where
+        D: Deserializer<'de>,
+{
+    match Option::<i32>::deserialize(deserializer)? {
+        Some(days) if days < 1 => Err(de::Error::custom(format!("hotspot_window_days must be at least 1, not {}", days))),
+        days => Ok(days)
+    }
+}
+
 pub fn read(filename: String) -> Result<Config, Box<dyn Error>> {
     let reader = File::open(filename)?;
     let v: Config = serde_json::from_reader(reader)?;
@@ -218,12 +410,23 @@ mod tests {
             population,
             disease: Disease::new(5, 20, 40, 9, 12, 0.025, 0.25, 0.035, 0.3, 0.3, 48, 48),
             disease_overrides: vec![disease_override],
+            compartment_model: None,
+            strains: vec![],
+            cross_immunity: vec![],
             geography_parameters: GeographyParameters::new(5660, 0.003),
             hours: 10000,
             interventions: vec![InterventionConfig::Vaccinate(vaccinate)],
             output_file: None,
             enable_citizen_state_messages: false,
+            enable_contact_log: false,
+            hotspot_window_days: None,
             starting_infections: StartingInfections::default(),
+            seed: None,
+            checkpoint_interval: None,
+            location_store: LocationStore::HashMap,
+            schedule: Schedule::default(),
+            calendar: None,
+            vaccines: HashMap::new(),
         };
 
         assert_eq!(expected_config, read_config);
@@ -245,14 +448,95 @@ mod tests {
             population,
             disease: Disease::new(5, 20, 40, 9, 12, 0.025, 0.25, 0.035, 0.3, 0.3, 48, 48),
             disease_overrides: vec![],
+            compartment_model: None,
+            strains: vec![],
+            cross_immunity: vec![],
             geography_parameters: GeographyParameters::new(250, 0.003),
             hours: 10000,
             interventions: vec![InterventionConfig::Vaccinate(vaccinate)],
             output_file: Some("simulation_default_config".to_string()),
             enable_citizen_state_messages: false,
+            enable_contact_log: false,
+            hotspot_window_days: None,
             starting_infections: StartingInfections::new(2, 3, 4, 5),
+            seed: None,
+            checkpoint_interval: None,
+            location_store: LocationStore::HashMap,
+            schedule: Schedule::default(),
+            calendar: None,
+            vaccines: HashMap::new(),
         };
 
         assert_eq!(expected_config, read_config);
     }
+
+    #[test]
+    fn should_read_seed() {
+        let read_config = read(String::from("config/test/seeded_auto_pop.json")).unwrap();
+
+        assert_eq!(Some(42), read_config.get_seed());
+    }
+
+    #[test]
+    fn should_resolve_disease_presets() {
+        let read_config = read(String::from("config/pune.json")).unwrap();
+
+        let covid = Disease::init("config/diseases.yaml", &String::from("covid_19"));
+        let elderly = Disease::new(5, 20, 26, 9, 12, 0.025, 0.25, 0.2, 0.3, 0.3, 48, 48);
+        let disease_override = DiseaseOverride::new(
+            String::from("age"),
+            vec!["60-64".to_string(), "65-69".to_string(), "70-74".to_string(), "75-79".to_string(), "80+".to_string()],
+            elderly);
+
+        assert_eq!(covid, read_config.get_disease());
+        assert_eq!(vec![disease_override], read_config.get_disease_overrides());
+    }
+
+    fn auto_pop_config_with(fields: serde_json::Value) -> Result<Config, serde_json::Error> {
+        let mut config: serde_json::Value = serde_json::from_reader(File::open("config/test/auto_pop.json").unwrap()).unwrap();
+        for (field, value) in fields.as_object().unwrap() {
+            config[field] = value.clone();
+        }
+        serde_json::from_value(config)
+    }
+
+    #[test]
+    fn should_reject_hotspot_windows_of_no_days() {
+        let config = auto_pop_config_with(serde_json::json!({"hotspot_window_days": 7})).unwrap();
+        assert_eq!(config.get_hotspot_window_days(), Some(7));
+        let error = auto_pop_config_with(serde_json::json!({"hotspot_window_days": 0})).unwrap_err();
+        assert!(error.to_string().contains("hotspot_window_days must be at least 1"), "{}", error);
+    }
+
+    #[test]
+    fn should_reject_strains_that_can_not_be_simulated() {
+        let delta = serde_json::json!({"name": "delta", "introduced_at": 240, "introductions": 10,
+            "disease": {"Preset": "sars", "catalogue": "config/diseases.yaml"}});
+        let error_of = |fields: serde_json::Value| auto_pop_config_with(fields).unwrap_err().to_string();
+
+        let config = auto_pop_config_with(serde_json::json!({"strains": [delta],
+            "cross_immunity": [{"from": "delta", "to": "wild_type", "protection": 0.5}]})).unwrap();
+        assert_eq!(config.get_strains().len(), 1);
+        assert!(error_of(serde_json::json!({"strains": [delta, delta]})).contains("Strain delta is declared twice"));
+        assert!(error_of(serde_json::json!({"strains": [delta],
+            "cross_immunity": [{"from": "omicron", "to": "delta", "protection": 0.5}]}))
+            .contains("unknown strain omicron"));
+        assert!(error_of(serde_json::json!({"strains": [delta],
+            "cross_immunity": [{"from": "delta", "to": "wild_type", "protection": 1.5}]}))
+            .contains("not between 0 and 1"));
+        assert!(error_of(serde_json::json!({"strains": [delta], "compartment_model": "config/models/seirs.yaml"}))
+            .contains("can not be combined with a compartment model"));
+    }
+
+    #[test]
+    fn should_reject_campaigns_with_unknown_vaccines() {
+        let campaign_of = |vaccine: &str| serde_json::json!({
+            "interventions": [{"VaccinationCampaign": {"start_hour": 240, "doses_per_day": 500, "vaccine": vaccine}}],
+            "vaccines": {"two_dose": {"infection_efficacy": 0.7, "severity_efficacy": 0.9, "doses": 2}}
+        });
+
+        assert!(auto_pop_config_with(campaign_of("two_dose")).is_ok());
+        let error = auto_pop_config_with(campaign_of("one_dose")).unwrap_err();
+        assert!(error.to_string().contains("No vaccine named one_dose in vaccines"), "{}", error);
+    }
 }
@@ -0,0 +1,104 @@
+/*
+ * EpiRust
+ * Copyright (c) 2020  ThoughtWorks, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use fnv::FnvHashSet;
+use uuid::Uuid;
+
+use crate::constants;
+use crate::geography::Point;
+use crate::listeners::listener::Listeners;
+
+/// Aggregates the contacts between citizens over a day, and reports each pair that met once, at the hour and
+/// location where they first met that day. The contacts of the day so far are part of a checkpoint
+#[derive(Serialize, Deserialize)]
+pub struct ContactRecorder {
+    recording: bool,
+    met_today: FnvHashSet<(Uuid, Uuid)>,
+    contacts: Vec<(i32, Uuid, Uuid, Point)>,
+}
+
+impl ContactRecorder {
+    pub fn new(recording: bool) -> ContactRecorder {
+        ContactRecorder { recording, met_today: FnvHashSet::default(), contacts: Vec::new() }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    pub fn record(&mut self, hour: i32, citizen: Uuid, neighbour: Uuid, location: Point) {
+        if !self.recording || citizen == neighbour {
+            return;
+        }
+        let pair = if citizen < neighbour { (citizen, neighbour) } else { (neighbour, citizen) };
+        if self.met_today.insert(pair) {
+            self.contacts.push((hour, pair.0, pair.1, location));
+        }
+    }
+
+    /// Reports the contacts of the day once its last hour is over
+    pub fn hour_ended(&mut self, hour: i32, listeners: &mut Listeners) {
+        if (hour + 1) % constants::HOURS_IN_A_DAY == 0 {
+            self.flush(listeners);
+        }
+    }
+
+    /// Reports the contacts recorded since the last flush
+    pub fn flush(&mut self, listeners: &mut Listeners) {
+        for (hour, a, b, location) in self.contacts.drain(..) {
+            listeners.citizen_contact(hour, a, b, &location);
+        }
+        self.met_today.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_record_a_pair_once_a_day() {
+        let mut recorder = ContactRecorder::new(true);
+        let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        recorder.record(1, a, b, Point::new(1, 1));
+        recorder.record(2, b, a, Point::new(2, 2));
+        recorder.record(2, a, c, Point::new(2, 2));
+        recorder.record(3, c, c, Point::new(2, 2));
+
+        assert_eq!(recorder.contacts.len(), 2);
+        assert_eq!(recorder.contacts[0].0, 1);
+        assert_eq!(recorder.contacts[0].3, Point::new(1, 1));
+
+        recorder.hour_ended(22, &mut Listeners::from(vec![]));
+        assert_eq!(recorder.contacts.len(), 2);
+        recorder.hour_ended(23, &mut Listeners::from(vec![]));
+        assert!(recorder.contacts.is_empty());
+
+        recorder.record(25, b, a, Point::new(1, 1));
+        assert_eq!(recorder.contacts.len(), 1);
+    }
+
+    #[test]
+    fn should_not_record_unless_recording() {
+        let mut recorder = ContactRecorder::new(false);
+        recorder.record(1, Uuid::new_v4(), Uuid::new_v4(), Point::new(1, 1));
+
+        assert!(recorder.contacts.is_empty());
+    }
+}
@@ -0,0 +1,199 @@
+/*
+ * EpiRust
+ * Copyright (c) 2020  ThoughtWorks, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use std::num::NonZeroU32;
+use std::slice;
+
+use fnv::FnvHashMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::agent::Citizen;
+use crate::geography::Point;
+
+/// Citizens stored in an arena, with one slot per cell of the grid pointing into it. Lookups by location are a
+/// bounds check and an index, at the cost of 4 bytes per cell. Iterates in arena order.
+/// Starting locations can lie beyond the grid, so the few citizens outside it are looked up in a hash map instead.
+#[derive(Clone)]
+pub struct DenseGrid {
+    side: i32,
+    // 1-based index into citizens, so that an empty cell fits in 4 bytes
+    cells: Vec<Option<NonZeroU32>>,
+    outside: FnvHashMap<Point, NonZeroU32>,
+    citizens: Vec<(Point, Citizen)>,
+}
+
+impl DenseGrid {
+    pub fn new(grid_size: i32, capacity: usize) -> DenseGrid {
+        // areas extend up to and including grid_size
+        let side = grid_size + 1;
+        DenseGrid {
+            side,
+            cells: vec![None; (side * side) as usize],
+            outside: FnvHashMap::default(),
+            citizens: Vec::with_capacity(capacity),
+        }
+    }
+
+    fn cell_index(&self, point: &Point) -> Option<usize> {
+        if point.x < 0 || point.y < 0 || point.x >= self.side || point.y >= self.side {
+            return None;
+        }
+        Some((point.x * self.side + point.y) as usize)
+    }
+
+    fn slot(&self, point: &Point) -> Option<NonZeroU32> {
+        match self.cell_index(point) {
+            Some(cell) => self.cells[cell],
+            None => self.outside.get(point).copied()
+        }
+    }
+
+    fn set_slot(&mut self, point: &Point, slot: Option<NonZeroU32>) {
+        match (self.cell_index(point), slot) {
+            (Some(cell), _) => self.cells[cell] = slot,
+            (None, Some(slot)) => { self.outside.insert(*point, slot); }
+            (None, None) => { self.outside.remove(point); }
+        }
+    }
+
+    fn citizen_index(&self, point: &Point) -> Option<usize> {
+        self.slot(point).map(|index| index.get() as usize - 1)
+    }
+
+    pub fn get(&self, point: &Point) -> Option<&Citizen> {
+        self.citizen_index(point).map(|index| &self.citizens[index].1)
+    }
+
+    pub fn contains(&self, point: &Point) -> bool {
+        self.citizen_index(point).is_some()
+    }
+
+    pub fn insert(&mut self, point: Point, citizen: Citizen) -> Option<Citizen> {
+        if let Some(index) = self.citizen_index(&point) {
+            return Some(std::mem::replace(&mut self.citizens[index].1, citizen));
+        }
+        self.citizens.push((point, citizen));
+        self.set_slot(&point, NonZeroU32::new(self.citizens.len() as u32));
+        None
+    }
+
+    pub fn remove(&mut self, point: &Point) -> Option<Citizen> {
+        let index = self.citizen_index(point)?;
+        self.set_slot(point, None);
+        let (_, citizen) = self.citizens.swap_remove(index);
+        if index < self.citizens.len() {
+            let moved = self.citizens[index].0;
+            self.set_slot(&moved, NonZeroU32::new(index as u32 + 1));
+        }
+        Some(citizen)
+    }
+
+    pub fn len(&self) -> usize {
+        self.citizens.len()
+    }
+
+    /// Only resets the occupied cells, so clearing is proportional to the population rather than the grid
+    pub fn clear(&mut self) {
+        for (point, _) in self.citizens.iter() {
+            if let Some(cell) = self.cell_index(point) {
+                self.cells[cell] = None;
+            }
+        }
+        self.outside.clear();
+        self.citizens.clear();
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, (Point, Citizen)> {
+        self.citizens.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> slice::IterMut<'_, (Point, Citizen)> {
+        self.citizens.iter_mut()
+    }
+}
+
+impl Serialize for DenseGrid {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (self.side - 1, &self.citizens).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for DenseGrid {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<DenseGrid, D::Error> {
+        let (grid_size, citizens): (i32, Vec<(Point, Citizen)>) = Deserialize::deserialize(deserializer)?;
+        let mut grid = DenseGrid::new(grid_size, citizens.len());
+        for (point, citizen) in citizens {
+            grid.insert(point, citizen);
+        }
+        Ok(grid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::thread_rng;
+
+    use crate::agent::WorkStatus;
+    use crate::geography::Area;
+
+    use super::*;
+
+    fn citizen() -> Citizen {
+        let home = Area::new(Point::new(0, 0), Point::new(2, 2));
+        let work = Area::new(Point::new(5, 0), Point::new(6, 2));
+        Citizen::new(home, work, Point::new(0, 0), false, false, WorkStatus::NA, &mut thread_rng())
+    }
+
+    #[test]
+    fn should_insert_and_remove_citizens() {
+        let mut grid = DenseGrid::new(10, 3);
+        let (first, second, third) = (citizen(), citizen(), citizen());
+        grid.insert(Point::new(0, 0), first);
+        grid.insert(Point::new(10, 10), second);
+        grid.insert(Point::new(3, 4), third);
+
+        assert_eq!(grid.len(), 3);
+        assert_eq!(grid.remove(&Point::new(0, 0)).unwrap().id, first.id);
+        assert!(!grid.contains(&Point::new(0, 0)));
+        assert_eq!(grid.get(&Point::new(3, 4)).unwrap().id, third.id);
+        assert_eq!(grid.get(&Point::new(10, 10)).unwrap().id, second.id);
+        assert!(grid.get(&Point::new(11, 0)).is_none());
+        assert!(grid.get(&Point::new(-1, 0)).is_none());
+
+        grid.insert(Point::new(15, 3), first);
+        assert_eq!(grid.get(&Point::new(15, 3)).unwrap().id, first.id);
+        assert_eq!(grid.remove(&Point::new(3, 4)).unwrap().id, third.id);
+        assert_eq!(grid.get(&Point::new(15, 3)).unwrap().id, first.id);
+
+        grid.clear();
+        assert_eq!(grid.len(), 0);
+        assert!(!grid.contains(&Point::new(3, 4)));
+    }
+
+    #[test]
+    fn should_replace_citizen_in_occupied_cell() {
+        let mut grid = DenseGrid::new(10, 2);
+        let (first, second) = (citizen(), citizen());
+        grid.insert(Point::new(1, 1), first);
+
+        assert_eq!(grid.insert(Point::new(1, 1), second).unwrap().id, first.id);
+        assert_eq!(grid.len(), 1);
+        assert_eq!(grid.get(&Point::new(1, 1)).unwrap().id, second.id);
+    }
+}
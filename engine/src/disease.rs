@@ -17,37 +17,153 @@
  *
  */
 
+use std::error::Error;
+use std::fs::File;
+
 use rand::Rng;
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de;
+use serde_yaml::{Mapping, Value};
 
+use crate::agent::PopulationRecord;
+use crate::compartment_model::CompartmentModel;
+use crate::strain::{cross_immunity_matrix, CrossImmunity, Strain, WILD_TYPE};
+use crate::duration::Duration;
 
+const DEFAULT_DISEASE_CATALOGUE: &str = "config/diseases.yaml";
+/// Parameters that presets may leave out
+const OPTIONAL_PARAMETERS: [&str; 3] = ["immunity_duration", "untreated_death_rate", "critical_illness"];
 
+/// How long recovered citizens stay immune
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(remote = "Self")]
+pub enum ImmunityDuration {
+    Days(i32),
+    UniformDays { min: i32, max: i32 },
+    ExponentialDays { mean: f64 },
+}
+
+/// Immunity durations are checked when the config is read, rather than when they are first sampled
+impl<'de> Deserialize<'de> for ImmunityDuration {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+    {
+        let immunity_duration = ImmunityDuration::deserialize(deserializer)?;
+        immunity_duration.validate().map_err(de::Error::custom)?;
+        Ok(immunity_duration)
+    }
+}
+
+impl Serialize for ImmunityDuration {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+    {
+        ImmunityDuration::serialize(self, serializer)
+    }
+}
+
+impl ImmunityDuration {
+    fn validate(&self) -> Result<(), String> {
+        match *self {
+            ImmunityDuration::Days(days) if days < 0 => {
+                Err(format!("Immunity can not last {} days", days))
+            }
+            ImmunityDuration::UniformDays { min, max } if !(0 <= min && min <= max) => {
+                Err(format!("Uniform immunity duration needs 0 <= min <= max, not {} and {} days", min, max))
+            }
+            ImmunityDuration::ExponentialDays { mean } if !(mean > 0.0 && mean.is_finite()) => {
+                Err(format!("Exponential immunity duration needs a positive mean, not {} days", mean))
+            }
+            _ => Ok(())
+        }
+    }
+
+    pub fn sample_days(&self, rng: &mut impl rand::RngCore) -> i32 {
+        match *self {
+            ImmunityDuration::Days(days) => days,
+            ImmunityDuration::UniformDays { min, max } => rng.gen_range(min, max + 1),
+            ImmunityDuration::ExponentialDays { mean } => {
+                let uniform: f64 = rng.gen();
+                (-mean * (1.0 - uniform).ln()).round() as i32
+            }
+        }
+    }
+}
+
+/// Severe cases that get worse and need intensive care
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct CriticalIllness {
+    /// The chance of a severe case becoming critical
+    pub percentage: f64,
+    /// Hours from becoming severe to becoming critical
+    pub delay_hours: Duration,
+    /// Days of intensive care, after which the citizen dies or recovers
+    pub stay_days: Duration,
+    pub death_rate: f64,
+    /// The death rate without an ICU bed, `death_rate` without one
+    #[serde(default)]
+    pub untreated_death_rate: Option<f64>,
+}
+
+impl CriticalIllness {
+    pub fn to_be_deceased(&self, rng: &mut impl rand::RngCore, treated: bool) -> bool {
+        let death_rate = if treated { self.death_rate } else { self.untreated_death_rate.unwrap_or(self.death_rate) };
+        rng.gen_bool(death_rate)
+    }
+}
+
+/// Durations are sampled for each citizen. The `_last_day`s are the infection day each kind of case ends on, and
+/// `exposed_duration` and `pre_symptomatic_duration` are in hours
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct Disease {
     regular_transmission_start_day: i32,
     high_transmission_start_day: i32,
-    last_day: i32,
-    asymptomatic_last_day: i32,
-    mild_infected_last_day: i32,
+    /// For severe cases
+    last_day: Duration,
+    asymptomatic_last_day: Duration,
+    mild_infected_last_day: Duration,
     regular_transmission_rate: f64,
     high_transmission_rate: f64,
     death_rate: f64,
     percentage_asymptomatic_population: f64,
     percentage_severe_infected_population: f64,
-    exposed_duration: i32,
-    pre_symptomatic_duration: i32,
+    exposed_duration: Duration,
+    pre_symptomatic_duration: Duration,
+    /// Immunity lasts for good without one
+    #[serde(default)]
+    immunity_duration: Option<ImmunityDuration>,
+    /// The death rate of severe cases who needed a hospital bed and did not get one, `death_rate` without one
+    #[serde(default)]
+    untreated_death_rate: Option<f64>,
+    /// No severe case becomes critical without one
+    #[serde(default)]
+    critical_illness: Option<CriticalIllness>,
 }
 
 impl Disease {
     #[cfg(test)]
     pub fn init(config_file_path: &str, disease_name: &String) -> Disease {
-        use std::collections::HashMap;
-        use std::fs::File;
+        Disease::from_preset(config_file_path, disease_name, &Mapping::new()).expect("Failed to load disease")
+    }
 
-        let reader = File::open(config_file_path).expect("Failed to open disease config file");
-        let yaml: HashMap<String, Disease> = serde_yaml::from_reader(reader).expect("Failed to parse disease config file");
-        let disease = yaml.get(disease_name).expect("Failed to find disease");
-        *disease
+    /// Loads the named disease from a catalogue file, replacing any parameters present in `overrides`
+    pub fn from_preset(catalogue_path: &str, name: &str, overrides: &Mapping) -> Result<Disease, Box<dyn Error>> {
+        let reader = File::open(catalogue_path)?;
+        let catalogue: Mapping = serde_yaml::from_reader(reader)?;
+        let mut parameters = match catalogue.get(&Value::String(name.to_string())) {
+            Some(Value::Mapping(parameters)) => parameters.clone(),
+            _ => return Err(format!("Disease {} not found in {}", name, catalogue_path).into())
+        };
+        for (key, value) in overrides {
+            let optional = OPTIONAL_PARAMETERS.iter().any(|p| *key == Value::from(*p));
+            if !parameters.contains_key(key) && !optional {
+                return Err(format!("Unknown disease parameter {:?}", key).into());
+            }
+            parameters.insert(key.clone(), value.clone());
+        }
+        Ok(serde_yaml::from_value(Value::Mapping(parameters))?)
     }
 
     #[cfg(test)]
@@ -56,23 +172,42 @@ impl Disease {
         Disease {
             regular_transmission_start_day,
             high_transmission_start_day,
-            last_day,
-            asymptomatic_last_day,
-            mild_infected_last_day,
+            last_day: Duration::Fixed(last_day),
+            asymptomatic_last_day: Duration::Fixed(asymptomatic_last_day),
+            mild_infected_last_day: Duration::Fixed(mild_infected_last_day),
             regular_transmission_rate,
             high_transmission_rate,
             death_rate,
             percentage_asymptomatic_population,
             percentage_severe_infected_population,
-            exposed_duration,
-            pre_symptomatic_duration
+            exposed_duration: Duration::Fixed(exposed_duration),
+            pre_symptomatic_duration: Duration::Fixed(pre_symptomatic_duration),
+            immunity_duration: None,
+            untreated_death_rate: None,
+            critical_illness: None,
         }
     }
 
+    #[cfg(test)]
+    pub fn with_critical_illness(self, critical_illness: CriticalIllness) -> Disease {
+        Disease { critical_illness: Some(critical_illness), ..self }
+    }
+
+    #[cfg(test)]
+    pub fn with_untreated_death_rate(self, untreated_death_rate: f64) -> Disease {
+        Disease { untreated_death_rate: Some(untreated_death_rate), ..self }
+    }
+
+    #[cfg(test)]
+    pub fn with_immunity_duration(self, immunity_duration: ImmunityDuration) -> Disease {
+        Disease { immunity_duration: Some(immunity_duration), ..self }
+    }
+
+    /// The high transmission rate lasts until the infection ends
     pub fn get_current_transmission_rate(&self, infection_day: i32) -> f64 {
         if self.regular_transmission_start_day < infection_day && infection_day <= self.high_transmission_start_day {
             return self.regular_transmission_rate;
-        } else if self.high_transmission_start_day < infection_day && infection_day <= self.last_day {
+        } else if self.high_transmission_start_day < infection_day {
             return self.high_transmission_rate;
         }
         0.0
@@ -86,12 +221,21 @@ impl Disease {
         false
     }
 
-    pub fn get_disease_last_day(&self) -> i32 {
-        self.last_day
+    pub fn get_disease_last_day(&self) -> &Duration {
+        &self.last_day
+    }
+
+    pub fn get_asymptomatic_last_day(&self) -> &Duration {
+        &self.asymptomatic_last_day
+    }
+
+    pub fn get_mild_infected_last_day(&self) -> &Duration {
+        &self.mild_infected_last_day
     }
 
-    pub fn to_be_deceased(&self, rng: &mut impl rand::RngCore) -> bool {
-        if rng.gen_bool(self.death_rate) {
+    pub fn to_be_deceased(&self, rng: &mut impl rand::RngCore, treated: bool) -> bool {
+        let death_rate = if treated { self.death_rate } else { self.untreated_death_rate.unwrap_or(self.death_rate) };
+        if rng.gen_bool(death_rate) {
             return true;
         }
         false
@@ -105,20 +249,79 @@ impl Disease {
         self.percentage_severe_infected_population
     }
 
-    pub fn get_exposed_duration(&self) -> i32 {
-        self.exposed_duration
+    pub fn get_exposed_duration(&self) -> &Duration {
+        &self.exposed_duration
+    }
+
+    pub fn get_pre_symptomatic_duration(&self) -> &Duration {
+        &self.pre_symptomatic_duration
+    }
+
+    pub fn get_immunity_duration(&self) -> Option<ImmunityDuration> {
+        self.immunity_duration
+    }
+
+    pub fn get_critical_illness(&self) -> Option<&CriticalIllness> {
+        self.critical_illness.as_ref()
     }
 
-    pub fn get_pre_symptomatic_duration(&self) -> i32 {
-        self.pre_symptomatic_duration
+    /// The hour at which a case that became severe at `hour` turns critical, if it does
+    pub fn critical_at(&self, hour: i32, rng: &mut impl rand::RngCore) -> Option<i32> {
+        let critical_illness = self.critical_illness.as_ref()?;
+        if rng.gen_bool(critical_illness.percentage) {
+            return Some(hour + critical_illness.delay_hours.sample(rng));
+        }
+        None
     }
 }
 
+/// A disease in the simulation config: either all parameters inline, or a preset from the disease catalogue
+/// such as `{"Preset": "covid_19", "death_rate": 0.05}`, where the remaining fields override the preset
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DiseaseConfig {
+    Inline(Box<Disease>),
+    Preset {
+        #[serde(rename = "Preset")]
+        name: String,
+        #[serde(default)]
+        catalogue: Option<String>,
+        #[serde(flatten)]
+        overrides: Mapping,
+    },
+}
+
+pub fn deserialize_disease<'de, D>(deserializer: D) -> Result<Disease, D::Error>
+    where
+        D: Deserializer<'de>,
+{
+    match DiseaseConfig::deserialize(deserializer)? {
+        DiseaseConfig::Inline(disease) => Ok(*disease),
+        DiseaseConfig::Preset { name, catalogue, overrides } => {
+            let catalogue = catalogue.unwrap_or_else(|| DEFAULT_DISEASE_CATALOGUE.to_string());
+            Disease::from_preset(&catalogue, &name, &overrides).map_err(de::Error::custom)
+        }
+    }
+}
+
+fn deserialize_population_param<'de, D>(deserializer: D) -> Result<String, D::Error>
+    where
+        D: Deserializer<'de>,
+{
+    let name = String::deserialize(deserializer)?;
+    if !PopulationRecord::ATTRIBUTES.contains(&name.as_str()) {
+        return Err(de::Error::unknown_variant(&name, &PopulationRecord::ATTRIBUTES));
+    }
+    Ok(name)
+}
+
 /// Override disease parameters for a specific population trait
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct DiseaseOverride {
+    #[serde(deserialize_with = "deserialize_population_param")]
     population_param: String,
     values: Vec<String>,
+    #[serde(deserialize_with = "deserialize_disease")]
     disease: Disease,
 }
 
@@ -129,6 +332,95 @@ impl DiseaseOverride {
             population_param, values, disease
         }
     }
+
+    fn applies_to(&self, record: &PopulationRecord) -> bool {
+        match record.get_attribute(&self.population_param) {
+            Some(value) => self.values.contains(&value),
+            None => false
+        }
+    }
+}
+
+/// The disease, along with its overrides and variants. Each citizen holds the index of the profile that applies to
+/// them, rather than a copy of the disease
+pub struct DiseaseProfiles {
+    disease: Disease,
+    overrides: Vec<DiseaseOverride>,
+    /// Replaces the course of the disease for every profile
+    model: Option<CompartmentModel>,
+    strains: Vec<Strain>,
+    /// Indexed by strain, the wild type first
+    cross_immunity: Vec<Vec<f64>>,
+}
+
+impl DiseaseProfiles {
+    pub const DEFAULT_PROFILE: usize = 0;
+
+    pub fn new(disease: Disease, overrides: Vec<DiseaseOverride>) -> DiseaseProfiles {
+        DiseaseProfiles { disease, overrides, model: None, strains: vec![], cross_immunity: vec![vec![1.0]] }
+    }
+
+    pub fn with_strains(self, strains: Vec<Strain>, cross_immunity: &[CrossImmunity]) -> DiseaseProfiles {
+        let cross_immunity = cross_immunity_matrix(&strains, cross_immunity);
+        DiseaseProfiles { strains, cross_immunity, ..self }
+    }
+
+    /// The variants; strain `i` is the variant at `i - 1`, after the wild type
+    pub fn get_strains(&self) -> &[Strain] {
+        &self.strains
+    }
+
+    pub fn has_variants(&self) -> bool {
+        !self.strains.is_empty()
+    }
+
+    /// Whether any strain is introduced at this hour or later
+    pub fn introduces_strains_from(&self, hour: i32) -> bool {
+        self.strains.iter().any(|strain| strain.introduced_at >= hour)
+    }
+
+    /// The names of all strains, the wild type first, if there are any variants
+    pub fn strain_names(&self) -> Vec<String> {
+        if !self.has_variants() {
+            return vec![];
+        }
+        std::iter::once(WILD_TYPE.to_string()).chain(self.strains.iter().map(|strain| strain.name.clone())).collect()
+    }
+
+    /// The chance of a citizen who recovered from strain `from` not being infected by an exposure to `to`
+    pub fn cross_immunity(&self, from: usize, to: usize) -> f64 {
+        self.cross_immunity[from][to]
+    }
+
+    /// The best protection against `to` of any of the strains a citizen recovered from
+    pub fn protection(&self, recovered_from: impl Iterator<Item=usize>, to: usize) -> f64 {
+        recovered_from.map(|from| self.cross_immunity(from, to)).fold(0.0, f64::max)
+    }
+
+    pub fn with_model(self, model: Option<CompartmentModel>) -> DiseaseProfiles {
+        DiseaseProfiles { model, ..self }
+    }
+
+    pub fn get_model(&self) -> Option<&CompartmentModel> {
+        self.model.as_ref()
+    }
+
+    /// The first override that matches the record wins; citizens without a match get the default disease
+    pub fn profile_for(&self, record: &PopulationRecord) -> usize {
+        self.overrides.iter()
+            .position(|disease_override| disease_override.applies_to(record))
+            .map(|index| index + 1)
+            .unwrap_or(DiseaseProfiles::DEFAULT_PROFILE)
+    }
+
+    /// Overrides only apply to the wild type
+    pub fn get(&self, profile: usize, strain: usize) -> &Disease {
+        match (profile, strain) {
+            (DiseaseProfiles::DEFAULT_PROFILE, 0) => &self.disease,
+            (_, 0) => &self.overrides[profile - 1].disease,
+            _ => &self.strains[strain - 1].disease
+        }
+    }
 }
 
 #[cfg(test)]
@@ -161,17 +453,136 @@ mod tests {
         let expected = Disease {
             regular_transmission_start_day: 10,
             high_transmission_start_day: 16,
-            last_day: 22,
-            asymptomatic_last_day: 22,
-            mild_infected_last_day: 22,
+            last_day: Duration::Fixed(22),
+            asymptomatic_last_day: Duration::Fixed(22),
+            mild_infected_last_day: Duration::Fixed(22),
             regular_transmission_rate: 0.05,
             high_transmission_rate: 0.5,
             death_rate: 0.2,
             percentage_asymptomatic_population: 0.0,
             percentage_severe_infected_population: 1.0,
-            exposed_duration: 0,
-            pre_symptomatic_duration: 0
+            exposed_duration: Duration::Fixed(0),
+            pre_symptomatic_duration: Duration::Fixed(0),
+            immunity_duration: None,
+            untreated_death_rate: None,
+            critical_illness: None,
         };
         assert_eq!(expected, disease)
     }
+
+    #[test]
+    fn should_load_preset_with_overrides() {
+        let mut overrides = Mapping::new();
+        overrides.insert(Value::from("death_rate"), Value::from(0.5));
+        let disease = Disease::from_preset("config/diseases.yaml", "sars", &overrides).unwrap();
+
+        let expected = Disease::new(5, 10, 23, 23, 23, 0.025, 0.25, 0.5, 0.0, 1.0, 0, 0);
+        assert_eq!(expected, disease);
+    }
+
+    #[test]
+    fn should_use_untreated_death_rate_without_treatment() {
+        let disease = Disease::new(5, 10, 23, 23, 23, 0.025, 0.25, 0.0, 0.0, 1.0, 0, 0).with_untreated_death_rate(1.0);
+        let mut rng = rand::thread_rng();
+
+        assert!(!disease.to_be_deceased(&mut rng, true));
+        assert!(disease.to_be_deceased(&mut rng, false));
+    }
+
+    #[test]
+    fn should_reject_unknown_preset_or_parameter() {
+        assert!(Disease::from_preset("config/diseases.yaml", "flu", &Mapping::new()).is_err());
+
+        let mut overrides = Mapping::new();
+        overrides.insert(Value::from("deth_rate"), Value::from(0.5));
+        assert!(Disease::from_preset("config/diseases.yaml", "sars", &overrides).is_err());
+    }
+
+    #[test]
+    fn should_load_preset_with_immunity_duration() {
+        let mut overrides = Mapping::new();
+        overrides.insert(Value::from("immunity_duration"),
+                         serde_yaml::from_str("UniformDays: {min: 90, max: 180}").unwrap());
+        let disease = Disease::from_preset("config/diseases.yaml", "sars", &overrides).unwrap();
+        let mut rng = rand::thread_rng();
+
+        let days = disease.get_immunity_duration().unwrap().sample_days(&mut rng);
+        assert!(90 <= days && days <= 180);
+        assert_eq!(ImmunityDuration::Days(30).sample_days(&mut rng), 30);
+        assert!(ImmunityDuration::ExponentialDays { mean: 100.0 }.sample_days(&mut rng) >= 0);
+    }
+
+    #[test]
+    fn should_reject_immunity_durations_that_can_not_be_sampled() {
+        let error_of = |immunity_duration: &str| {
+            serde_yaml::from_str::<ImmunityDuration>(immunity_duration).unwrap_err().to_string()
+        };
+
+        assert!(error_of("Days: -1").contains("Immunity can not last -1 days"));
+        assert!(error_of("UniformDays: {min: 180, max: 90}").contains("needs 0 <= min <= max"));
+        assert!(error_of("UniformDays: {min: -10, max: 90}").contains("needs 0 <= min <= max"));
+        assert!(error_of("ExponentialDays: {mean: 0.0}").contains("needs a positive mean"));
+        assert!(error_of("ExponentialDays: {mean: -30.0}").contains("needs a positive mean"));
+        assert!(serde_yaml::from_str::<ImmunityDuration>("UniformDays: {min: 90, max: 90}").is_ok());
+
+        let mut overrides = Mapping::new();
+        overrides.insert(Value::from("immunity_duration"),
+                         serde_yaml::from_str("UniformDays: {min: 180, max: 90}").unwrap());
+        assert!(Disease::from_preset("config/diseases.yaml", "sars", &overrides).is_err());
+    }
+
+    fn record(age: &str, working: bool) -> PopulationRecord {
+        PopulationRecord { ind: 0, age: age.to_string(), sex: None, working, pub_transport: false }
+    }
+
+    #[test]
+    fn should_resolve_profile_from_first_matching_override() {
+        let base = Disease::new(5, 20, 40, 9, 12, 0.025, 0.25, 0.035, 0.3, 0.3, 48, 48);
+        let elderly = Disease::new(5, 20, 40, 9, 12, 0.025, 0.25, 0.2, 0.3, 0.3, 48, 48);
+        let workers = Disease::new(5, 20, 40, 9, 12, 0.025, 0.25, 0.1, 0.3, 0.3, 48, 48);
+        let overrides = vec![
+            DiseaseOverride::new(String::from("age"), vec!["70-74".to_string(), "80+".to_string()], elderly.clone()),
+            DiseaseOverride::new(String::from("working"), vec!["True".to_string()], workers.clone()),
+        ];
+        let profiles = DiseaseProfiles::new(base.clone(), overrides);
+
+        assert_eq!(profiles.profile_for(&record("80+", true)), 1);
+        assert_eq!(profiles.profile_for(&record("30-34", true)), 2);
+        assert_eq!(profiles.profile_for(&record("30-34", false)), DiseaseProfiles::DEFAULT_PROFILE);
+
+        assert_eq!(*profiles.get(1, 0), elderly);
+        assert_eq!(*profiles.get(2, 0), workers);
+        assert_eq!(*profiles.get(DiseaseProfiles::DEFAULT_PROFILE, 0), base);
+    }
+
+    #[test]
+    fn should_reject_override_of_unknown_population_param() {
+        let disease = r#""disease": {"Preset": "sars", "catalogue": "config/diseases.yaml"}"#;
+        let known = format!(r#"{{"population_param": "age", "values": ["80+"], {}}}"#, disease);
+        let misspelled = format!(r#"{{"population_param": "agee", "values": ["80+"], {}}}"#, disease);
+
+        assert!(serde_json::from_str::<DiseaseOverride>(&known).is_ok());
+        let error = serde_json::from_str::<DiseaseOverride>(&misspelled).unwrap_err().to_string();
+        assert!(error.contains("unknown variant `agee`"), "{}", error);
+    }
+
+    #[test]
+    fn should_use_variant_disease_whatever_the_profile() {
+        let base = Disease::new(5, 20, 40, 9, 12, 0.025, 0.25, 0.035, 0.3, 0.3, 48, 48);
+        let elderly = Disease::new(5, 20, 40, 9, 12, 0.025, 0.25, 0.2, 0.3, 0.3, 48, 48);
+        let variant = Disease::new(5, 20, 40, 9, 12, 0.05, 0.5, 0.035, 0.3, 0.3, 48, 48);
+        let overrides = vec![DiseaseOverride::new(String::from("age"), vec!["80+".to_string()], elderly.clone())];
+        let strains = vec![Strain { name: "delta".to_string(), disease: variant.clone(), introduced_at: 24, introductions: 1 }];
+        let profiles = DiseaseProfiles::new(base, overrides).with_strains(strains, &[]);
+
+        assert_eq!(*profiles.get(1, 0), elderly);
+        assert_eq!(*profiles.get(1, 1), variant);
+        assert_eq!(profiles.strain_names(), vec![WILD_TYPE.to_string(), "delta".to_string()]);
+        assert_eq!(profiles.cross_immunity(0, 1), 0.0);
+        assert_eq!(profiles.protection(vec![0, 1].into_iter(), 0), 1.0);
+        assert_eq!(profiles.protection(vec![0, 1].into_iter(), 1), 1.0);
+        assert_eq!(profiles.protection(std::iter::empty(), 1), 0.0);
+        assert!(profiles.introduces_strains_from(24));
+        assert!(!profiles.introduces_strains_from(25));
+    }
 }
@@ -17,11 +17,11 @@
  *
  */
 
+use crate::compartment_model::{CompartmentKind, CompartmentModel};
 use crate::disease::Disease;
+use crate::hospital_beds::Bed;
 
 use rand::Rng;
-use crate::constants;
-use rand::seq::SliceRandom;
 
 #[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub enum State {
@@ -36,13 +36,29 @@ pub enum State {
 pub enum InfectionSeverity {
     Pre { at_hour: i32 },
     Mild,
-    Severe,
+    /// Turns critical at the hour `critical_at`, if ever
+    Severe { critical_at: Option<i32> },
+    /// Needs intensive care until the infection day `until_day`
+    Critical { until_day: i32 },
 }
 
 #[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct DiseaseStateMachine {
     pub state: State,
     infection_day: i32,
+    infections: i32,
+    /// Days until a recovered citizen becomes susceptible again, if immunity wanes
+    immune_days: Option<i32>,
+    /// When the current stage ends: the hour while exposed or pre-symptomatic, and the infection day once the
+    /// severity is known. Sampled from the disease on the first check in each stage
+    stage_end: Option<i32>,
+    /// The compartment of the compartment model, when there is one, and the hour at which the citizen leaves it
+    compartment: Option<usize>,
+    leaves_at: Option<i32>,
+    /// The strain of the current or last infection
+    strain: usize,
+    /// A bit for each strain of the earlier infections, while the citizen is still immune
+    recovered_from: u64,
 }
 
 impl DiseaseStateMachine {
@@ -50,6 +66,13 @@ impl DiseaseStateMachine {
         DiseaseStateMachine {
             state: State::Susceptible {},
             infection_day: 0,
+            infections: 0,
+            immune_days: None,
+            stage_end: None,
+            compartment: None,
+            leaves_at: None,
+            strain: 0,
+            recovered_from: 0,
         }
     }
 
@@ -65,7 +88,8 @@ impl DiseaseStateMachine {
     pub fn expose(&mut self, current_hour: i32) {
         match self.state {
             State::Susceptible {} => {
-                self.state = State::Exposed { at_hour: current_hour }
+                self.state = State::Exposed { at_hour: current_hour };
+                self.infections += 1;
             }
             _ => {
                 panic!("Invalid state transition!")
@@ -73,18 +97,44 @@ impl DiseaseStateMachine {
         }
     }
 
+    /// Exposes a susceptible citizen to `strain`, or a recovered one to a strain they are not fully protected against
+    pub fn expose_to(&mut self, current_hour: i32, strain: usize) {
+        if let State::Recovered {} = self.state {
+            self.recovered_from |= 1 << self.strain;
+            self.state = State::Susceptible {};
+            self.infection_day = 0;
+            self.immune_days = None;
+        }
+        self.expose(current_hour);
+        self.strain = strain;
+    }
+
+    pub fn get_strain(&self) -> usize {
+        self.strain
+    }
+
+    /// The strains a recovered citizen had, the last one included
+    pub fn recovered_strains(&self) -> impl Iterator<Item=usize> {
+        let recovered_from = match self.state {
+            State::Recovered {} => self.recovered_from | 1 << self.strain,
+            _ => 0
+        };
+        (0..64).filter(move |strain| recovered_from & 1 << strain != 0)
+    }
+
     pub fn infect(&mut self, rng: &mut impl rand::RngCore, sim_hr: i32, disease: &Disease) -> bool {
         match self.state {
             State::Exposed { at_hour } => {
-                let option = constants::RANGE_FOR_EXPOSED.choose(rng);
-                let random_factor = *option.unwrap();
-                if sim_hr - at_hour >= disease.get_exposed_duration() + random_factor {
+                let infectious_at = *self.stage_end
+                    .get_or_insert_with(|| at_hour + disease.get_exposed_duration().sample(rng));
+                if sim_hr >= infectious_at {
                     let symptoms = rng.gen_bool(1.0 - disease.get_percentage_asymptomatic_population());
                     let mut severity = InfectionSeverity::Pre { at_hour: sim_hr };
                     if !symptoms {
                         severity = InfectionSeverity::Mild {};
                     }
                     self.state = State::Infected { symptoms, severity };
+                    self.stage_end = None;
                     return true;
                 }
                 return false;
@@ -95,20 +145,31 @@ impl DiseaseStateMachine {
         }
     }
 
-    pub fn change_infection_severity(&mut self, current_hour: i32, rng: &mut impl rand::RngCore, disease: &Disease) {
+    /// `protection` is the chance of an infection that would have been severe staying mild
+    pub fn change_infection_severity(&mut self, current_hour: i32, rng: &mut impl rand::RngCore, disease: &Disease,
+                                     protection: f64) {
         match self.state {
             State::Infected { symptoms: true, severity } => {
                 match severity {
                     InfectionSeverity::Pre { at_hour } => {
-                        if current_hour - at_hour >= disease.get_pre_symptomatic_duration() {
+                        let symptoms_at = *self.stage_end
+                            .get_or_insert_with(|| at_hour + disease.get_pre_symptomatic_duration().sample(rng));
+                        if current_hour >= symptoms_at {
                             let mut severity = InfectionSeverity::Mild {};
-                            let severe = rng.gen_bool(disease.get_percentage_severe_infected_population());
+                            let severe = rng.gen_bool(disease.get_percentage_severe_infected_population() * (1.0 - protection));
                             if severe {
-                                severity = InfectionSeverity::Severe {};
+                                severity = InfectionSeverity::Severe { critical_at: disease.critical_at(current_hour, rng) };
                             }
                             self.state = State::Infected { symptoms: true, severity };
+                            self.stage_end = None;
                         }
                     }
+                    InfectionSeverity::Severe { critical_at: Some(critical_at) } if current_hour >= critical_at => {
+                        let stay_days = disease.get_critical_illness().map_or(0, |critical| critical.stay_days.sample(rng));
+                        let severity = InfectionSeverity::Critical { until_day: self.infection_day + stay_days };
+                        self.state = State::Infected { symptoms: true, severity };
+                        self.stage_end = None;
+                    }
                     _ => {}
                 }
             }
@@ -120,8 +181,9 @@ impl DiseaseStateMachine {
 
     pub fn hospitalize(&mut self, disease: &Disease, immunity: i32) -> bool {
         match self.state {
-            State::Infected { symptoms: true, severity: InfectionSeverity::Severe} =>
+            State::Infected { symptoms: true, severity: InfectionSeverity::Severe { .. } } =>
                 return disease.to_be_hospitalized(self.infection_day + immunity),
+            State::Infected { symptoms: true, severity: InfectionSeverity::Critical { .. } } => true,
             State::Infected { .. } => { false }
             _ => {
                 panic!("Invalid state transition!")
@@ -129,27 +191,43 @@ impl DiseaseStateMachine {
         }
     }
 
-    pub fn decease(&mut self, rng: &mut impl rand::RngCore, disease: &Disease) -> (i32, i32) {
+    /// `treated` is false for a severe or critical case that needed a hospital bed and did not get one
+    pub fn decease(&mut self, rng: &mut impl rand::RngCore, disease: &Disease, treated: bool) -> (i32, i32) {
         match self.state {
-            State::Infected { symptoms: true, severity: InfectionSeverity::Severe {} } => {
-                if self.infection_day == disease.get_disease_last_day() {
-                    if disease.to_be_deceased(rng) {
+            State::Infected { symptoms: true, severity: InfectionSeverity::Critical { until_day } } => {
+                if self.infection_day >= until_day {
+                    let critical_illness = disease.get_critical_illness().expect("Critical case without critical illness");
+                    if critical_illness.to_be_deceased(rng, treated) {
                         self.state = State::Deceased {};
                         return (1, 0);
                     }
-                    self.state = State::Recovered {};
+                    self.recover(rng, disease);
+                    return (0, 1);
+                }
+            }
+            State::Infected { symptoms: true, severity: InfectionSeverity::Severe { .. } } => {
+                let last_day = *self.stage_end.get_or_insert_with(|| disease.get_disease_last_day().sample(rng));
+                if self.infection_day >= last_day {
+                    if disease.to_be_deceased(rng, treated) {
+                        self.state = State::Deceased {};
+                        return (1, 0);
+                    }
+                    self.recover(rng, disease);
                     return (0, 1);
                 }
             }
             State::Infected { symptoms:true, severity: InfectionSeverity::Mild{} } => {
-                if self.infection_day == constants::MILD_INFECTED_LAST_DAY {
-                    self.state = State::Recovered {};
+                let last_day = *self.stage_end.get_or_insert_with(|| disease.get_mild_infected_last_day().sample(rng));
+                if self.infection_day >= last_day {
+                    self.recover(rng, disease);
                     return (0, 1);
                 }
             }
+            State::Infected { severity: InfectionSeverity::Pre { .. }, .. } => {}
             State::Infected { .. } => {
-                if self.infection_day == constants::ASYMPTOMATIC_LAST_DAY {
-                    self.state = State::Recovered {};
+                let last_day = *self.stage_end.get_or_insert_with(|| disease.get_asymptomatic_last_day().sample(rng));
+                if self.infection_day >= last_day {
+                    self.recover(rng, disease);
                     return (0, 1);
                 }
             }
@@ -160,6 +238,99 @@ impl DiseaseStateMachine {
         (0, 0)
     }
 
+    fn recover(&mut self, rng: &mut impl rand::RngCore, disease: &Disease) {
+        self.state = State::Recovered {};
+        self.stage_end = None;
+        self.immune_days = disease.get_immunity_duration().map(|duration| duration.sample_days(rng));
+    }
+
+    /// Puts a citizen in the first compartment of the model matching their state, at the start of the simulation
+    pub fn start_in(&mut self, model: &CompartmentModel, rng: &mut impl rand::RngCore) {
+        let kind = match self.state {
+            State::Susceptible {} => CompartmentKind::Susceptible,
+            State::Exposed { .. } => CompartmentKind::Exposed,
+            State::Infected { .. } => CompartmentKind::Infected,
+            State::Recovered {} => CompartmentKind::Recovered,
+            State::Deceased {} => CompartmentKind::Deceased,
+        };
+        self.enter(model, model.start_of(kind), 0, rng);
+    }
+
+    /// Leaves a susceptible compartment of the model along one of its transitions, if it has any. Returns whether
+    /// the citizen was exposed
+    pub fn expose_in(&mut self, model: &CompartmentModel, current_hour: i32, rng: &mut impl rand::RngCore) -> bool {
+        let compartment = self.compartment.expect("Citizen is not in the compartment model");
+        match model.next(compartment, true, rng) {
+            Some(next) => {
+                self.infections += 1;
+                self.enter(model, next, current_hour, rng);
+                true
+            }
+            None => false
+        }
+    }
+
+    /// Moves on to the next compartment of the model once the dwell time in this one is over. `treated` is false
+    /// for citizens who needed a hospital bed and did not get one
+    pub fn progress(&mut self, model: &CompartmentModel, current_hour: i32, treated: bool, rng: &mut impl rand::RngCore) {
+        match (self.compartment, self.leaves_at) {
+            (Some(compartment), Some(leaves_at)) if current_hour >= leaves_at => {
+                match model.next(compartment, treated, rng) {
+                    Some(next) => self.enter(model, next, current_hour, rng),
+                    None => self.leaves_at = None
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn enter(&mut self, model: &CompartmentModel, compartment: usize, current_hour: i32, rng: &mut impl rand::RngCore) {
+        let entered = model.get(compartment);
+        self.state = match entered.kind {
+            CompartmentKind::Susceptible => {
+                self.infection_day = 0;
+                State::Susceptible {}
+            }
+            CompartmentKind::Exposed => State::Exposed { at_hour: current_hour },
+            CompartmentKind::Infected => State::Infected { symptoms: entered.symptomatic, severity: InfectionSeverity::Mild },
+            CompartmentKind::Recovered => State::Recovered {},
+            CompartmentKind::Deceased => State::Deceased {},
+        };
+        self.compartment = Some(compartment);
+        self.leaves_at = match entered.kind {
+            CompartmentKind::Susceptible => None,
+            _ => entered.dwell.as_ref().map(|dwell| current_hour + dwell.sample(rng))
+        };
+    }
+
+    pub fn get_compartment(&self) -> Option<usize> {
+        self.compartment
+    }
+
+    /// The bed needed in the current compartment of the model
+    pub fn needs_bed(&self, model: &CompartmentModel) -> Option<Bed> {
+        self.compartment.and_then(|compartment| model.get(compartment).bed)
+    }
+
+    /// Counts down a day of immunity, and makes a recovered citizen susceptible again once it has waned
+    pub fn update_immunity(&mut self) {
+        if let (State::Recovered {}, Some(days)) = (self.state, self.immune_days) {
+            if days <= 1 {
+                self.state = State::Susceptible {};
+                self.infection_day = 0;
+                self.immune_days = None;
+                self.recovered_from = 0;
+            } else {
+                self.immune_days = Some(days - 1);
+            }
+        }
+    }
+
+    /// Infections after the first one
+    pub fn get_reinfections(&self) -> i32 {
+        (self.infections - 1).max(0)
+    }
+
     pub fn is_susceptible(&self) -> bool {
         match self.state {
             State::Susceptible {} => {
@@ -169,6 +340,10 @@ impl DiseaseStateMachine {
         }
     }
 
+    pub fn is_recovered(&self) -> bool {
+        matches!(self.state, State::Recovered {})
+    }
+
     pub fn is_exposed(&self) -> bool {
         match self.state {
             State::Exposed { .. } => {
@@ -229,19 +404,22 @@ impl DiseaseStateMachine {
     // should be called only during initialization
     pub fn set_mild_asymptomatic(&mut self) {
         self.state = State::Infected { symptoms: false, severity: InfectionSeverity::Mild };
-        self.infection_day = 1
+        self.infection_day = 1;
+        self.infections = 1;
     }
 
     // should be called only during initialization
     pub fn set_mild_symptomatic(&mut self) {
         self.state = State::Infected { symptoms: true, severity: InfectionSeverity::Mild };
-        self.infection_day = 1
+        self.infection_day = 1;
+        self.infections = 1;
     }
 
     // should be called only during initialization
     pub fn set_severe_infected(&mut self) {
-        self.state = State::Infected { symptoms: true, severity: InfectionSeverity::Severe };
-        self.infection_day = 1
+        self.state = State::Infected { symptoms: true, severity: InfectionSeverity::Severe { critical_at: None } };
+        self.infection_day = 1;
+        self.infections = 1;
     }
 
     #[cfg(test)]
@@ -261,7 +439,14 @@ impl DiseaseStateMachine {
 
     pub fn is_infected_severe(&self) -> bool {
         match self.state {
-            State::Infected { symptoms: true, severity: InfectionSeverity::Severe } => { true }
+            State::Infected { symptoms: true, severity: InfectionSeverity::Severe { .. } } => { true }
+            _ => { false }
+        }
+    }
+
+    pub fn is_critical(&self) -> bool {
+        match self.state {
+            State::Infected { symptoms: true, severity: InfectionSeverity::Critical { .. } } => { true }
             _ => { false }
         }
     }
@@ -270,6 +455,8 @@ impl DiseaseStateMachine {
 #[cfg(test)]
 mod tests {
     use rand::thread_rng;
+    use crate::disease::{CriticalIllness, ImmunityDuration};
+    use crate::duration::Duration;
     use super::*;
 
     #[test]
@@ -332,7 +519,7 @@ mod tests {
 
         machine.state = State::Infected { symptoms: true, severity: InfectionSeverity::Pre { at_hour: 100 } };
 
-        machine.change_infection_severity(140, &mut rng, &disease);
+        machine.change_infection_severity(140, &mut rng, &disease, 0.0);
 
         let result = match machine.state {
             State::Infected { symptoms: true, severity } => {
@@ -357,7 +544,7 @@ mod tests {
 
         machine.state = State::Infected { symptoms: true, severity: InfectionSeverity::Pre { at_hour: 100 } };
 
-        machine.change_infection_severity(120, &mut rng, &disease);
+        machine.change_infection_severity(120, &mut rng, &disease, 0.0);
 
         let result = match machine.state {
             State::Infected { symptoms: true, severity } => {
@@ -374,6 +561,79 @@ mod tests {
         assert_eq!(result, true);
     }
 
+    #[test]
+    fn should_keep_infection_mild_when_protected_from_severity() {
+        let mut machine = DiseaseStateMachine::new();
+        let disease = Disease::new(10, 20, 40, 9, 12, 0.025, 0.25, 0.02, 0.3, 1.0, 24, 24);
+        let mut rng = thread_rng();
+
+        machine.state = State::Infected { symptoms: true, severity: InfectionSeverity::Pre { at_hour: 100 } };
+        machine.change_infection_severity(140, &mut rng, &disease, 1.0);
+
+        assert_eq!(machine.state, State::Infected { symptoms: true, severity: InfectionSeverity::Mild {} });
+    }
+
+    #[test]
+    fn should_become_susceptible_again_once_immunity_wanes() {
+        let mut machine = DiseaseStateMachine::new();
+        let disease = Disease::new(10, 20, 40, 9, 12, 0.025, 0.25, 0.02, 0.3, 0.3, 24, 24)
+            .with_immunity_duration(ImmunityDuration::Days(2));
+        let mut rng = thread_rng();
+
+        machine.expose(10);
+        machine.state = State::Infected { symptoms: false, severity: InfectionSeverity::Mild };
+        machine.infection_day = 9;
+        assert_eq!(machine.decease(&mut rng, &disease, false), (0, 1));
+
+        machine.update_immunity();
+        assert_eq!(machine.state, State::Recovered {});
+        machine.update_immunity();
+        assert!(machine.is_susceptible());
+        assert_eq!(machine.get_infection_day(), 0);
+        assert_eq!(machine.get_reinfections(), 0);
+
+        machine.expose(100);
+        assert_eq!(machine.get_reinfections(), 1);
+    }
+
+    #[test]
+    fn should_turn_critical_after_delay_and_decease_at_its_death_rate() {
+        let critical_illness = CriticalIllness { percentage: 1.0, delay_hours: Duration::Fixed(24), stay_days: Duration::Fixed(5), death_rate: 1.0, untreated_death_rate: None };
+        let disease = Disease::new(10, 20, 40, 9, 12, 0.025, 0.25, 0.0, 0.3, 1.0, 24, 24)
+            .with_critical_illness(critical_illness);
+        let mut machine = DiseaseStateMachine::new();
+        let mut rng = thread_rng();
+        machine.expose(0);
+        machine.state = State::Infected { symptoms: true, severity: InfectionSeverity::Pre { at_hour: 0 } };
+        machine.infection_day = 2;
+
+        machine.change_infection_severity(24, &mut rng, &disease, 0.0);
+        assert_eq!(machine.state, State::Infected { symptoms: true, severity: InfectionSeverity::Severe { critical_at: Some(48) } });
+        machine.change_infection_severity(47, &mut rng, &disease, 0.0);
+        assert!(machine.is_infected_severe());
+        machine.change_infection_severity(48, &mut rng, &disease, 0.0);
+        assert_eq!(machine.state, State::Infected { symptoms: true, severity: InfectionSeverity::Critical { until_day: 7 } });
+        assert!(machine.hospitalize(&disease, 0));
+
+        assert_eq!(machine.decease(&mut rng, &disease, true), (0, 0));
+        machine.infection_day = 7;
+        assert_eq!(machine.decease(&mut rng, &disease, true), (1, 0));
+    }
+
+    #[test]
+    fn should_stay_immune_without_immunity_duration() {
+        let mut machine = DiseaseStateMachine::new();
+        let disease = Disease::new(10, 20, 40, 9, 12, 0.025, 0.25, 0.02, 0.3, 0.3, 24, 24);
+
+        machine.set_mild_asymptomatic();
+        machine.infection_day = 9;
+        machine.decease(&mut thread_rng(), &disease, false);
+        for _ in 0..1000 {
+            machine.update_immunity();
+        }
+        assert_eq!(machine.state, State::Recovered {});
+    }
+
     #[test]
     fn should_check_if_pre_symptomatic() {
         let mut machine = DiseaseStateMachine::new();
@@ -405,7 +665,7 @@ mod tests {
     fn should_set_severe_infected() {
         let mut machine = DiseaseStateMachine::new();
         machine.set_severe_infected();
-        assert_eq!(machine.state, State::Infected { symptoms: true, severity: InfectionSeverity::Severe });
+        assert_eq!(machine.state, State::Infected { symptoms: true, severity: InfectionSeverity::Severe { critical_at: None } });
         assert_eq!(machine.infection_day, 1);
     }
 
@@ -416,7 +676,7 @@ mod tests {
         machine.state = State::Infected { symptoms: true, severity: InfectionSeverity::Mild };
         assert_eq!(machine.is_symptomatic(), true);
 
-        machine.state = State::Infected { symptoms: true, severity: InfectionSeverity::Severe };
+        machine.state = State::Infected { symptoms: true, severity: InfectionSeverity::Severe { critical_at: None } };
         assert_eq!(machine.is_symptomatic(), true);
 
         machine.state = State::Infected { symptoms: false, severity: InfectionSeverity::Mild};
@@ -425,4 +685,92 @@ mod tests {
         machine.state = State::Infected { symptoms: true, severity: InfectionSeverity::Pre { at_hour: 100 } };
         assert_eq!(machine.is_symptomatic(), false);
     }
+
+    #[test]
+    fn should_sample_exposed_duration_for_each_citizen() {
+        let disease: Disease = serde_json::from_str(r#"{
+            "regular_transmission_start_day": 5, "high_transmission_start_day": 20, "last_day": 40,
+            "asymptomatic_last_day": 9, "mild_infected_last_day": 12, "regular_transmission_rate": 0.025,
+            "high_transmission_rate": 0.25, "death_rate": 0.035, "percentage_asymptomatic_population": 0.3,
+            "percentage_severe_infected_population": 0.3, "exposed_duration": {"Uniform": {"min": 10, "max": 50}},
+            "pre_symptomatic_duration": 48
+        }"#).unwrap();
+        let mut rng = thread_rng();
+
+        let infectious_hours: Vec<i32> = (0..100).map(|_| {
+            let mut machine = DiseaseStateMachine::new();
+            machine.expose(0);
+            (0..100).find(|hour| machine.infect(&mut rng, *hour, &disease)).unwrap()
+        }).collect();
+
+        assert!(infectious_hours.iter().all(|hour| (10..=50).contains(hour)));
+        assert!(infectious_hours.iter().any(|hour| *hour != infectious_hours[0]));
+    }
+
+    #[test]
+    fn should_move_through_compartment_model() {
+        let model: CompartmentModel = serde_yaml::from_str(r#"
+compartments:
+  - {name: S, kind: Susceptible}
+  - {name: E, kind: Exposed, dwell: 24}
+  - {name: I, kind: Infected, dwell: 48, symptomatic: true}
+  - {name: R, kind: Recovered, dwell: 100}
+transitions:
+  - {from: S, to: E}
+  - {from: E, to: I}
+  - {from: I, to: R}
+  - {from: R, to: S}
+"#).unwrap();
+        let mut rng = thread_rng();
+        let mut machine = DiseaseStateMachine::new();
+        machine.start_in(&model, &mut rng);
+        assert_eq!(machine.get_compartment(), Some(0));
+
+        assert!(machine.expose_in(&model, 10, &mut rng));
+        assert_eq!(machine.state, State::Exposed { at_hour: 10 });
+        machine.progress(&model, 33, true, &mut rng);
+        assert!(machine.is_exposed());
+        machine.progress(&model, 34, true, &mut rng);
+        assert!(machine.is_symptomatic());
+        machine.progress(&model, 82, true, &mut rng);
+        assert_eq!(machine.state, State::Recovered {});
+        machine.progress(&model, 182, true, &mut rng);
+        assert!(machine.is_susceptible());
+        assert_eq!(machine.get_compartment(), Some(0));
+        assert_eq!(machine.get_reinfections(), 0);
+    }
+
+    #[test]
+    fn should_expose_recovered_citizen_to_another_strain() {
+        let mut machine = DiseaseStateMachine::new();
+        machine.state = State::Recovered {};
+        machine.infection_day = 12;
+        machine.infections = 1;
+
+        machine.expose_to(100, 1);
+
+        assert_eq!(machine.state, State::Exposed { at_hour: 100 });
+        assert_eq!(machine.get_strain(), 1);
+        assert_eq!(machine.infection_day, 0);
+        assert_eq!(machine.get_reinfections(), 1);
+    }
+
+    #[test]
+    fn should_remember_every_strain_recovered_from_until_immunity_wanes() {
+        let mut machine = DiseaseStateMachine::new();
+        machine.expose_to(10, 0);
+        machine.state = State::Recovered {};
+        machine.expose_to(100, 2);
+        assert_eq!(machine.recovered_strains().count(), 0);
+
+        machine.state = State::Recovered {};
+        machine.immune_days = Some(1);
+        assert_eq!(machine.recovered_strains().collect::<Vec<_>>(), vec![0, 2]);
+
+        machine.update_immunity();
+        assert!(machine.is_susceptible());
+        machine.expose_to(200, 1);
+        machine.state = State::Recovered {};
+        assert_eq!(machine.recovered_strains().collect::<Vec<_>>(), vec![1]);
+    }
 }
@@ -0,0 +1,216 @@
+/*
+ * EpiRust
+ * Copyright (c) 2020  ThoughtWorks, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use rand::Rng;
+use serde::{Deserialize, Deserializer};
+use serde::de;
+
+/// How long a citizen stays in a state, in the unit of the parameter it is for: a number, as in older configs, or a
+/// distribution sampled for each citizen, such as `{"Gamma": {"shape": 2.0, "scale": 24.0}}`
+#[derive(Serialize, Debug, PartialEq, Clone)]
+#[serde(untagged)]
+pub enum Duration {
+    Fixed(i32),
+    Distribution(Distribution),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DurationConfig {
+    Fixed(i32),
+    Distribution(Distribution),
+}
+
+/// Distributions are checked when the config is read, rather than when they are first sampled
+impl<'de> Deserialize<'de> for Duration {
+    fn deserialize<D>(deserializer: D) -> Result<Duration, D::Error>
+        where
+            D: Deserializer<'de>,
+    {
+        match DurationConfig::deserialize(deserializer)? {
+            DurationConfig::Fixed(value) if value < 0 => {
+                Err(de::Error::custom(format!("Duration of {} can not be negative", value)))
+            }
+            DurationConfig::Fixed(value) => Ok(Duration::Fixed(value)),
+            DurationConfig::Distribution(distribution) => {
+                distribution.validate().map_err(de::Error::custom)?;
+                Ok(Duration::Distribution(distribution))
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub enum Distribution {
+    Uniform { min: f64, max: f64 },
+    Gamma { shape: f64, scale: f64 },
+    /// The logarithm of the duration is normally distributed
+    LogNormal { mu: f64, sigma: f64 },
+    /// A histogram, where each of the `values` is taken with the matching relative weight
+    Empirical { values: Vec<i32>, weights: Vec<f64> },
+}
+
+impl Duration {
+    /// Rounded to the nearest whole unit, and never negative
+    pub fn sample(&self, rng: &mut impl rand::RngCore) -> i32 {
+        match self {
+            Duration::Fixed(value) => *value,
+            Duration::Distribution(distribution) => distribution.sample(rng).round().max(0.0) as i32,
+        }
+    }
+}
+
+impl Distribution {
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            Distribution::Uniform { min, max } if !(min <= max && min.is_finite() && max.is_finite()) => {
+                Err(format!("Uniform distribution needs min <= max, not {} and {}", min, max))
+            }
+            Distribution::Gamma { shape, scale } if !(*shape > 0.0 && *scale > 0.0) => {
+                Err(format!("Gamma distribution needs a positive shape and scale, not {} and {}", shape, scale))
+            }
+            Distribution::LogNormal { mu, sigma } if !(*sigma >= 0.0 && sigma.is_finite() && mu.is_finite()) => {
+                Err(format!("LogNormal distribution needs a finite mu and a sigma that is not negative, not {} and {}",
+                            mu, sigma))
+            }
+            Distribution::Empirical { values, .. } if values.is_empty() => {
+                Err("Empirical distribution without values".to_string())
+            }
+            Distribution::Empirical { values, weights } if values.len() != weights.len() => {
+                Err(format!("Empirical distribution with {} values and {} weights", values.len(), weights.len()))
+            }
+            Distribution::Empirical { weights, .. } if weights.iter().any(|weight| !(*weight >= 0.0 && weight.is_finite())) => {
+                Err(format!("Empirical distribution with weights {:?}; they can not be negative", weights))
+            }
+            Distribution::Empirical { weights, .. } if weights.iter().sum::<f64>() <= 0.0 => {
+                Err("Empirical distribution with weights that are all zero".to_string())
+            }
+            _ => Ok(())
+        }
+    }
+
+    fn sample(&self, rng: &mut impl rand::RngCore) -> f64 {
+        match self {
+            Distribution::Uniform { min, max } => min + (max - min) * rng.gen::<f64>(),
+            Distribution::Gamma { shape, scale } => standard_gamma(*shape, rng) * scale,
+            Distribution::LogNormal { mu, sigma } => (mu + sigma * standard_normal(rng)).exp(),
+            Distribution::Empirical { values, weights } => {
+                let mut remaining = rng.gen::<f64>() * weights.iter().sum::<f64>();
+                for (value, weight) in values.iter().zip(weights) {
+                    if remaining < *weight {
+                        return *value as f64;
+                    }
+                    remaining -= weight;
+                }
+                *values.last().expect("Empirical distribution without values") as f64
+            }
+        }
+    }
+}
+
+/// Box-Muller transform
+fn standard_normal(rng: &mut impl rand::RngCore) -> f64 {
+    let u1 = 1.0 - rng.gen::<f64>();
+    let u2 = rng.gen::<f64>();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+}
+
+/// Marsaglia and Tsang's method, boosted for shapes below 1
+fn standard_gamma(shape: f64, rng: &mut impl rand::RngCore) -> f64 {
+    if shape < 1.0 {
+        let u = 1.0 - rng.gen::<f64>();
+        return standard_gamma(shape + 1.0, rng) * u.powf(1.0 / shape);
+    }
+    let d = shape - 1.0 / 3.0;
+    let c = 1.0 / (9.0 * d).sqrt();
+    loop {
+        let x = standard_normal(rng);
+        let v = (1.0 + c * x).powi(3);
+        if v <= 0.0 {
+            continue;
+        }
+        let u = 1.0 - rng.gen::<f64>();
+        if u.ln() < 0.5 * x * x + d - d * v + d * v.ln() {
+            return d * v;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64;
+
+    use super::*;
+
+    fn mean_of(duration: &Duration) -> f64 {
+        let mut rng = Pcg64::seed_from_u64(7);
+        let samples = 20000;
+        (0..samples).map(|_| duration.sample(&mut rng) as f64).sum::<f64>() / samples as f64
+    }
+
+    #[test]
+    fn should_read_fixed_durations_and_distributions() {
+        let fixed: Duration = serde_json::from_str("48").unwrap();
+        assert_eq!(fixed, Duration::Fixed(48));
+
+        let gamma: Duration = serde_json::from_str(r#"{"Gamma": {"shape": 2.0, "scale": 3.0}}"#).unwrap();
+        assert_eq!(gamma, Duration::Distribution(Distribution::Gamma { shape: 2.0, scale: 3.0 }));
+    }
+
+    #[test]
+    fn should_reject_durations_that_can_not_be_sampled() {
+        let error_of = |json: &str| serde_json::from_str::<Duration>(json).unwrap_err().to_string();
+
+        assert!(error_of(r#"{"Empirical": {"values": [], "weights": []}}"#).contains("without values"));
+        assert!(error_of(r#"{"Empirical": {"values": [1, 2], "weights": [1.0]}}"#).contains("2 values and 1 weights"));
+        assert!(error_of(r#"{"Empirical": {"values": [1, 2], "weights": [1.0, -1.0]}}"#).contains("can not be negative"));
+        assert!(error_of(r#"{"Empirical": {"values": [1, 2], "weights": [0.0, 0.0]}}"#).contains("all zero"));
+        assert!(error_of(r#"{"Gamma": {"shape": 0.0, "scale": 3.0}}"#).contains("positive shape and scale"));
+        assert!(error_of(r#"{"Uniform": {"min": 72.0, "max": 24.0}}"#).contains("needs min <= max"));
+        assert!(error_of(r#"{"LogNormal": {"mu": 3.0, "sigma": -0.5}}"#).contains("sigma that is not negative"));
+        assert!(error_of("-24").contains("can not be negative"));
+        assert!(serde_json::from_str::<Duration>(r#"{"Uniform": {"min": 24.0, "max": 24.0}}"#).is_ok());
+        assert!(serde_json::from_str::<Duration>(r#"{"Empirical": {"values": [1, 2], "weights": [0.0, 1.0]}}"#).is_ok());
+    }
+
+    #[test]
+    fn should_sample_around_the_mean_of_each_distribution() {
+        assert_eq!(mean_of(&Duration::Fixed(5)), 5.0);
+        let uniform = Duration::Distribution(Distribution::Uniform { min: 4.0, max: 8.0 });
+        assert!((mean_of(&uniform) - 6.0).abs() < 0.1);
+        let gamma = Duration::Distribution(Distribution::Gamma { shape: 4.0, scale: 12.0 });
+        assert!((mean_of(&gamma) - 48.0).abs() < 1.0);
+        let small_shape = Duration::Distribution(Distribution::Gamma { shape: 0.5, scale: 20.0 });
+        assert!((mean_of(&small_shape) - 10.0).abs() < 0.5);
+        let log_normal = Duration::Distribution(Distribution::LogNormal { mu: 2.0, sigma: 0.5 });
+        assert!((mean_of(&log_normal) - (2.0_f64 + 0.125).exp()).abs() < 0.2);
+    }
+
+    #[test]
+    fn should_only_sample_values_of_empirical_distribution_with_weight() {
+        let empirical = Duration::Distribution(Distribution::Empirical { values: vec![3, 5, 9], weights: vec![1.0, 0.0, 3.0] });
+        let mut rng = Pcg64::seed_from_u64(7);
+        let samples: Vec<i32> = (0..1000).map(|_| empirical.sample(&mut rng)).collect();
+
+        assert!(samples.iter().all(|sample| *sample == 3 || *sample == 9));
+        let nines = samples.iter().filter(|sample| **sample == 9).count();
+        assert!(nines > 700 && nines < 800);
+    }
+}
@@ -19,25 +19,39 @@
 
 use core::borrow::Borrow;
 use core::borrow::BorrowMut;
+use std::path::Path;
 use std::time::{Duration, Instant, SystemTime};
 
 use chrono::{DateTime, Local};
 use futures::StreamExt;
-use rand::{Rng, thread_rng};
-
-use crate::{allocation_map, RunMode, ticks_consumer, travellers_consumer};
+use rand::{Rng, SeedableRng};
+use rand::seq::index;
+use fnv::{FnvHashMap, FnvHashSet};
+use rand_pcg::Pcg64;
+use rayon::prelude::*;
+use uuid::Uuid;
+
+use crate::{allocation_map, environment, RunMode, ticks_consumer, travellers_consumer};
 use crate::allocation_map::AgentLocationMap;
+use crate::checkpoint;
+use crate::checkpoint::{Checkpoint, CheckpointRef};
+use crate::checkpoint::ordered_map;
 use crate::config::{Config, Population, StartingInfections};
-use crate::disease::Disease;
+use crate::disease::DiseaseProfiles;
 use crate::geography;
-use crate::geography::{Grid, Point};
+use crate::geography::{Area, Grid, Point};
 use crate::interventions::hospital::BuildNewHospital;
-use crate::interventions::lockdown::LockdownIntervention;
+use crate::interventions::lockdown::{LockdownIntervention, LockdownTier};
+use crate::interventions::school_closure::CloseSchoolsIntervention;
 use crate::interventions::vaccination::VaccinateIntervention;
+use crate::interventions::test_trace_isolate::{TestTraceIsolate, TracingLog};
+use crate::interventions::vaccination_campaign::VaccinationCampaign;
 use crate::kafka_producer::{KafkaProducer, TickAck};
+use crate::listeners::contact_log::ContactLogWriter;
 use crate::listeners::csv_service::CsvListener;
 use crate::listeners::disease_tracker::Hotspot;
 use crate::listeners::events::counts::Counts;
+use crate::listeners::events::exposure::Exposure;
 use crate::listeners::events_kafka_producer::EventsKafkaProducer;
 use crate::listeners::listener::{Listeners, Listener};
 
@@ -45,19 +59,29 @@ use rdkafka::consumer::{MessageStream, DefaultConsumerContext};
 use crate::ticks_consumer::Tick;
 use crate::travel_plan::{EngineTravelPlan, TravellersByRegion, Traveller};
 use futures::join;
+use crate::listeners::reproduction_number::ReproductionNumber;
+use crate::listeners::transmission_tree::TransmissionTree;
 use crate::listeners::travel_counter::TravelCounter;
 use crate::listeners::intervention_reporter::InterventionReporter;
 use crate::interventions::Interventions;
-use crate::constants::HOSPITAL_STAFF_PERCENTAGE;
-use crate::agent::Citizen;
+use crate::constants::{self, HOSPITAL_STAFF_PERCENTAGE, TILE_SIZE};
+use crate::agent::{Citizen, Contacts, LockdownRestriction, WorkStatus};
+use crate::contact_recorder::ContactRecorder;
+use crate::hospital_beds::{Bed, BedOccupancy, HospitalBeds};
 use crate::disease_state_machine::State;
+use crate::tiles::Tiles;
+use crate::schedule::Schedule;
+use crate::vaccine::Vaccine;
 
 pub struct Epidemiology {
     pub agent_location_map: allocation_map::AgentLocationMap,
     pub write_agent_location_map: allocation_map::AgentLocationMap,
     pub grid: Grid,
-    pub disease: Disease,
+    pub disease_profiles: DiseaseProfiles,
+    pub schedule: Schedule,
     pub sim_id: String,
+    rng: Pcg64,
+    contact_recorder: ContactRecorder,
 }
 
 impl Epidemiology {
@@ -66,33 +90,99 @@ impl Epidemiology {
     ///     Each agent, is given a random home and work place that they use throughout the pandemic
     pub fn new(config: &Config, sim_id: String) -> Epidemiology {
         let start = Instant::now();
-        let disease = config.get_disease();
+        let disease_profiles = DiseaseProfiles::new(config.get_disease(), config.get_disease_overrides())
+            .with_model(config.get_compartment_model())
+            .with_strains(config.get_strains(), &config.get_cross_immunity());
         let start_infections = config.get_starting_infections();
-        let mut grid = geography::define_geography(config.get_grid_size());
-        let mut rng = thread_rng();
-        let (start_locations, agent_list) = match config.get_population() {
-            Population::Csv(csv_pop) => grid.read_population(&csv_pop, &start_infections, &mut rng),
-            Population::Auto(auto_pop) => grid.generate_population(&auto_pop, &start_infections, &mut rng),
+        let mut rng = Epidemiology::create_rng(config.get_seed());
+        let (mut grid, (start_locations, mut agent_list)) = match config.get_population() {
+            Population::Csv(csv_pop) => {
+                let records = csv_pop.read_records();
+                let mut grid = if records.iter().any(|record| record.is_student()) {
+                    geography::define_geography_with_school(config.get_grid_size())
+                } else {
+                    geography::define_geography(config.get_grid_size())
+                };
+                let population = grid.read_population(records, &start_infections, &disease_profiles, &mut rng);
+                (grid, population)
+            }
+            Population::Auto(auto_pop) => {
+                let mut grid = geography::define_geography(config.get_grid_size());
+                let population = grid.generate_population(&auto_pop, &start_infections, &mut rng);
+                (grid, population)
+            }
         };
-        grid.resize_hospital(agent_list.len(), HOSPITAL_STAFF_PERCENTAGE, config.get_geography_parameters().hospital_beds_percentage);
+        if let Some(model) = disease_profiles.get_model() {
+            for agent in agent_list.iter_mut() {
+                agent.state_machine.start_in(model, &mut rng);
+            }
+        }
+        let hospital_beds = config.get_geography_parameters().get_hospital_beds(agent_list.len());
+        grid.resize_hospital(agent_list.len(), HOSPITAL_STAFF_PERCENTAGE, hospital_beds);
 
-        let agent_location_map = allocation_map::AgentLocationMap::new(config.get_grid_size(), &agent_list, &start_locations);
+        let agent_location_map = allocation_map::AgentLocationMap::new(config.get_grid_size(), &agent_list, &start_locations,
+                                                                       config.get_location_store());
         let write_agent_location_map = agent_location_map.clone();
 
         info!("Initialization completed in {} seconds", start.elapsed().as_secs_f32());
-        Epidemiology { agent_location_map, write_agent_location_map, grid, disease, sim_id }
+        let schedule = config.get_schedule();
+        let contact_recorder = ContactRecorder::new(config.enable_contact_log());
+        Epidemiology {
+            agent_location_map,
+            write_agent_location_map,
+            grid,
+            disease_profiles,
+            schedule,
+            sim_id,
+            rng,
+            contact_recorder,
+        }
+    }
+
+    /// Restores a simulation from a checkpoint taken with the same config
+    pub fn from_checkpoint(config: &Config, sim_id: String, checkpoint: Checkpoint) -> (Epidemiology, Counts, Interventions) {
+        let disease_profiles = DiseaseProfiles::new(config.get_disease(), config.get_disease_overrides())
+            .with_model(config.get_compartment_model())
+            .with_strains(config.get_strains(), &config.get_cross_immunity());
+        info!("Resuming from hour {}", checkpoint.counts.get_hour());
+        let epidemiology = Epidemiology {
+            agent_location_map: checkpoint.agent_location_map,
+            write_agent_location_map: checkpoint.write_agent_location_map,
+            grid: checkpoint.grid,
+            disease_profiles,
+            schedule: config.get_schedule(),
+            sim_id,
+            rng: checkpoint.rng,
+            contact_recorder: checkpoint.contact_recorder,
+        };
+        (epidemiology, checkpoint.counts, checkpoint.interventions)
+    }
+
+    /// All the randomness in a simulation is drawn from this single rng, so that runs with the same
+    /// config and seed are reproducible
+    fn create_rng(seed: Option<u64>) -> Pcg64 {
+        match seed {
+            Some(s) => {
+                info!("Using seed {}", s);
+                Pcg64::seed_from_u64(s)
+            }
+            None => Pcg64::from_entropy()
+        }
     }
 
-    fn stop_simulation(lock_down_details: &mut LockdownIntervention, run_mode: &RunMode, row: Counts) -> bool {
+    /// Lockdowns in force note when active cases run out, in every run mode. A run that isn't one of several engines
+    /// then stops, unless strains are still to be introduced, including at this hour as its counts do not have them yet
+    fn stop_simulation(lockdowns: &mut [LockdownIntervention], run_mode: &RunMode, row: &Counts,
+                       disease_profiles: &DiseaseProfiles) -> bool {
         let zero_active_cases = row.get_exposed() == 0 && row.get_infected() == 0 && row.get_hospitalized() == 0;
-        match run_mode {
-            RunMode::MultiEngine { .. } => {
-                if lock_down_details.is_locked_down() && zero_active_cases {
-                    lock_down_details.set_zero_infection_hour(row.get_hour());
-                }
-                false
+        for lockdown in lockdowns.iter_mut() {
+            if lockdown.is_locked_down() && zero_active_cases {
+                lockdown.set_zero_infection_hour(row.get_hour());
             }
-            _ => zero_active_cases
+        }
+        match run_mode {
+            RunMode::MultiEngine { .. } => false,
+            _ => zero_active_cases && !disease_profiles.introduces_strains_from(row.get_hour())
         }
     }
 
@@ -105,33 +195,57 @@ impl Epidemiology {
         format!("{}_{}", output_file_prefix, now.format("%Y-%m-%dT%H:%M:%S"))
     }
 
-    fn create_listeners(&self, config: &Config, run_mode: &RunMode) -> Listeners {
-        let output_file_format = Epidemiology::output_file_format(config, run_mode);
+    fn create_listeners(&self, config: &Config, run_mode: &RunMode, output_file_format: &str) -> Listeners {
         let counts_file_name = format!("{}.csv", output_file_format);
 
-        let csv_listener = CsvListener::new(counts_file_name);
+        let mut csv_listener = CsvListener::new(counts_file_name, config.get_calendar());
+        if let Some(model) = self.disease_profiles.get_model() {
+            csv_listener = csv_listener.with_compartments(model.names());
+        }
+        if self.disease_profiles.has_variants() {
+            csv_listener = csv_listener.with_strains(format!("{}_strains.csv", output_file_format),
+                                                     self.disease_profiles.strain_names());
+        }
         let population = self.agent_location_map.current_population();
+        let compartment_names = self.disease_profiles.get_model().map_or(vec![], |model| model.names());
 
-        let hotspot_tracker = Hotspot::new();
+        let hotspot_tracker = Hotspot::new(output_file_format.to_string(),
+                                           config.get_hotspot_window_days().map(|days| days * constants::HOURS_IN_A_DAY));
         let intervention_reporter = InterventionReporter::new(format!("{}_interventions.json", output_file_format));
+        let transmission_tree = TransmissionTree::new(format!("{}_transmission_tree.csv", output_file_format));
+        let rt_file_name = format!("{}_rt.csv", output_file_format);
         let mut listeners_vec: Vec<Box<dyn Listener>> = vec![Box::new(csv_listener),
                                                              Box::new(hotspot_tracker),
-                                                             Box::new(intervention_reporter)];
+                                                             Box::new(intervention_reporter),
+                                                             Box::new(transmission_tree)];
+        if config.enable_contact_log() {
+            let contact_log = ContactLogWriter::new(format!("{}_contacts.csv.gz", output_file_format));
+            listeners_vec.push(Box::new(contact_log));
+        }
 
         match run_mode {
-            RunMode::Standalone => {}
+            RunMode::Standalone => {
+                listeners_vec.push(Box::new(ReproductionNumber::new(rt_file_name)));
+            }
             RunMode::SingleDaemon => {
+                listeners_vec.push(Box::new(ReproductionNumber::publishing_to_kafka(rt_file_name, self.sim_id.clone())));
+
                 let kafka_listener = EventsKafkaProducer::new(self.sim_id.clone(), population as usize,
-                                                              config.enable_citizen_state_messages());
+                                                              config.enable_citizen_state_messages(),
+                                                              compartment_names.clone(),
+                                                              self.disease_profiles.strain_names());
                 listeners_vec.push(Box::new(kafka_listener));
             }
             RunMode::MultiEngine { .. } => {
                 let travels_file_name = format!("{}_outgoing_travels.csv", output_file_format);
                 let travel_counter = TravelCounter::new(travels_file_name);
                 listeners_vec.push(Box::new(travel_counter));
+                listeners_vec.push(Box::new(ReproductionNumber::publishing_to_kafka(rt_file_name, self.sim_id.clone())));
 
                 let kafka_listener = EventsKafkaProducer::new(self.sim_id.clone(), population as usize,
-                                                              config.enable_citizen_state_messages());
+                                                              config.enable_citizen_state_messages(),
+                                                              compartment_names.clone(),
+                                                              self.disease_profiles.strain_names());
                 listeners_vec.push(Box::new(kafka_listener));
             }
         }
@@ -147,23 +261,29 @@ impl Epidemiology {
         Counts::new(s, e, i)
     }
 
-    fn init_interventions(&mut self, config: &Config, rng: &mut impl rand::RngCore) -> Interventions {
+    fn init_interventions(&mut self, config: &Config) -> Interventions {
         let vaccinations = VaccinateIntervention::init(config);
-        let lock_down_details = LockdownIntervention::init(config);
+        let lockdowns = LockdownIntervention::init(config);
         let hospital_intervention = BuildNewHospital::init(config);
-        let essential_workers_population = lock_down_details.get_essential_workers_percentage();
+        let close_schools = CloseSchoolsIntervention::init(config);
+        let vaccination_campaign = VaccinationCampaign::init(config);
+        let test_trace_isolate = TestTraceIsolate::init(config);
+        let essential_workers_population = LockdownIntervention::essential_workers_population(&lockdowns);
 
         for (_, agent) in self.agent_location_map.iter_mut() {
-            agent.assign_essential_worker(essential_workers_population, rng);
+            agent.assign_essential_worker(essential_workers_population, &mut self.rng);
         }
         Interventions {
             vaccinate: vaccinations,
-            lockdown: lock_down_details,
+            lockdowns,
             build_new_hospital: hospital_intervention,
+            close_schools,
+            vaccination_campaign,
+            test_trace_isolate,
         }
     }
 
-    fn process_interventions(interventions: &mut Interventions, counts_at_hr: &Counts,
+    fn process_interventions(interventions: &mut Interventions, counts_at_hr: &mut Counts,
                              listeners: &mut Listeners, rng: &mut impl rand::RngCore, write_buffer: &mut AgentLocationMap,
                              config: &Config, grid: &mut Grid) {
         Epidemiology::apply_vaccination_intervention(
@@ -174,15 +294,24 @@ impl Epidemiology {
             listeners,
         );
 
-        if interventions.lockdown.should_apply(&counts_at_hr) {
-            interventions.lockdown.apply();
-            Epidemiology::lock_city(counts_at_hr.get_hour(), write_buffer);
-            listeners.intervention_applied(counts_at_hr.get_hour(), &interventions.lockdown)
+        Epidemiology::run_vaccination_campaign(&mut interventions.vaccination_campaign, counts_at_hr, write_buffer,
+                                               rng, listeners);
+
+        Epidemiology::apply_lockdown_interventions(&mut interventions.lockdowns, counts_at_hr, write_buffer, rng,
+                                                   listeners);
+
+        Epidemiology::test_trace_isolate(&mut interventions.test_trace_isolate, counts_at_hr, write_buffer, rng,
+                                         listeners);
+
+        if interventions.close_schools.should_apply(&counts_at_hr) {
+            interventions.close_schools.apply(counts_at_hr.get_hour());
+            Epidemiology::close_schools(counts_at_hr.get_hour(), write_buffer);
+            listeners.intervention_applied(counts_at_hr.get_hour(), &interventions.close_schools)
         }
-        if interventions.lockdown.should_unlock(&counts_at_hr) {
-            Epidemiology::unlock_city(counts_at_hr.get_hour(), write_buffer);
-            interventions.lockdown.unapply();
-            listeners.intervention_applied(counts_at_hr.get_hour(), &interventions.lockdown)
+        if interventions.close_schools.should_reopen(&counts_at_hr) {
+            Epidemiology::reopen_schools(counts_at_hr.get_hour(), write_buffer);
+            interventions.close_schools.unapply();
+            listeners.intervention_applied(counts_at_hr.get_hour(), &interventions.close_schools)
         }
 
         interventions.build_new_hospital.counts_updated(&counts_at_hr);
@@ -197,36 +326,44 @@ impl Epidemiology {
     }
 
     pub async fn run(&mut self, config: &Config, run_mode: &RunMode) {
-        let mut listeners = self.create_listeners(config, run_mode);
         let population = self.agent_location_map.current_population();
-        let mut counts_at_hr = Epidemiology::counts_at_start(population, &config.get_starting_infections());
-        let mut rng = thread_rng();
+        let counts_at_hr = Epidemiology::counts_at_start(population, &config.get_starting_infections());
 
         self.write_agent_location_map.init_with_capacity(population as usize);
 
-        let mut interventions = self.init_interventions(config, &mut rng);
+        let interventions = self.init_interventions(config);
+        self.resume(config, run_mode, counts_at_hr, interventions).await
+    }
+
+    /// Runs the simulation from the hour after `counts_at_hr`
+    pub async fn resume(&mut self, config: &Config, run_mode: &RunMode, mut counts_at_hr: Counts,
+                        mut interventions: Interventions) {
+        let output_file_format = Epidemiology::output_file_format(config, run_mode);
+        let mut listeners = self.create_listeners(config, run_mode, &output_file_format);
 
         listeners.grid_updated(&self.grid);
         match run_mode {
             RunMode::MultiEngine { .. } => {
                 self.run_multi_engine(config, run_mode, &mut listeners, &mut counts_at_hr,
-                                      &mut interventions, &mut rng).await
+                                      &mut interventions).await
             }
             _ => {
+                let mut checkpoint_file = environment::output_dir();
+                checkpoint_file.push(format!("{}_checkpoint.bin", output_file_format));
                 self.run_single_engine(config, run_mode, &mut listeners, &mut counts_at_hr,
-                                       &mut interventions, &mut rng).await
+                                       &mut interventions, &checkpoint_file).await
             }
         }
     }
 
     pub async fn run_single_engine(&mut self, config: &Config, run_mode: &RunMode, listeners: &mut Listeners,
-                                   counts_at_hr: &mut Counts, interventions: &mut Interventions, rng: &mut impl rand::RngCore) {
+                                   counts_at_hr: &mut Counts, interventions: &mut Interventions, checkpoint_file: &Path) {
         let start_time = Instant::now();
         let mut outgoing = Vec::new();
         let percent_outgoing = 0.0;
 
         counts_at_hr.log();
-        for simulation_hour in 1..config.get_hours() {
+        for simulation_hour in (counts_at_hr.get_hour() + 1)..config.get_hours() {
             debug!("Hour: {}, Total Agents: {}, Counts {:?}",simulation_hour, self.agent_location_map.current_population(),counts_at_hr);
             counts_at_hr.increment_hour();
 
@@ -245,18 +382,28 @@ impl Epidemiology {
             }
 
             Epidemiology::simulate(counts_at_hr, simulation_hour, read_buffer_reference, write_buffer_reference,
-                                   &self.grid, listeners, rng, &self.disease, percent_outgoing,
-                                   &mut outgoing, config.enable_citizen_state_messages());
-
-            listeners.counts_updated(*counts_at_hr);
-            Epidemiology::process_interventions(interventions, &counts_at_hr, listeners,
-                                                rng, write_buffer_reference, config, &mut self.grid);
-
-            if Epidemiology::stop_simulation(&mut interventions.lockdown, &run_mode, *counts_at_hr) {
+                                   &self.grid, listeners, &mut self.rng, &self.disease_profiles, &self.schedule,
+                                   percent_outgoing, &mut outgoing, config.enable_citizen_state_messages(),
+                                   interventions.test_trace_isolate.get_tracing_log_mut(), &mut self.contact_recorder);
+
+            listeners.counts_updated(counts_at_hr.clone());
+            Epidemiology::process_interventions(interventions, counts_at_hr, listeners,
+                                                &mut self.rng, write_buffer_reference, config, &mut self.grid);
+            Epidemiology::introduce_strains(simulation_hour, &self.disease_profiles, write_buffer_reference,
+                                            &mut self.rng);
+
+            if Epidemiology::stop_simulation(&mut interventions.lockdowns, &run_mode, counts_at_hr,
+                                             &self.disease_profiles) {
                 info!("Finished early, with stats: {:?}",counts_at_hr);
                 break;
             }
 
+            if let Some(interval) = config.get_checkpoint_interval() {
+                if simulation_hour % interval == 0 {
+                    self.save_checkpoint(checkpoint_file, counts_at_hr, interventions);
+                }
+            }
+
             if simulation_hour % 100 == 0 {
                 info!("Throughput: {} iterations/sec; simulation hour {} of {}",
                       simulation_hour as f32 / start_time.elapsed().as_secs_f32(),
@@ -267,12 +414,14 @@ impl Epidemiology {
         let elapsed_time = start_time.elapsed().as_secs_f32();
         info!("Number of iterations: {}, Total Time taken {} seconds", counts_at_hr.get_hour(), elapsed_time);
         info!("Iterations/sec: {}", counts_at_hr.get_hour() as f32 / elapsed_time);
+        self.contact_recorder.flush(listeners);
         listeners.simulation_ended();
     }
 
     pub async fn run_multi_engine(&mut self, config: &Config, run_mode: &RunMode, listeners: &mut Listeners,
-                                  counts_at_hr: &mut Counts, interventions: &mut Interventions, rng: &mut impl rand::RngCore) {
+                                  counts_at_hr: &mut Counts, interventions: &mut Interventions) {
         let start_time = Instant::now();
+        let rng = &mut self.rng;
         let mut producer = KafkaProducer::new();
 
         //todo stream should be started only in case of multi-sim mode
@@ -323,14 +472,18 @@ impl Epidemiology {
             engine_travel_plan.set_current_population(read_buffer_reference.current_population());
 
             let grid = &self.grid;
-            let disease = &self.disease;
+            let disease_profiles = &self.disease_profiles;
+            let schedule = &self.schedule;
 
             let percent_outgoing = engine_travel_plan.percent_outgoing();
+            let tracing_log = interventions.test_trace_isolate.get_tracing_log_mut();
+            let contact_recorder = &mut self.contact_recorder;
             let recv_travellers = Epidemiology::receive_travellers(tick.clone(), &mut travel_stream, &engine_travel_plan);
             let sim = async {
                 Epidemiology::simulate(counts_at_hr, simulation_hour, read_buffer_reference, write_buffer_reference,
-                                       grid, listeners, rng, disease, percent_outgoing,
-                                       &mut outgoing, config.enable_citizen_state_messages());
+                                       grid, listeners, rng, disease_profiles, schedule, percent_outgoing,
+                                       &mut outgoing, config.enable_citizen_state_messages(), tracing_log,
+                                       contact_recorder);
                 let outgoing_travellers_by_region = engine_travel_plan.alloc_outgoing_to_regions(&outgoing);
                 if simulation_hour % 24 == 0 {
                     listeners.outgoing_travellers_added(simulation_hour, &outgoing_travellers_by_region);
@@ -343,15 +496,17 @@ impl Epidemiology {
             write_buffer_reference.remove_citizens(&outgoing, counts_at_hr, &mut self.grid);
             write_buffer_reference.assimilate_citizens(&mut incoming, &mut self.grid, counts_at_hr, rng);
 
-            listeners.counts_updated(*counts_at_hr);
-            Epidemiology::process_interventions(interventions, &counts_at_hr, listeners,
+            listeners.counts_updated(counts_at_hr.clone());
+            Epidemiology::process_interventions(interventions, counts_at_hr, listeners,
                                                 rng, write_buffer_reference, config, &mut self.grid);
+            Epidemiology::introduce_strains(simulation_hour, &self.disease_profiles, write_buffer_reference, rng);
 
-            if Epidemiology::stop_simulation(&mut interventions.lockdown, &run_mode, *counts_at_hr) {
+            if Epidemiology::stop_simulation(&mut interventions.lockdowns, &run_mode, counts_at_hr,
+                                             &self.disease_profiles) {
                 break;
             }
 
-            Epidemiology::send_ack(run_mode, &mut producer, *counts_at_hr, simulation_hour, &interventions.lockdown).await;
+            Epidemiology::send_ack(run_mode, &mut producer, counts_at_hr.clone(), simulation_hour, &interventions.lockdowns).await;
 
             if simulation_hour % 100 == 0 {
                 info!("Throughput: {} iterations/sec; simulation hour {} of {}",
@@ -367,9 +522,42 @@ impl Epidemiology {
         let elapsed_time = start_time.elapsed().as_secs_f32();
         info!("Number of iterations: {}, Total Time taken {} seconds", counts_at_hr.get_hour(), elapsed_time);
         info!("Iterations/sec: {}", counts_at_hr.get_hour() as f32 / elapsed_time);
+        self.contact_recorder.flush(listeners);
         listeners.simulation_ended();
     }
 
+    /// Saves the state at the end of the hour in `counts`. The maps are rebuilt afterwards, so that this run
+    /// continues exactly like one resumed from the checkpoint.
+    fn save_checkpoint(&mut self, checkpoint_file: &Path, counts: &Counts, interventions: &Interventions) {
+        let stale_buffer = if counts.get_hour() % 2 == 0 {
+            &mut self.write_agent_location_map
+        } else {
+            &mut self.agent_location_map
+        };
+        stale_buffer.clear();
+
+        let checkpoint = CheckpointRef {
+            agent_location_map: &self.agent_location_map,
+            write_agent_location_map: &self.write_agent_location_map,
+            grid: &self.grid,
+            houses_occupancy: ordered_map::Ref(&self.grid.houses_occupancy),
+            offices_occupancy: ordered_map::Ref(&self.grid.offices_occupancy),
+            counts,
+            interventions,
+            rng: &self.rng,
+            contact_recorder: &self.contact_recorder,
+        };
+        match checkpoint::write(checkpoint_file, &checkpoint) {
+            Ok(_) => info!("Saved checkpoint for hour {} to {}", counts.get_hour(), checkpoint_file.display()),
+            Err(e) => error!("Failed to save checkpoint for hour {}: {}", counts.get_hour(), e)
+        }
+
+        self.agent_location_map.rebuild();
+        self.write_agent_location_map.rebuild();
+        self.grid.houses_occupancy = ordered_map::rebuild(&self.grid.houses_occupancy);
+        self.grid.offices_occupancy = ordered_map::rebuild(&self.grid.offices_occupancy);
+    }
+
     async fn receive_tick(run_mode: &RunMode, message_stream: &mut MessageStream<'_, DefaultConsumerContext>,
                           simulation_hour: i32) -> Option<Tick> {
         if simulation_hour > 1 && simulation_hour % 24 != 0 {
@@ -394,7 +582,7 @@ impl Epidemiology {
     }
 
     async fn send_ack(run_mode: &RunMode, producer: &mut KafkaProducer, counts: Counts, simulation_hour: i32,
-                      lockdown: &LockdownIntervention) {
+                      lockdowns: &[LockdownIntervention]) {
         if simulation_hour > 1 && simulation_hour % 24 != 0 {
             return;
         }
@@ -403,7 +591,7 @@ impl Epidemiology {
                 engine_id: engine_id.to_string(),
                 hour: simulation_hour,
                 counts,
-                locked_down: lockdown.is_locked_down(),
+                locked_down: lockdowns.iter().any(|lockdown| lockdown.is_locked_down()),
             };
             match producer.send_ack(&ack).await.unwrap() {
                 Ok(_) => {}
@@ -455,57 +643,294 @@ impl Epidemiology {
         match vaccinations.get_vaccination_percentage(counts) {
             Some(vac_percent) => {
                 info!("Vaccination");
-                Epidemiology::vaccinate(*vac_percent, write_buffer_reference, rng);
+                Epidemiology::vaccinate(counts.get_hour(), *vac_percent, write_buffer_reference, rng);
                 listeners.intervention_applied(counts.get_hour(), vaccinations)
             }
             _ => {}
         };
     }
 
-    fn vaccinate(vaccination_percentage: f64, write_buffer_reference: &mut AgentLocationMap, rng: &mut impl rand::RngCore) {
+    /// Picks the day's doses at random among the citizens of each priority in turn
+    fn run_vaccination_campaign(campaign: &mut VaccinationCampaign, counts: &Counts,
+                                write_buffer_reference: &mut AgentLocationMap, rng: &mut impl rand::RngCore,
+                                listeners: &mut Listeners) {
+        if !campaign.should_vaccinate(counts) {
+            return;
+        }
+        let hour = counts.get_hour();
+        let mut waiting = vec![0; campaign.number_of_priorities()];
+        for (_, agent) in write_buffer_reference.iter() {
+            if let Some(priority) = campaign.priority_of(agent, hour) {
+                waiting[priority] += 1;
+            }
+        }
+        let doses = campaign.allocate_doses(&waiting);
+        let mut chosen: Vec<Vec<bool>> = waiting.iter().map(|n| vec![false; *n]).collect();
+        for (priority, chosen) in chosen.iter_mut().enumerate() {
+            for i in index::sample(rng, waiting[priority], doses[priority]).iter() {
+                chosen[i] = true;
+            }
+        }
+
+        let vaccine = campaign.get_vaccine();
+        let mut seen = vec![0; waiting.len()];
+        for (_, agent) in write_buffer_reference.iter_mut() {
+            if let Some(priority) = campaign.priority_of(agent, hour) {
+                if chosen[priority][seen[priority]] {
+                    agent.vaccinate(vaccine, hour);
+                }
+                seen[priority] += 1;
+            }
+        }
+        campaign.doses_given(doses.iter().sum());
+        listeners.intervention_applied(counts.get_hour(), campaign);
+    }
+
+    /// Exposes citizens picked at random among the susceptible ones to each strain introduced at this hour
+    fn introduce_strains(hour: i32, disease_profiles: &DiseaseProfiles, write_buffer_reference: &mut AgentLocationMap,
+                         rng: &mut impl rand::RngCore) {
+        for (position, strain) in disease_profiles.get_strains().iter().enumerate() {
+            if strain.introduced_at != hour {
+                continue;
+            }
+            let susceptible = write_buffer_reference.iter()
+                .filter(|(_, agent)| agent.state_machine.is_susceptible())
+                .count();
+            let introductions = (strain.introductions.max(0) as usize).min(susceptible);
+            let mut chosen = vec![false; susceptible];
+            for i in index::sample(rng, susceptible, introductions).iter() {
+                chosen[i] = true;
+            }
+
+            let mut seen = 0;
+            for (_, agent) in write_buffer_reference.iter_mut() {
+                if agent.state_machine.is_susceptible() {
+                    if chosen[seen] {
+                        agent.state_machine.expose_to(hour, position + 1);
+                    }
+                    seen += 1;
+                }
+            }
+            info!("Introduced strain {} in {} citizens", strain.name, introductions);
+        }
+    }
+
+    /// Tests citizens picked at random among those who can be tested, then quarantines the positives and the contacts
+    /// they recall
+    fn test_trace_isolate(tti: &mut TestTraceIsolate, counts: &mut Counts, write_buffer_reference: &mut AgentLocationMap,
+                          rng: &mut impl rand::RngCore, listeners: &mut Listeners) {
+        if !tti.should_test(counts) {
+            return;
+        }
+        let hour = counts.get_hour();
+        let can_be_tested = write_buffer_reference.iter().filter(|(_, agent)| TestTraceIsolate::can_be_tested(agent)).count();
+        let tests = tti.tests_per_day().min(can_be_tested);
+        let mut chosen = vec![false; can_be_tested];
+        for i in index::sample(rng, can_be_tested, tests).iter() {
+            chosen[i] = true;
+        }
+
+        let mut positives = FnvHashSet::default();
+        let mut seen = 0;
+        for (_, agent) in write_buffer_reference.iter_mut() {
+            if TestTraceIsolate::can_be_tested(agent) {
+                if chosen[seen] && tti.test(rng) {
+                    agent.quarantine(TestTraceIsolate::quarantine_ends_at(hour));
+                    positives.insert(agent.id);
+                }
+                seen += 1;
+            }
+        }
+
+        let traced: FnvHashSet<Uuid> = tti.get_tracing_log().contacts_of(&positives)
+            .filter(|_| tti.recalls_contact(rng))
+            .collect();
+        for (_, agent) in write_buffer_reference.iter_mut() {
+            if traced.contains(&agent.id) && !agent.state_machine.is_deceased() {
+                agent.quarantine(TestTraceIsolate::quarantine_ends_at(hour));
+            }
+        }
+
+        counts.update_tested(tests as i32, positives.len() as i32);
+        tti.tested(tests, positives.len(), traced.len());
+        listeners.intervention_applied(hour, tti);
+    }
+
+    /// The city is locked down at the tier of the strictest lockdown in force, and only the lockdown that changes
+    /// the tier is reported
+    fn apply_lockdown_interventions(lockdowns: &mut [LockdownIntervention], counts: &Counts,
+                                    write_buffer_reference: &mut AgentLocationMap, rng: &mut impl rand::RngCore,
+                                    listeners: &mut Listeners) {
+        let tier_before = LockdownIntervention::active_tier(lockdowns);
+        let mut changed = None;
+        for (i, lockdown) in lockdowns.iter_mut().enumerate() {
+            lockdown.update(counts);
+            if lockdown.should_apply(counts) {
+                lockdown.apply(counts.get_hour());
+                changed = Some(i);
+            } else if lockdown.should_unlock(counts) {
+                lockdown.unapply();
+                changed = Some(i);
+            }
+        }
+        let tier = LockdownIntervention::active_tier(lockdowns);
+        if tier == tier_before {
+            return;
+        }
+        let strictest = tier.and_then(|tier| {
+            lockdowns.iter().position(|lockdown| lockdown.is_locked_down() && lockdown.get_tier() == Some(tier))
+        });
+        match (tier, strictest) {
+            (Some(tier), Some(i)) => {
+                let exempt = lockdowns[i].essential_workers_exempt(lockdowns);
+                Epidemiology::lock_city(counts.get_hour(), tier, exempt, write_buffer_reference, rng)
+            }
+            _ => Epidemiology::unlock_city(counts.get_hour(), write_buffer_reference)
+        }
+        let reported = strictest.or(changed);
+        if let Some(i) = reported {
+            listeners.intervention_applied(counts.get_hour(), &lockdowns[i]);
+        }
+    }
+
+    fn vaccinate(hour: i32, vaccination_percentage: f64, write_buffer_reference: &mut AgentLocationMap, rng: &mut impl rand::RngCore) {
         for (_v, agent) in write_buffer_reference.iter_mut() {
             if agent.state_machine.is_susceptible() && rng.gen_bool(vaccination_percentage) {
-                agent.set_vaccination(true);
+                agent.vaccinate(Vaccine::perfect(), hour);
             }
         }
     }
 
+    /// Tiles of the grid are simulated in parallel, each with its own rng seeded from `rng`. When two citizens
+    /// move to the same cell, the first one in tile order gets it and the other stays put, so a seeded run
+    /// gives the same result on any number of threads.
     fn simulate(csv_record: &mut Counts, simulation_hour: i32, read_buffer: &AgentLocationMap,
                 write_buffer: &mut AgentLocationMap, grid: &Grid, listeners: &mut Listeners,
-                rng: &mut impl rand::RngCore, disease: &Disease, percent_outgoing: f64,
-                outgoing: &mut Vec<(Point, Traveller)>, publish_citizen_state: bool) {
+                rng: &mut impl rand::RngCore, disease_profiles: &DiseaseProfiles, schedule: &Schedule,
+                percent_outgoing: f64, outgoing: &mut Vec<(Point, Traveller)>, publish_citizen_state: bool,
+                tracing_log: &mut TracingLog, contact_recorder: &mut ContactRecorder) {
         write_buffer.clear();
         csv_record.clear();
-        for (cell, agent) in read_buffer.iter() {
-            let mut current_agent = *agent;
-            let infection_status = current_agent.state_machine.is_infected();
-            let point = current_agent.perform_operation(*cell, simulation_hour, &grid, read_buffer, rng, disease);
-            Epidemiology::update_counts(csv_record, &current_agent);
-
-            if infection_status == false && current_agent.state_machine.is_infected() == true {
-                listeners.citizen_got_infected(&cell);
+        let tiles = Tiles::new(grid.grid_size, TILE_SIZE);
+        let citizens_by_tile = tiles.split(read_buffer);
+        let tile_seeds: Vec<u64> = citizens_by_tile.iter().map(|_| rng.gen()).collect();
+        let record_all_contacts = contact_recorder.is_recording();
+
+        let mut moves: Vec<Vec<Move>> = citizens_by_tile.par_iter().zip(tile_seeds.par_iter())
+            .map(|(citizens, seed)| {
+                let mut tile_rng = Pcg64::seed_from_u64(*seed);
+                Epidemiology::simulate_tile(citizens, &tiles, simulation_hour, read_buffer, grid, &mut tile_rng,
+                                            disease_profiles, schedule, percent_outgoing, record_all_contacts)
+            })
+            .collect();
+
+        Epidemiology::allocate_beds(&mut moves, grid.hospital_beds);
+        for m in moves.iter().flatten().filter(|m| m.settled) {
+            write_buffer.insert(m.new_location, m.citizen);
+        }
+        for m in moves.iter_mut().flatten().filter(|m| !m.settled) {
+            if !write_buffer.is_cell_vacant(&m.new_location) {
+                m.new_location = m.cell;
             }
+            write_buffer.insert(m.new_location, m.citizen);
+        }
 
-            let agent_option = write_buffer.get(&point);
-            let new_location = match agent_option {
-                Some(mut _agent) => cell, //occupied
-                _ => &point
-            };
-
-            if simulation_hour % 24 == 0 && current_agent.can_move()
-                && rng.gen_bool(percent_outgoing) {
-                let traveller = Traveller::from(&current_agent);
-                outgoing.push((*new_location, traveller));
+        for m in moves.iter().flatten() {
+            Epidemiology::update_counts(csv_record, &m.citizen);
+            for neighbour in &m.contacts.infectious {
+                tracing_log.record(simulation_hour, m.citizen.id, *neighbour);
+            }
+            if let Some(met_at) = m.contacts.met_at {
+                for neighbour in &m.contacts.neighbours {
+                    contact_recorder.record(simulation_hour, m.citizen.id, *neighbour, met_at);
+                }
+                if let Some(infector) = m.contacts.infector {
+                    listeners.citizen_got_infected(simulation_hour, &met_at);
+                    listeners.citizen_exposed(&Exposure {
+                        hour: simulation_hour,
+                        infector,
+                        infectee: m.citizen.id,
+                        location: met_at,
+                        setting: grid.setting_of(&met_at, &m.citizen.home_location),
+                    });
+                }
+            }
+            if m.infection_ended {
+                listeners.infection_ended(simulation_hour, m.citizen.id);
+            }
+            if m.travels {
+                outgoing.push((m.new_location, Traveller::from(&m.citizen)));
             }
-
-            write_buffer.insert(*new_location, current_agent);
             if publish_citizen_state {
-                listeners.citizen_state_updated(simulation_hour, &current_agent, new_location);
+                listeners.citizen_state_updated(simulation_hour, &m.citizen, &m.new_location);
             }
         }
+        contact_recorder.hour_ended(simulation_hour, listeners);
         assert_eq!(csv_record.total(), write_buffer.current_population());
     }
 
+    /// Patients asking for a bed this hour get the free ones in tile order. The ones left over keep the bed they had,
+    /// or stay where they were
+    fn allocate_beds(moves: &mut [Vec<Move>], beds: HospitalBeds) {
+        let mut occupancy = BedOccupancy::new(beds);
+        for m in moves.iter().flatten() {
+            let held = if m.requested_bed.is_some() { m.previous_bed } else { m.citizen.get_bed() };
+            if let Some(bed) = held {
+                occupancy.keep(bed);
+            }
+        }
+        for m in moves.iter_mut().flatten() {
+            if let Some(bed) = m.requested_bed {
+                if occupancy.admit(bed) {
+                    if let Some(previous_bed) = m.previous_bed {
+                        occupancy.release(previous_bed);
+                    }
+                    m.citizen.admit(bed);
+                } else {
+                    m.citizen.turn_away(m.previous_bed);
+                    if m.previous_bed.is_none() {
+                        m.new_location = m.cell;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Moves that stay inside the tile are settled here; moves into another tile are settled by `simulate`
+    fn simulate_tile(citizens: &[(&Point, &Citizen)], tiles: &Tiles, simulation_hour: i32, read_buffer: &AgentLocationMap,
+                     grid: &Grid, rng: &mut impl rand::RngCore, disease_profiles: &DiseaseProfiles,
+                     schedule: &Schedule, percent_outgoing: f64, record_all_contacts: bool) -> Vec<Move> {
+        let mut claimed_cells = FnvHashSet::default();
+        citizens.iter().map(|(cell, agent)| {
+            let mut current_agent = **agent;
+            let was_infectious = current_agent.state_machine.is_infected() || current_agent.state_machine.is_exposed();
+            let mut contacts = Contacts::new(record_all_contacts);
+            let point = current_agent.perform_operation(**cell, simulation_hour, grid, read_buffer, rng, disease_profiles,
+                                                        schedule, &mut contacts);
+
+            let settled = tiles.tile_of(&point) == tiles.tile_of(cell);
+            let new_location = if settled && !claimed_cells.insert(point) {
+                **cell //occupied
+            } else {
+                point
+            };
+            let travels = simulation_hour % 24 == 0 && current_agent.can_move() && rng.gen_bool(percent_outgoing);
+            let infectious = current_agent.state_machine.is_exposed() || current_agent.state_machine.is_infected();
+
+            Move {
+                cell: **cell,
+                new_location,
+                settled,
+                citizen: current_agent,
+                infection_ended: (was_infectious && !infectious) || (infectious && travels),
+                travels,
+                contacts,
+                requested_bed: current_agent.get_bed().filter(|bed| agent.get_bed() != Some(*bed)),
+                previous_bed: agent.get_bed(),
+            }
+        }).collect()
+    }
+
     fn update_counts(counts_at_hr: &mut Counts, citizen: &Citizen) {
         match citizen.state_machine.state {
             State::Susceptible { .. } => { counts_at_hr.update_susceptible(1) }
@@ -520,13 +945,33 @@ impl Epidemiology {
             State::Recovered { .. } => { counts_at_hr.update_recovered(1) }
             State::Deceased { .. } => { counts_at_hr.update_deceased(1) }
         }
+        if citizen.is_vaccinated() {
+            counts_at_hr.update_vaccinated(&citizen.state_machine.state, citizen.is_hospitalized(), 1);
+        }
+        counts_at_hr.update_reinfections(citizen.state_machine.get_reinfections());
+        if citizen.is_quarantined() && !citizen.state_machine.is_deceased() {
+            counts_at_hr.update_quarantined(1);
+        }
+        counts_at_hr.update_beds(citizen.get_bed(), citizen.is_turned_away());
+        if citizen.state_machine.is_critical() {
+            counts_at_hr.update_critical(1);
+        }
+        if let Some(compartment) = citizen.state_machine.get_compartment() {
+            counts_at_hr.update_compartment(compartment, 1);
+        }
+        counts_at_hr.update_strain(citizen.state_machine.get_strain(), &citizen.state_machine.state,
+                                   citizen.is_hospitalized(), 1);
     }
 
-    fn lock_city(hr: i32, write_buffer_reference: &mut AgentLocationMap) {
-        info!("Locking the city. Hour: {}", hr);
+    /// Essential workers are exempt with probability `essential_workers_exempt`
+    fn lock_city(hr: i32, tier: LockdownTier, essential_workers_exempt: f64,
+                 write_buffer_reference: &mut AgentLocationMap, rng: &mut impl rand::RngCore) {
+        info!("Locking the city at level {}. Hour: {}", tier.level, hr);
         for (_v, agent) in write_buffer_reference.iter_mut() {
-            if !agent.is_essential_worker() {
-                agent.set_isolation(true);
+            if agent.is_essential_worker() && rng.gen_bool(essential_workers_exempt) {
+                agent.set_lockdown_restriction(LockdownRestriction::None);
+            } else {
+                agent.set_lockdown_restriction(tier.restriction(rng));
             }
         }
     }
@@ -534,20 +979,76 @@ impl Epidemiology {
     fn unlock_city(hr: i32, write_buffer_reference: &mut AgentLocationMap) {
         info!("Unlocking city. Hour: {}", hr);
         for (_v, agent) in write_buffer_reference.iter_mut() {
-            if agent.is_isolated() {
-                agent.set_isolation(false);
+            agent.set_lockdown_restriction(LockdownRestriction::None);
+        }
+    }
+
+    /// Keeps students at home, along with one caregiver per household: an adult, or a citizen of unknown age, who
+    /// doesn't work if there is anyone, otherwise a worker who isn't essential
+    fn close_schools(hr: i32, write_buffer_reference: &mut AgentLocationMap) {
+        info!("Closing schools. Hour: {}", hr);
+        let mut caregivers: FnvHashMap<Area, (Option<Uuid>, Option<Uuid>)> = FnvHashMap::default();
+        for (_, agent) in write_buffer_reference.iter() {
+            if agent.is_student() {
+                caregivers.entry(agent.home_location).or_insert((None, None));
+            }
+        }
+        for (_, agent) in write_buffer_reference.iter() {
+            if let Some((not_working, worker)) = caregivers.get_mut(&agent.home_location) {
+                if agent.get_age_band_start().map_or(false, |age| age <= constants::SCHOOL_AGE_END) {
+                    continue;
+                }
+                match agent.get_work_status() {
+                    WorkStatus::NA {} if not_working.is_none() => *not_working = Some(agent.id),
+                    WorkStatus::Normal {} if worker.is_none() => *worker = Some(agent.id),
+                    _ => {}
+                }
+            }
+        }
+        for (_, agent) in write_buffer_reference.iter_mut() {
+            let is_caregiver = caregivers.get(&agent.home_location)
+                .map_or(false, |(not_working, worker)| not_working.or(*worker) == Some(agent.id));
+            if agent.is_student() || is_caregiver {
+                agent.set_kept_home(true);
             }
         }
     }
+
+    fn reopen_schools(hr: i32, write_buffer_reference: &mut AgentLocationMap) {
+        info!("Reopening schools. Hour: {}", hr);
+        for (_, agent) in write_buffer_reference.iter_mut() {
+            agent.set_kept_home(false);
+        }
+    }
+}
+
+/// What a citizen did in an hour, before it is written to the next buffer
+struct Move {
+    cell: Point,
+    new_location: Point,
+    settled: bool,
+    citizen: Citizen,
+    /// Recovered or died this hour, or left for another engine while still infectious
+    infection_ended: bool,
+    travels: bool,
+    contacts: Contacts,
+    /// Went to hospital or turned critical this hour, and needs a bed of this kind from the pools
+    requested_bed: Option<Bed>,
+    previous_bed: Option<Bed>,
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::config::{AutoPopulation, GeographyParameters};
+    use crate::agent::PopulationRecord;
+    use crate::config::{AutoPopulation, GeographyParameters, LocationStore};
+    use crate::disease::Disease;
     use crate::geography::Area;
     use crate::geography::Point;
     use crate::interventions::InterventionConfig;
+    use crate::interventions::lockdown::LockdownConfig;
+    use crate::interventions::test_trace_isolate::TestTraceIsolateConfig;
     use crate::interventions::vaccination::VaccinateConfig;
+    use crate::strain::Strain;
 
     use super::*;
 
@@ -575,9 +1076,324 @@ mod tests {
         let expected_work_area = Area::new(Point::new(50, 0), Point::new(69, 100));
         assert_eq!(epidemiology.grid.work_area, expected_work_area);
 
+        assert!(epidemiology.grid.classrooms.is_empty());
+
         let expected_hospital_area = Area::new(Point::new(70, 0), Point::new(79, 0));
         assert_eq!(epidemiology.grid.hospital_area, expected_hospital_area);
 
         assert_eq!(epidemiology.agent_location_map.current_population(), 10);
     }
+
+    #[test]
+    fn should_generate_same_population_for_same_seed() {
+        let pop = AutoPopulation {
+            number_of_agents: 100,
+            public_transport_percentage: 0.2,
+            working_percentage: 0.7,
+        };
+        let disease = Disease::new(0, 0, 0, 0, 0, 0.0, 0.0, 0.0, 0.0, 0.0, 0, 0);
+        let geography_parameters = GeographyParameters::new(100, 0.003);
+        let mut config = Config::new(Population::Auto(pop), disease, geography_parameters, vec![], 100, vec![], None);
+        config.set_seed(7);
+
+        let first = Epidemiology::new(&config, "id".to_string());
+        let second = Epidemiology::new(&config, "id".to_string());
+
+        let citizens_of = |epidemiology: &Epidemiology| {
+            let mut citizens: Vec<(Point, String)> = epidemiology.agent_location_map.iter()
+                .map(|(point, citizen)| (*point, citizen.id.to_string()))
+                .collect();
+            citizens.sort_by_key(|(point, _)| (point.x, point.y));
+            citizens
+        };
+        assert_eq!(citizens_of(&first), citizens_of(&second));
+    }
+
+    #[test]
+    fn should_restore_state_from_checkpoint() {
+        let pop = AutoPopulation {
+            number_of_agents: 100,
+            public_transport_percentage: 0.2,
+            working_percentage: 0.7,
+        };
+        let disease = Disease::new(0, 0, 0, 0, 0, 0.0, 0.0, 0.0, 0.0, 0.0, 0, 0);
+        let geography_parameters = GeographyParameters::new(100, 0.003);
+        let mut config = Config::new(Population::Auto(pop), disease, geography_parameters, vec![], 100, vec![], None);
+        config.set_seed(7);
+        let checkpoint_file = std::env::temp_dir().join("epirust_test_checkpoint.bin");
+
+        let mut epidemiology = Epidemiology::new(&config, "id".to_string());
+        let interventions = epidemiology.init_interventions(&config);
+        let counts = Counts::new_test(5, 99, 1, 0, 0, 0, 0);
+        epidemiology.save_checkpoint(&checkpoint_file, &counts, &interventions);
+
+        let checkpoint = checkpoint::read(checkpoint_file.to_str().unwrap()).unwrap();
+        let (mut restored, restored_counts, _) = Epidemiology::from_checkpoint(&config, "id".to_string(), checkpoint);
+        std::fs::remove_file(&checkpoint_file).unwrap();
+
+        let citizens_of = |epidemiology: &Epidemiology| {
+            epidemiology.write_agent_location_map.iter()
+                .map(|(point, citizen)| (*point, citizen.id))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(counts, restored_counts);
+        assert_eq!(citizens_of(&epidemiology), citizens_of(&restored));
+        assert_eq!(0, restored.agent_location_map.current_population());
+        assert_eq!(epidemiology.grid.houses_occupancy, restored.grid.houses_occupancy);
+        assert_eq!(epidemiology.rng.gen::<u64>(), restored.rng.gen::<u64>());
+    }
+
+    #[test]
+    fn should_resume_from_checkpoint_with_the_same_counts_as_an_uninterrupted_run() {
+        let pop = AutoPopulation {
+            number_of_agents: 300,
+            public_transport_percentage: 0.2,
+            working_percentage: 0.7,
+        };
+        let disease = Disease::new(1, 3, 10, 4, 6, 0.3, 0.6, 0.1, 0.3, 0.3, 12, 12);
+        let geography_parameters = GeographyParameters::new(60, 0.003);
+        let mut config = Config::new(Population::Auto(pop), disease, geography_parameters, vec![], 120, vec![], None);
+        config.set_seed(7);
+        config.set_checkpoint_interval(48);
+        config.set_starting_infections(StartingInfections::new(2, 2, 0, 5));
+        let checkpoint_file = std::env::temp_dir().join("epirust_test_resume_checkpoint.bin");
+
+        let mut epidemiology = Epidemiology::new(&config, "id".to_string());
+        let population = epidemiology.agent_location_map.current_population();
+        let mut counts = Epidemiology::counts_at_start(population, &config.get_starting_infections());
+        epidemiology.write_agent_location_map.init_with_capacity(population as usize);
+        let mut interventions = epidemiology.init_interventions(&config);
+        futures::executor::block_on(epidemiology.run_single_engine(
+            &config, &RunMode::Standalone, &mut Listeners::from(vec![]), &mut counts, &mut interventions,
+            &checkpoint_file));
+
+        let checkpoint = checkpoint::read(checkpoint_file.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&checkpoint_file).unwrap();
+        let (mut resumed, mut resumed_counts, mut resumed_interventions) =
+            Epidemiology::from_checkpoint(&config, "id".to_string(), checkpoint);
+        assert_eq!(resumed_counts.get_hour(), 96);
+        futures::executor::block_on(resumed.run_single_engine(
+            &config, &RunMode::Standalone, &mut Listeners::from(vec![]), &mut resumed_counts,
+            &mut resumed_interventions, &checkpoint_file));
+
+        assert_eq!(counts.get_hour(), 119);
+        assert!(counts.get_recovered() + counts.get_deceased() > 0);
+        assert_eq!(counts, resumed_counts);
+        assert_eq!(epidemiology.rng.gen::<u64>(), resumed.rng.gen::<u64>());
+    }
+
+    #[test]
+    fn should_write_the_same_outputs_for_the_same_seed() {
+        let config = crate::config::read(String::from("config/test/seeded_auto_pop.json")).unwrap();
+        let outputs_of_run = |run: usize| {
+            let output_path = |suffix: &str| std::env::temp_dir().join(format!("epirust_test_seeded_run_{}{}", run, suffix));
+            let mut listeners = Listeners::from(vec![
+                Box::new(CsvListener::new(output_path(".csv").to_str().unwrap().to_string(), None)),
+                Box::new(InterventionReporter::new(output_path("_interventions.json").to_str().unwrap().to_string())),
+            ]);
+            let mut epidemiology = Epidemiology::new(&config, "id".to_string());
+            let population = epidemiology.agent_location_map.current_population();
+            let mut counts = Epidemiology::counts_at_start(population, &config.get_starting_infections());
+            epidemiology.write_agent_location_map.init_with_capacity(population as usize);
+            let mut interventions = epidemiology.init_interventions(&config);
+            futures::executor::block_on(epidemiology.run_single_engine(
+                &config, &RunMode::Standalone, &mut listeners, &mut counts, &mut interventions,
+                &output_path("_checkpoint.bin")));
+
+            let outputs: Vec<Vec<u8>> = [".csv", "_interventions.json"].iter()
+                .map(|suffix| {
+                    let output = std::fs::read(output_path(suffix)).unwrap();
+                    std::fs::remove_file(output_path(suffix)).unwrap();
+                    output
+                })
+                .collect();
+            outputs
+        };
+
+        let first = outputs_of_run(0);
+        let second = outputs_of_run(1);
+
+        assert!(String::from_utf8_lossy(&first[1]).contains("lockdown"));
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn simulate_should_not_depend_on_number_of_threads() {
+        let pop = AutoPopulation {
+            number_of_agents: 1000,
+            public_transport_percentage: 0.2,
+            working_percentage: 0.7,
+        };
+        let disease = Disease::new(0, 0, 0, 0, 0, 0.0, 0.0, 0.0, 0.0, 0.0, 0, 0);
+        let geography_parameters = GeographyParameters::new(100, 0.003);
+        let mut config = Config::new(Population::Auto(pop), disease, geography_parameters, vec![], 100, vec![], None);
+        config.set_seed(7);
+
+        let simulate_hour_on = |threads: usize| {
+            let mut epidemiology = Epidemiology::new(&config, "id".to_string());
+            let mut counts = Counts::new(1000, 0, 0);
+            let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+            pool.install(|| {
+                let mut listeners = Listeners::from(vec![]);
+                Epidemiology::simulate(&mut counts, 7, &epidemiology.agent_location_map,
+                                       &mut epidemiology.write_agent_location_map, &epidemiology.grid, &mut listeners,
+                                       &mut epidemiology.rng, &epidemiology.disease_profiles, &epidemiology.schedule, 0.0,
+                                       &mut vec![], false, &mut TracingLog::default(),
+                                       &mut ContactRecorder::new(false))
+            });
+            epidemiology.write_agent_location_map.iter()
+                .map(|(point, citizen)| (*point, citizen.id))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(simulate_hour_on(1), simulate_hour_on(3));
+    }
+
+    #[test]
+    fn should_end_the_infections_of_citizens_leaving_for_another_engine() {
+        let pop = AutoPopulation {
+            number_of_agents: 100,
+            public_transport_percentage: 0.2,
+            working_percentage: 0.7,
+        };
+        let disease = Disease::new(1, 3, 10, 4, 6, 0.0, 0.0, 0.0, 0.3, 0.3, 48, 48);
+        let geography_parameters = GeographyParameters::new(50, 0.003);
+        let mut config = Config::new(Population::Auto(pop), disease, geography_parameters, vec![], 100, vec![], None);
+        config.set_seed(7);
+        config.set_starting_infections(StartingInfections::new(5, 0, 0, 5));
+        let mut epidemiology = Epidemiology::new(&config, "id".to_string());
+
+        let tiles = Tiles::new(epidemiology.grid.grid_size, TILE_SIZE);
+        let citizens: Vec<(&Point, &Citizen)> = tiles.split(&epidemiology.agent_location_map).into_iter().flatten().collect();
+        let moves = Epidemiology::simulate_tile(&citizens, &tiles, 24, &epidemiology.agent_location_map,
+                                                &epidemiology.grid, &mut epidemiology.rng,
+                                                &epidemiology.disease_profiles, &epidemiology.schedule, 1.0, false);
+
+        let infectious = |m: &&Move| m.citizen.state_machine.is_exposed() || m.citizen.state_machine.is_infected();
+        let travelling: Vec<&Move> = moves.iter().filter(|m| m.travels).filter(infectious).collect();
+        assert_eq!(travelling.len(), 10);
+        assert!(travelling.iter().all(|m| m.infection_ended));
+        assert!(moves.iter().filter(|m| !m.travels).all(|m| !m.infection_ended));
+    }
+
+    #[test]
+    fn should_keep_students_and_one_caregiver_per_household_home_while_schools_are_closed() {
+        let mut rng = rand::thread_rng();
+        let family_home = Area::new(Point::new(0, 0), Point::new(1, 1));
+        let working_home = Area::new(Point::new(2, 0), Point::new(3, 1));
+        let office = Area::new(Point::new(10, 0), Point::new(19, 9));
+        let citizen = |home: Area, working: bool, work_status: WorkStatus, rng: &mut rand::rngs::ThreadRng| {
+            Citizen::new(home, office, Point::new(0, 0), false, working, work_status, rng)
+        };
+        let citizens = vec![
+            citizen(family_home, false, WorkStatus::Student {}, &mut rng),
+            citizen(family_home, true, WorkStatus::Normal {}, &mut rng),
+            citizen(family_home, true, WorkStatus::Normal {}, &mut rng),
+            citizen(working_home, true, WorkStatus::Normal {}, &mut rng),
+        ];
+        let points = vec![Point::new(0, 0), Point::new(0, 1), Point::new(1, 0), Point::new(2, 0)];
+        let mut map = AgentLocationMap::new(20, &citizens, &points, LocationStore::HashMap);
+
+        Epidemiology::close_schools(24, &mut map);
+        let kept_home: Vec<bool> = points.iter().map(|p| map.get_agent_for(p).unwrap().is_kept_home()).collect();
+        assert!(kept_home[0]);
+        assert_eq!(kept_home[1..3].iter().filter(|kept_home| **kept_home).count(), 1);
+        assert!(!kept_home[3]);
+
+        Epidemiology::reopen_schools(48, &mut map);
+        assert!(points.iter().all(|p| !map.get_agent_for(p).unwrap().is_kept_home()));
+    }
+
+    #[test]
+    fn should_not_keep_a_toddler_home_as_the_caregiver() {
+        let mut rng = Pcg64::seed_from_u64(7);
+        let home = Area::new(Point::new(0, 0), Point::new(1, 1));
+        let school = Area::new(Point::new(10, 0), Point::new(14, 4));
+        let profiles = DiseaseProfiles::new(Disease::new(0, 0, 0, 0, 0, 0.0, 0.0, 0.0, 0.0, 0.0, 0, 0), vec![]);
+        let mut citizen = |age: &str, working: bool| {
+            let record = PopulationRecord { ind: 0, age: age.to_string(), sex: None, working, pub_transport: false };
+            Citizen::from_record(record, home, school, Point::new(0, 0), &profiles, &mut rng)
+        };
+        let citizens = vec![citizen("0-4", false), citizen("5-9", false), citizen("35-39", true)];
+        assert!(matches!(citizens[0].get_work_status(), WorkStatus::NA {}));
+        let points = vec![Point::new(0, 0), Point::new(0, 1), Point::new(1, 0)];
+        let mut map = AgentLocationMap::new(20, &citizens, &points, LocationStore::HashMap);
+
+        Epidemiology::close_schools(24, &mut map);
+        let kept_home: Vec<bool> = points.iter().map(|p| map.get_agent_for(p).unwrap().is_kept_home()).collect();
+        assert_eq!(kept_home, vec![false, true, true]);
+    }
+
+    #[test]
+    fn should_stop_infections_by_quarantining_positives_and_their_contacts() {
+        let run = |interventions: Vec<InterventionConfig>| {
+            let pop = AutoPopulation {
+                number_of_agents: 300,
+                public_transport_percentage: 0.2,
+                working_percentage: 0.7,
+            };
+            let disease = Disease::new(0, 3, 10, 6, 6, 0.4, 0.6, 0.0, 0.0, 0.0, 12, 12);
+            let geography_parameters = GeographyParameters::new(50, 0.003);
+            let mut config = Config::new(Population::Auto(pop), disease, geography_parameters, vec![], 480,
+                                         interventions, None);
+            config.set_seed(7);
+            config.set_starting_infections(StartingInfections::new(0, 5, 0, 0));
+
+            let mut epidemiology = Epidemiology::new(&config, "id".to_string());
+            let population = epidemiology.agent_location_map.current_population();
+            let mut counts = Epidemiology::counts_at_start(population, &config.get_starting_infections());
+            epidemiology.write_agent_location_map.init_with_capacity(population as usize);
+            let mut interventions = epidemiology.init_interventions(&config);
+            futures::executor::block_on(epidemiology.run_single_engine(
+                &config, &RunMode::Standalone, &mut Listeners::from(vec![]), &mut counts, &mut interventions,
+                &std::env::temp_dir().join("epirust_test_tti_checkpoint.bin")));
+            counts
+        };
+        let tti = TestTraceIsolateConfig { start_hour: 24, tests_per_day: 300, sensitivity: 1.0, contact_recall: 1.0 };
+
+        let without_tti = run(vec![]);
+        let with_tti = run(vec![InterventionConfig::TestTraceIsolate(tti)]);
+
+        assert_eq!(with_tti.get_exposed() + with_tti.get_infected() + with_tti.get_hospitalized(), 0);
+        assert!(with_tti.get_susceptible() > without_tti.get_susceptible() + 50,
+                "{} susceptible with tracing, {} without", with_tti.get_susceptible(), without_tti.get_susceptible());
+    }
+
+    #[test]
+    fn should_note_when_active_cases_run_out_during_a_lockdown_in_every_run_mode() {
+        let pop = AutoPopulation {
+            number_of_agents: 10,
+            public_transport_percentage: 1.0,
+            working_percentage: 1.0,
+        };
+        let disease = Disease::new(0, 0, 0, 0, 0, 0.0, 0.0, 0.0, 0.0, 0.0, 0, 0);
+        let profiles = DiseaseProfiles::new(disease.clone(), vec![]);
+        let lockdown: LockdownConfig = serde_json::from_str(
+            r#"{"at_number_of_infections": 100, "essential_workers_population": 0.1}"#).unwrap();
+        let config = Config::new(Population::Auto(pop), disease, GeographyParameters::new(100, 0.003), vec![], 100,
+                                 vec![InterventionConfig::Lockdown(lockdown)], None);
+        let active = Counts::new_test(30, 90, 0, 5, 0, 5, 0);
+        let no_active_cases = Counts::new_test(40, 90, 0, 0, 0, 10, 0);
+
+        for run_mode in &[RunMode::Standalone, RunMode::SingleDaemon, RunMode::MultiEngine { engine_id: "e".to_string() }] {
+            let mut lockdowns = LockdownIntervention::init(&config);
+            lockdowns[0].apply(24);
+            Epidemiology::stop_simulation(&mut lockdowns, run_mode, &active, &profiles);
+            assert_eq!(lockdowns[0].zero_infection_hour, 0);
+            Epidemiology::stop_simulation(&mut lockdowns, run_mode, &no_active_cases, &profiles);
+            assert_eq!(lockdowns[0].zero_infection_hour, 40);
+        }
+    }
+
+    #[test]
+    fn should_not_stop_before_every_strain_is_introduced() {
+        let disease = Disease::new(0, 0, 0, 0, 0, 0.0, 0.0, 0.0, 0.0, 0.0, 0, 0);
+        let strain = Strain { name: "delta".to_string(), disease: disease.clone(), introduced_at: 48, introductions: 1 };
+        let profiles = DiseaseProfiles::new(disease, vec![]).with_strains(vec![strain], &[]);
+        let no_active_cases = |hour| Counts::new_test(hour, 90, 0, 0, 0, 10, 0);
+
+        assert!(!Epidemiology::stop_simulation(&mut [], &RunMode::Standalone, &no_active_cases(1), &profiles));
+        assert!(!Epidemiology::stop_simulation(&mut [], &RunMode::Standalone, &no_active_cases(48), &profiles));
+        assert!(Epidemiology::stop_simulation(&mut [], &RunMode::Standalone, &no_active_cases(49), &profiles));
+    }
 }
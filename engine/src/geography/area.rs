@@ -130,6 +130,21 @@ pub fn area_factory(start_point: Point, end_point: Point, size: i32) -> Vec<Area
     areas
 }
 
+/// The building made by `area_factory` for `zone` that contains `cell`, if any
+pub fn building_containing(zone: &Area, size: i32, cell: &Point) -> Option<Area> {
+    if !zone.contains(cell) {
+        return None;
+    }
+    let start = Point::new(zone.start_offset.x + (cell.x - zone.start_offset.x) / size * size,
+                           zone.start_offset.y + (cell.y - zone.start_offset.y) / size * size);
+    let end = Point::new(start.x + size - 1, start.y + size - 1);
+    if end.x <= zone.end_offset.x && end.y <= zone.end_offset.y {
+        Some(Area::new(start, end))
+    } else {
+        None
+    }
+}
+
 pub struct AreaIterator {
     area: Area,
     iter_index: Point,
@@ -279,6 +294,17 @@ mod tests {
         assert_eq!(buildings.last().unwrap().end_offset, Point::new(21, 8));
     }
 
+    #[test]
+    fn should_find_building_containing_cell() {
+        let zone = Area::new(Point::new(10, 0), Point::new(21, 10));
+        let buildings = area_factory(zone.start_offset, zone.end_offset, 3);
+
+        assert_eq!(building_containing(&zone, 3, &Point::new(14, 4)), Some(buildings[5]));
+        assert_eq!(building_containing(&zone, 3, &Point::new(21, 8)), buildings.last().copied());
+        assert_eq!(building_containing(&zone, 3, &Point::new(12, 9)), None);
+        assert_eq!(building_containing(&zone, 3, &Point::new(9, 4)), None);
+    }
+
     #[test]
     fn should_get_neighbor_of() {
         let area = get_area();
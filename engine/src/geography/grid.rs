@@ -21,27 +21,43 @@ use plotters::prelude::*;
 
 use crate::{agent, constants};
 use crate::agent::{Citizen, PopulationRecord};
-use crate::config::{AutoPopulation, CsvPopulation, StartingInfections};
+use crate::config::{AutoPopulation, StartingInfections};
+use crate::disease::DiseaseProfiles;
 use crate::geography::{Area, Point};
+use crate::hospital_beds::HospitalBeds;
+
+use fnv::FnvHashMap;
+
+/// Where in the grid something happened to a citizen
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Setting {
+    Home,
+    Housing,
+    Transport,
+    Work,
+    School,
+    Hospital,
+}
 
-use std::fs::File;
-use std::collections::HashMap;
-
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 pub struct Grid {
     pub grid_size: i32,
     pub housing_area: Area,
     pub work_area: Area,
     pub transport_area: Area,
     pub hospital_area: Area,
+    pub school_area: Area,
     pub houses: Vec<Area>,
     pub offices: Vec<Area>,
+    pub classrooms: Vec<Area>,
+    #[serde(default)]
+    pub hospital_beds: HospitalBeds,
 
     //Occupancy based on home and work locations - updated when travellers arrive/depart
-    #[serde(skip_serializing)]
-    pub houses_occupancy: HashMap<Area, i32>,
-    #[serde(skip_serializing)]
-    pub offices_occupancy: HashMap<Area, i32>,
+    #[serde(skip)]
+    pub houses_occupancy: FnvHashMap<Area, i32>,
+    #[serde(skip)]
+    pub offices_occupancy: FnvHashMap<Area, i32>,
 }
 
 impl Grid {
@@ -105,8 +121,8 @@ impl Grid {
 
 
     /// Takes a list of Citizen's and returns a hashmap of the agents that reside at the same position
-    pub fn group_agents_by_home_locations(agent_list: &Vec<Citizen>) -> HashMap<&Area, Vec<&Citizen>> {
-        let mut agents_by_home_locations: HashMap<&Area, Vec<&Citizen>> = HashMap::new();
+    pub fn group_agents_by_home_locations(agent_list: &Vec<Citizen>) -> FnvHashMap<&Area, Vec<&Citizen>> {
+        let mut agents_by_home_locations: FnvHashMap<&Area, Vec<&Citizen>> = FnvHashMap::default();
         agent_list.iter().for_each(|agent| {
             match agents_by_home_locations.get(&agent.home_location) {
                 None => {
@@ -122,13 +138,15 @@ impl Grid {
         agents_by_home_locations
     }
     /// Creates a png file, of the worls
-    /// Where each vertical slice is colour coded, to the area type (Home - Yellow, Transport - Grey, Work - Dark Blue , Hospital - Red)
+    /// Where each vertical slice is colour coded, to the area type (Home - Yellow, Transport - Grey, Work - Dark Blue,
+    /// School - Green, Hospital - Red)
     /// Draws the actual homes,
     fn draw(&self, home_locations: &Vec<Point>, homes: &Vec<Area>, offices: &Vec<Area>) {
         let mut draw_backend = BitMapBackend::new("grid.png", (self.grid_size as u32, self.grid_size as u32));
         Grid::draw_rect(&mut draw_backend, &self.housing_area, &plotters::style::YELLOW);
         Grid::draw_rect(&mut draw_backend, &self.transport_area, &plotters::style::RGBColor(121, 121, 121));
         Grid::draw_rect(&mut draw_backend, &self.work_area, &plotters::style::BLUE);
+        Grid::draw_rect(&mut draw_backend, &self.school_area, &plotters::style::GREEN);
         Grid::draw_rect(&mut draw_backend, &self.hospital_area, &plotters::style::RED);
         for home in homes {
             Grid::draw_rect(&mut draw_backend, home, &plotters::style::RGBColor(204, 153, 0));
@@ -136,6 +154,9 @@ impl Grid {
         for office in offices {
             Grid::draw_rect(&mut draw_backend, office, &plotters::style::RGBColor(51, 153, 255));
         }
+        for classroom in &self.classrooms {
+            Grid::draw_rect(&mut draw_backend, classroom, &plotters::style::RGBColor(102, 204, 102));
+        }
         for home in home_locations {
             draw_backend.draw_pixel((home.x, home.y), &plotters::style::BLACK.to_rgba()).unwrap();
         }
@@ -147,21 +168,22 @@ impl Grid {
                       style, true).unwrap();
     }
 
-    pub fn read_population(&mut self, csv_pop: &CsvPopulation, starting_infections: &StartingInfections,
-                           rng: &mut impl rand::RngCore) -> (Vec<Point>, Vec<Citizen>) {
-        let file = File::open(&csv_pop.file).expect("Could not read population file");
-        let mut rdr = csv::Reader::from_reader(file);
+    pub fn read_population(&mut self, records: Vec<PopulationRecord>, starting_infections: &StartingInfections,
+                           disease_profiles: &DiseaseProfiles, rng: &mut impl rand::RngCore) -> (Vec<Point>, Vec<Citizen>) {
         let mut homes_iter = self.houses.iter().cycle();
         let mut offices_iter = self.offices.iter().cycle();
+        let mut classrooms_iter = self.classrooms.iter().cycle();
 
         let mut citizens = Vec::new();
-        for result in rdr.deserialize() {
-            let record: PopulationRecord = result.expect("Could not deserialize population line");
-
+        for record in records {
             //TODO seems like transport point isn't being used on the routine() function
             let home = *homes_iter.next().unwrap();
-            let office = *offices_iter.next().unwrap();
-            let citizen = Citizen::from_record(record, home, office, home.get_random_point(rng), rng);
+            let work_location = if record.is_student() {
+                *classrooms_iter.next().expect("The grid is too small for any classrooms")
+            } else {
+                *offices_iter.next().unwrap()
+            };
+            let citizen = Citizen::from_record(record, home, work_location, home.get_random_point(rng), disease_profiles, rng);
             citizens.push(citizen);
         }
         let house_capacity = (constants::HOME_SIZE * constants::HOME_SIZE) as usize;
@@ -177,16 +199,37 @@ impl Grid {
         (home_loc, agents_in_order)
     }
 
+    /// The housing area outside of the citizen's `home` is `Housing`
+    pub fn setting_of(&self, cell: &Point, home: &Area) -> Setting {
+        if home.contains(cell) {
+            Setting::Home
+        } else if self.hospital_area.contains(cell) {
+            Setting::Hospital
+        } else if self.transport_area.contains(cell) {
+            Setting::Transport
+        } else if self.work_area.contains(cell) {
+            Setting::Work
+        } else if self.school_area.contains(cell) {
+            Setting::School
+        } else {
+            Setting::Housing
+        }
+    }
+
     pub fn increase_hospital_size(&mut self, grid_size: i32) {
         let start_offset = self.hospital_area.start_offset;
         let end_offset = Point::new(grid_size, grid_size);
+        let cells_before = self.hospital_area.get_number_of_cells();
 
-        self.hospital_area = Area::new(start_offset, end_offset)
+        self.hospital_area = Area::new(start_offset, end_offset);
+        self.hospital_beds.expand(self.hospital_area.get_number_of_cells() as f64 / cells_before as f64);
     }
 
-    pub fn resize_hospital(&mut self, number_of_agents: usize, hospital_staff_percentage: f64, hospital_beds_percentage: f64) {
-        let hospital_bed_count = (number_of_agents as f64 * hospital_beds_percentage +
-            number_of_agents as f64 * hospital_staff_percentage).ceil() as usize;
+    /// Sizes the hospital for its beds and staff
+    pub fn resize_hospital(&mut self, number_of_agents: usize, hospital_staff_percentage: f64, hospital_beds: HospitalBeds) {
+        self.hospital_beds = hospital_beds;
+        let hospital_bed_count = (hospital_beds.get_general() + hospital_beds.get_icu()) as usize +
+            (number_of_agents as f64 * hospital_staff_percentage).ceil() as usize;
 
         if !(hospital_bed_count > self.hospital_area.get_number_of_cells()) {
             let hospital_end_y = hospital_bed_count as i32 / (self.hospital_area.end_offset.x - self.hospital_area.start_offset.x);
@@ -208,8 +251,8 @@ impl Grid {
     // }
 
     /// Retrieves the number of Citizens per work building
-    pub fn group_office_locations_by_occupancy(&self, citizens: &[Citizen]) -> HashMap<Area, i32> {
-        let mut occupancy = HashMap::new();
+    pub fn group_office_locations_by_occupancy(&self, citizens: &[Citizen]) -> FnvHashMap<Area, i32> {
+        let mut occupancy = FnvHashMap::default();
         self.offices.iter().for_each(|house| {
             occupancy.insert(*house, 0);
         });
@@ -254,7 +297,7 @@ impl Grid {
 mod tests {
     use rand::thread_rng;
     use super::*;
-    use crate::geography::define_geography;
+    use crate::geography::{define_geography, define_geography_with_school};
 
     #[test]
     fn should_generate_population() {
@@ -288,11 +331,26 @@ mod tests {
     #[test]
     fn should_increase_hospital_size() {
         let mut grid = define_geography(100);
+        grid.hospital_beds = HospitalBeds::new(10, 2);
 
         grid.increase_hospital_size(120);
 
         assert_eq!(grid.hospital_area.start_offset, Point::new(70, 0));
         assert_eq!(grid.hospital_area.end_offset, Point::new(120, 120));
+        assert_eq!(grid.hospital_beds, HospitalBeds::new(62, 2));
+    }
+
+    #[test]
+    fn should_find_setting_of_cell() {
+        let grid = define_geography_with_school(100);
+        let home = Area::new(Point::new(0, 0), Point::new(2, 2));
+
+        assert_eq!(grid.setting_of(&Point::new(1, 1), &home), Setting::Home);
+        assert_eq!(grid.setting_of(&Point::new(5, 5), &home), Setting::Housing);
+        assert_eq!(grid.setting_of(&grid.transport_area.start_offset, &home), Setting::Transport);
+        assert_eq!(grid.setting_of(&grid.work_area.start_offset, &home), Setting::Work);
+        assert_eq!(grid.setting_of(&grid.school_area.start_offset, &home), Setting::School);
+        assert_eq!(grid.setting_of(&grid.hospital_area.start_offset, &home), Setting::Hospital);
     }
 
     #[test]
@@ -303,20 +361,23 @@ mod tests {
 
         let message = grid_message.as_object().unwrap();
         let keys = message.keys();
-        assert_eq!(keys.len(), 7);
+        assert_eq!(keys.len(), 10);
         assert!(message.contains_key("grid_size"));
         assert!(message.contains_key("housing_area"));
         assert!(message.contains_key("work_area"));
         assert!(message.contains_key("transport_area"));
         assert!(message.contains_key("hospital_area"));
+        assert!(message.contains_key("school_area"));
+        assert!(message.contains_key("hospital_beds"));
         assert!(message.contains_key("houses"));
         assert!(message.contains_key("offices"));
+        assert!(message.contains_key("classrooms"));
     }
 
     #[test]
     fn should_resize_hospital() {
         let mut grid = define_geography(100);
-        grid.resize_hospital(1000, 0.02, 0.01);
+        grid.resize_hospital(1000, 0.02, HospitalBeds::new(10, 0));
 
         assert_eq!(grid.hospital_area.start_offset, Point::new(70, 0));
         assert_eq!(grid.hospital_area.end_offset, Point::new(79, 3));
@@ -325,7 +386,7 @@ mod tests {
     #[test]
     fn should_not_resize_hospital_if_population_is_too_high() {
         let mut grid = define_geography(100);
-        grid.resize_hospital(50000, 0.02, 0.01);
+        grid.resize_hospital(50000, 0.02, HospitalBeds::new(500, 0));
 
         assert_eq!(grid.hospital_area.start_offset, Point::new(70, 0));
         assert_eq!(grid.hospital_area.end_offset, Point::new(79, 100));
@@ -18,44 +18,60 @@
  */
 
 //! This seems to make a rectangle of grid_size X grid_size
-//! Then breaks it up into 4 vertical slices
-//! Width dictated by: HOUSE_AREA_RELATIVE_SIZE, TRANSPORT_AREA_RELATIVE_SIZE, WORK_AREA_RELATIVE_SIZE, INITIAL_HOSPITAL_RELATIVE_SIZE
-//! Like: (H=Home, T=Transport, W=Work, I=Hospital)
-//!     HHHHH TT WWW II
-//!     HHHHH TT WWW II
-//!     HHHHH TT WWW II
-//!     HHHHH TT WWW II
-//!     HHHHH TT WWW II
+//! Then breaks it up into 5 vertical slices, or 4 without a school for populations with no students
+//! Width dictated by: HOUSE_AREA_RELATIVE_SIZE, TRANSPORT_AREA_RELATIVE_SIZE, WORK_AREA_RELATIVE_SIZE,
+//! SCHOOL_AREA_RELATIVE_SIZE, INITIAL_HOSPITAL_RELATIVE_SIZE
+//! Like: (H=Home, T=Transport, W=Work, S=School, I=Hospital)
+//!     HHHHH TT WWW SS II
+//!     HHHHH TT WWW SS II
+//!     HHHHH TT WWW SS II
+//!     HHHHH TT WWW SS II
+//!     HHHHH TT WWW SS II
 //!
-//! Then uses area factory, to make boxes inside the Home, and Work area to represent buildings
-//! Which are lined up next to each other
+//! Then uses area factory, to make boxes inside the Home, Work and School area to represent buildings
+//! (and classrooms) which are lined up next to each other
 use crate::constants;
+use crate::hospital_beds::HospitalBeds;
 
 mod area;
 mod grid;
 mod point;
 
-pub use self::area::Area;
-pub use self::grid::Grid;
+pub use self::area::{Area, building_containing};
+pub use self::grid::{Grid, Setting};
 pub use self::point::Point;
-use std::collections::HashMap;
+use fnv::FnvHashMap;
 
+/// The grid of a population without students, whose school area is empty
 pub fn define_geography(grid_size: i32) -> Grid {
+    define_areas(grid_size, 0.0)
+}
+
+/// The grid of a population with students, with a school area between the work area and the hospital
+pub fn define_geography_with_school(grid_size: i32) -> Grid {
+    define_areas(grid_size, constants::SCHOOL_AREA_RELATIVE_SIZE)
+}
+
+fn define_areas(grid_size: i32, school_area_relative_size: f32) -> Grid {
     let home_width = (grid_size as f32 * constants::HOUSE_AREA_RELATIVE_SIZE).ceil() as i32;
     let transport_start = home_width;
     let transport_end = home_width + (grid_size as f32 * constants::TRANSPORT_AREA_RELATIVE_SIZE).ceil() as i32;
     let work_area_start = transport_end;
     let work_area_end = transport_end + (grid_size as f32 * constants::WORK_AREA_RELATIVE_SIZE).ceil() as i32;
-    let hospital_start = work_area_end;
-    let hospital_end = work_area_end + (grid_size as f32 * constants::INITIAL_HOSPITAL_RELATIVE_SIZE).ceil() as i32;
+    let school_area_start = work_area_end;
+    let school_area_end = work_area_end + (grid_size as f32 * school_area_relative_size).ceil() as i32;
+    let hospital_start = school_area_end;
+    let hospital_end = school_area_end + (grid_size as f32 * constants::INITIAL_HOSPITAL_RELATIVE_SIZE).ceil() as i32;
 
     let housing_area = Area::new(Point::new(0, 0), Point::new(home_width - 1, grid_size));
     let transport_area = Area::new(Point::new(transport_start, 0), Point::new(transport_end - 1, grid_size));
     let work_area = Area::new(Point::new(work_area_start, 0), Point::new(work_area_end - 1, grid_size));
+    let school_area = Area::new(Point::new(school_area_start, 0), Point::new(school_area_end - 1, grid_size));
     let hospital_area = Area::new(Point::new(hospital_start, 0), Point::new(hospital_end - 1, grid_size));
 
     let houses = area::area_factory(housing_area.start_offset, housing_area.end_offset, constants::HOME_SIZE);
     let offices = area::area_factory(work_area.start_offset, work_area.end_offset, constants::OFFICE_SIZE);
+    let classrooms = area::area_factory(school_area.start_offset, school_area.end_offset, constants::CLASSROOM_SIZE);
 
     Grid {
         grid_size,
@@ -63,10 +79,13 @@ pub fn define_geography(grid_size: i32) -> Grid {
         transport_area,
         hospital_area,
         work_area,
+        school_area,
         houses,
         offices,
-        houses_occupancy: HashMap::new(),
-        offices_occupancy: HashMap::new(),
+        classrooms,
+        hospital_beds: HospitalBeds::default(),
+        houses_occupancy: FnvHashMap::default(),
+        offices_occupancy: FnvHashMap::default(),
     }
 }
 
@@ -88,5 +107,20 @@ mod tests {
 
         assert_eq!(grid.hospital_area.start_offset, Point::new(7, 0));
         assert_eq!(grid.hospital_area.end_offset, Point::new(7, 10));
+
+        assert!(grid.classrooms.is_empty());
+        assert!(!grid.school_area.contains(&Point::new(7, 0)));
+    }
+
+    #[test]
+    fn should_define_geography_with_school() {
+        let grid = define_geography_with_school(10);
+        assert_eq!(grid.work_area.end_offset, Point::new(6, 10));
+
+        assert_eq!(grid.school_area.start_offset, Point::new(7, 0));
+        assert_eq!(grid.school_area.end_offset, Point::new(7, 10));
+
+        assert_eq!(grid.hospital_area.start_offset, Point::new(8, 0));
+        assert_eq!(grid.hospital_area.end_offset, Point::new(8, 10));
     }
 }
@@ -0,0 +1,119 @@
+/*
+ * EpiRust
+ * Copyright (c) 2020  ThoughtWorks, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+/// The pool of hospital beds a patient is in
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Bed {
+    General,
+    Icu,
+}
+
+/// The number of beds in each pool
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize, Default)]
+pub struct HospitalBeds {
+    general: i32,
+    icu: i32,
+}
+
+impl HospitalBeds {
+    pub fn new(general: i32, icu: i32) -> HospitalBeds {
+        HospitalBeds { general, icu }
+    }
+
+    /// Adds general beds in proportion to the hospital growing by `factor`
+    pub fn expand(&mut self, factor: f64) {
+        self.general = (self.general as f64 * factor).ceil() as i32;
+    }
+
+    pub fn get_general(&self) -> i32 {
+        self.general
+    }
+
+    pub fn get_icu(&self) -> i32 {
+        self.icu
+    }
+}
+
+/// Hands out the beds of each pool over an hour. Patients who already have a bed keep it, and new ones get a bed
+/// of the kind they need while there are any free
+pub struct BedOccupancy {
+    beds: HospitalBeds,
+    general: i32,
+    icu: i32,
+}
+
+impl BedOccupancy {
+    pub fn new(beds: HospitalBeds) -> BedOccupancy {
+        BedOccupancy { beds, general: 0, icu: 0 }
+    }
+
+    /// Counts a patient staying in their bed, even over capacity, e.g. after beds have been taken away
+    pub fn keep(&mut self, bed: Bed) {
+        match bed {
+            Bed::General => self.general += 1,
+            Bed::Icu => self.icu += 1,
+        }
+    }
+
+    /// Takes a bed of the kind, if there is one free
+    pub fn admit(&mut self, bed: Bed) -> bool {
+        let (occupied, capacity) = match bed {
+            Bed::General => (&mut self.general, self.beds.general),
+            Bed::Icu => (&mut self.icu, self.beds.icu),
+        };
+        if *occupied < capacity {
+            *occupied += 1;
+            return true;
+        }
+        false
+    }
+
+    pub fn release(&mut self, bed: Bed) {
+        match bed {
+            Bed::General => self.general -= 1,
+            Bed::Icu => self.icu -= 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_admit_while_there_are_free_beds_of_the_kind() {
+        let mut occupancy = BedOccupancy::new(HospitalBeds::new(2, 1));
+        occupancy.keep(Bed::General);
+
+        assert!(occupancy.admit(Bed::General));
+        assert!(!occupancy.admit(Bed::General));
+        assert!(occupancy.admit(Bed::Icu));
+        assert!(!occupancy.admit(Bed::Icu));
+
+        occupancy.release(Bed::General);
+        assert!(occupancy.admit(Bed::General));
+    }
+
+    #[test]
+    fn should_expand_general_beds() {
+        let mut beds = HospitalBeds::new(10, 4);
+        beds.expand(2.5);
+        assert_eq!(beds, HospitalBeds::new(25, 4));
+    }
+}
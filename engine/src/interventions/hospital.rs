@@ -27,6 +27,7 @@ pub struct BuildNewHospitalConfig {
     pub spread_rate_threshold: i32
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct BuildNewHospital {
     new_infections_in_a_day: i32,
     intervention: Option<BuildNewHospitalConfig>,
@@ -17,51 +17,222 @@
  *
  */
 
+use rand::Rng;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::agent::LockdownRestriction;
 use crate::config::Config;
 use crate::constants;
 use crate::interventions::InterventionConfig::Lockdown;
 use crate::listeners::events::counts::Counts;
 use crate::interventions::intervention_type::InterventionType;
 
+/// A level of one of the metrics a lockdown is decided on
 #[derive(Debug, PartialEq, Serialize, Deserialize, Copy, Clone)]
+pub enum LockdownTrigger {
+    Infected(i32),
+    Hospitalized(i32),
+    /// Day over day growth of active (infected and hospitalized) cases, e.g. 0.1 for 10%
+    DailyGrowthRate(f64),
+}
+
+impl LockdownTrigger {
+    fn exceeded(&self, counts: &Counts, growth_rate: Option<f64>) -> bool {
+        match *self {
+            LockdownTrigger::Infected(infected) => counts.get_infected() > infected,
+            LockdownTrigger::Hospitalized(hospitalized) => counts.get_hospitalized() > hospitalized,
+            LockdownTrigger::DailyGrowthRate(rate) => growth_rate.map_or(false, |growth| growth > rate),
+        }
+    }
+
+    fn below(&self, counts: &Counts, growth_rate: Option<f64>) -> bool {
+        match *self {
+            LockdownTrigger::Infected(infected) => counts.get_infected() < infected,
+            LockdownTrigger::Hospitalized(hospitalized) => counts.get_hospitalized() < hospitalized,
+            LockdownTrigger::DailyGrowthRate(rate) => growth_rate.map_or(false, |growth| growth < rate),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Copy, Clone)]
+pub enum LockdownRelease {
+    AfterDays(i32),
+    /// Once the level has been below the threshold at the start of each day for that many days
+    BelowFor { level: LockdownTrigger, days: i32 },
+}
+
+/// How strict a lockdown is. Each citizen who isn't an essential worker skips work with probability `skip_work` at the
+/// start of the tier, and stays home altogether with probability `stay_home`, which is at most `skip_work`.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Copy, Clone)]
+pub struct LockdownTier {
+    pub level: i32,
+    #[serde(default)]
+    pub skip_work: f64,
+    #[serde(default)]
+    pub stay_home: f64,
+}
+
+impl LockdownTier {
+    /// Everyone who isn't an essential worker stays home
+    fn full() -> LockdownTier {
+        LockdownTier { level: 1, skip_work: 1.0, stay_home: 1.0 }
+    }
+
+    pub fn restriction(&self, rng: &mut impl rand::RngCore) -> LockdownRestriction {
+        // a single draw, so that those who stay home are among those who would skip work
+        let draw: f64 = rng.gen();
+        if draw < self.stay_home {
+            LockdownRestriction::StaysHome
+        } else if draw < self.skip_work {
+            LockdownRestriction::SkipsWork
+        } else {
+            LockdownRestriction::None
+        }
+    }
+}
+
+/// Without a `release` rule, the lockdown is lifted 1.5 quarantine periods after the last active case
+#[derive(Debug, PartialEq, Serialize, Deserialize, Copy, Clone)]
+#[serde(remote = "Self")]
 pub struct LockdownConfig {
-    pub at_number_of_infections: i32,
+    #[serde(default)]
+    pub at_number_of_infections: Option<i32>,
+    #[serde(default)]
+    pub trigger: Option<LockdownTrigger>,
     pub essential_workers_population: f64,
+    #[serde(default)]
+    pub release: Option<LockdownRelease>,
+    #[serde(default = "LockdownConfig::relock_by_default")]
+    pub relock: bool,
+    #[serde(default = "LockdownTier::full", deserialize_with = "deserialize_tier")]
+    pub tier: LockdownTier,
+}
+
+/// A lockdown needs exactly one of `trigger` and `at_number_of_infections`
+impl<'de> Deserialize<'de> for LockdownConfig {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+    {
+        let config = LockdownConfig::deserialize(deserializer)?;
+        if config.trigger.is_some() == config.at_number_of_infections.is_some() {
+            return Err(de::Error::custom("A lockdown needs exactly one of trigger and at_number_of_infections"));
+        }
+        Ok(config)
+    }
+}
+
+impl Serialize for LockdownConfig {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+    {
+        LockdownConfig::serialize(self, serializer)
+    }
 }
 
+fn deserialize_tier<'de, D>(deserializer: D) -> Result<LockdownTier, D::Error>
+    where
+        D: Deserializer<'de>,
+{
+    let tier = LockdownTier::deserialize(deserializer)?;
+    if !(0.0 <= tier.stay_home && tier.stay_home <= tier.skip_work && tier.skip_work <= 1.0) {
+        return Err(de::Error::custom(format!(
+            "A lockdown tier needs 0 <= stay_home <= skip_work <= 1, not stay_home {} and skip_work {}",
+            tier.stay_home, tier.skip_work)));
+    }
+    Ok(tier)
+}
+
+impl LockdownConfig {
+    fn relock_by_default() -> bool {
+        true
+    }
+
+    fn trigger(&self) -> LockdownTrigger {
+        match (self.trigger, self.at_number_of_infections) {
+            (Some(trigger), _) => trigger,
+            (None, infections) => LockdownTrigger::Infected(infections.expect("Lockdown has no trigger"))
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct LockdownIntervention {
     is_locked_down: bool,
     intervention: Option<LockdownConfig>,
     pub zero_infection_hour: i32,
+    locked_at: i32,
+    times_locked: i32,
+    active_at_start_of_day: Option<i32>,
+    growth_rate: Option<f64>,
+    below_since: Option<i32>,
 }
 
 impl LockdownIntervention {
-    pub fn get_lock_down_intervention(config: &Config) -> Option<LockdownConfig> {
-        return config.get_interventions().iter().filter_map(|i| {
+    pub fn get_lock_down_interventions(config: &Config) -> Vec<LockdownConfig> {
+        config.get_interventions().iter().filter_map(|i| {
             match i {
-                Lockdown(x) => Some(x),
+                Lockdown(x) => Some(*x),
                 _ => None
             }
-        }).next().copied();
+        }).collect()
+    }
+
+    /// One lockdown for each `Lockdown` in the config
+    pub fn init(config: &Config) -> Vec<LockdownIntervention> {
+        LockdownIntervention::get_lock_down_interventions(config).into_iter()
+            .map(LockdownIntervention::new)
+            .collect()
     }
 
-    pub fn init(config: &Config) -> LockdownIntervention {
+    fn new(config: LockdownConfig) -> LockdownIntervention {
         LockdownIntervention {
             is_locked_down: false,
-            intervention: LockdownIntervention::get_lock_down_intervention(config),
+            intervention: Some(config),
             zero_infection_hour: 0,
+            locked_at: 0,
+            times_locked: 0,
+            active_at_start_of_day: None,
+            growth_rate: None,
+            below_since: None,
+        }
+    }
+
+    /// Keeps track of the daily growth of active cases and how long the release level has been met, at the start
+    /// of each day
+    pub fn update(&mut self, counts: &Counts) {
+        if counts.get_hour() % constants::HOURS_IN_A_DAY != 0 {
+            return;
+        }
+        let active = counts.get_infected() + counts.get_hospitalized();
+        self.growth_rate = match self.active_at_start_of_day {
+            Some(previous) if previous > 0 => Some(active as f64 / previous as f64 - 1.0),
+            _ => None
+        };
+        self.active_at_start_of_day = Some(active);
+
+        if let Some(LockdownRelease::BelowFor { level, .. }) = self.intervention.and_then(|i| i.release) {
+            if self.is_locked_down && level.below(counts, self.growth_rate) {
+                self.below_since.get_or_insert(counts.get_hour());
+            } else {
+                self.below_since = None;
+            }
         }
     }
 
     pub fn should_apply(&self, counts: &Counts) -> bool {
-        !self.is_locked_down && counts.get_hour() % constants::HOURS_IN_A_DAY == 0 && self.above_threshold(counts)
+        let may_lock = match self.intervention {
+            Some(i) => self.times_locked == 0 || i.relock,
+            None => false
+        };
+        !self.is_locked_down && may_lock && counts.get_hour() % constants::HOURS_IN_A_DAY == 0
+            && self.above_threshold(counts)
     }
 
     fn above_threshold(&self, counts: &Counts) -> bool {
         match self.intervention {
-            Some(i) => {
-                counts.get_infected() > i.at_number_of_infections
-            }
+            Some(i) => i.trigger().exceeded(counts, self.growth_rate),
             None => false
         }
     }
@@ -73,17 +244,36 @@ impl LockdownIntervention {
     }
 
     pub fn should_unlock(&self, counts: &Counts) -> bool {
-        if counts.get_hour() == self.zero_infection_hour +
-            (constants::QUARANTINE_DAYS as f64 * 1.5).round() as i32 * constants::HOURS_IN_A_DAY {
-            return self.is_locked_down
+        let release = match self.intervention {
+            Some(i) => i.release,
+            None => return false
+        };
+        let hour = counts.get_hour();
+        match release {
+            None => {
+                if hour == self.zero_infection_hour +
+                    (constants::QUARANTINE_DAYS as f64 * 1.5).round() as i32 * constants::HOURS_IN_A_DAY {
+                    return self.is_locked_down;
+                }
+                false
+            }
+            Some(LockdownRelease::AfterDays(days)) => {
+                self.is_locked_down && hour % constants::HOURS_IN_A_DAY == 0
+                    && hour >= self.locked_at + days * constants::HOURS_IN_A_DAY
+            }
+            Some(LockdownRelease::BelowFor { days, .. }) => {
+                self.is_locked_down && hour % constants::HOURS_IN_A_DAY == 0
+                    && self.below_since.map_or(false, |since| hour >= since + days * constants::HOURS_IN_A_DAY)
+            }
         }
-        false
     }
 
-    pub fn apply(&mut self) {
+    pub fn apply(&mut self, hour: i32) {
         match self.intervention {
             Some(_i) => {
                 self.is_locked_down = true;
+                self.locked_at = hour;
+                self.times_locked += 1;
             }
             None => { panic!("Tried to apply lockdown when intervention is not present"); }
         }
@@ -92,6 +282,7 @@ impl LockdownIntervention {
     pub fn unapply(&mut self) {
         self.is_locked_down = false;
         self.zero_infection_hour = 0;
+        self.below_since = None;
     }
 
     pub fn get_essential_workers_percentage(&self) -> f64 {
@@ -101,9 +292,35 @@ impl LockdownIntervention {
         }
     }
 
+    /// Essential workers are picked once, for the lockdown with the most of them
+    pub fn essential_workers_population(lockdowns: &[LockdownIntervention]) -> f64 {
+        lockdowns.iter().map(|lockdown| lockdown.get_essential_workers_percentage()).fold(0.0, f64::max)
+    }
+
+    /// The share of the essential workers this lockdown lets work, for it to have its `essential_workers_population`
+    pub fn essential_workers_exempt(&self, lockdowns: &[LockdownIntervention]) -> f64 {
+        let picked = LockdownIntervention::essential_workers_population(lockdowns);
+        if picked > 0.0 { self.get_essential_workers_percentage() / picked } else { 0.0 }
+    }
+
     pub fn is_locked_down(&self) -> bool {
         self.is_locked_down
     }
+
+    pub fn get_tier(&self) -> Option<LockdownTier> {
+        self.intervention.map(|i| i.tier)
+    }
+
+    /// The tier of the strictest lockdown in force
+    pub fn active_tier(lockdowns: &[LockdownIntervention]) -> Option<LockdownTier> {
+        lockdowns.iter()
+            .filter(|lockdown| lockdown.is_locked_down())
+            .filter_map(|lockdown| lockdown.get_tier())
+            .fold(None, |strictest: Option<LockdownTier>, tier| match strictest {
+                Some(s) if s.level >= tier.level => Some(s),
+                _ => Some(tier)
+            })
+    }
 }
 
 impl InterventionType for LockdownIntervention {
@@ -113,7 +330,8 @@ impl InterventionType for LockdownIntervention {
 
     fn json_data(&self) -> String {
         if self.is_locked_down {
-            r#"{"status": "locked_down"}"#.to_string()
+            let level = self.get_tier().map_or(0, |tier| tier.level);
+            format!(r#"{{"status": "locked_down", "level": {}}}"#, level)
         } else {
             r#"{"status": "lockdown_revoked"}"#.to_string()
         }
@@ -122,72 +340,77 @@ impl InterventionType for LockdownIntervention {
 
 #[cfg(test)]
 mod tests {
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64;
+
     use super::*;
 
     fn get_test_lockdown_intervention(is_locked_down: bool) -> LockdownIntervention {
         let config = LockdownConfig {
-            at_number_of_infections: 20,
+            at_number_of_infections: Some(20),
+            trigger: None,
             essential_workers_population: 0.1,
+            release: None,
+            relock: true,
+            tier: LockdownTier::full(),
         };
-        return LockdownIntervention {
-            is_locked_down,
-            intervention: Some(config),
-            zero_infection_hour: 0,
-        };
+        let mut lockdown = LockdownIntervention::new(config);
+        lockdown.is_locked_down = is_locked_down;
+        lockdown
     }
 
     #[test]
     fn should_apply_lockdown_at_threshold() {
         let config = LockdownConfig {
-            at_number_of_infections: 20,
+            at_number_of_infections: Some(20),
+            trigger: None,
             essential_workers_population: 0.1,
+            release: None,
+            relock: true,
+            tier: LockdownTier::full(),
         };
-        let mut lockdown = LockdownIntervention {
-            is_locked_down: false,
-            intervention: Some(config),
-            zero_infection_hour: 0,
-        };
+        let mut lockdown = LockdownIntervention::new(config);
 
         assert!(!lockdown.should_apply(&Counts::new_test(0, 99, 0, 1, 0, 0, 0)));
         assert!(!lockdown.should_apply(&Counts::new_test(22, 80, 0, 20, 0, 0, 0)));
         assert!(!lockdown.should_apply(&Counts::new_test(28, 79, 0, 21, 0, 0, 0)));
         assert!(lockdown.should_apply(&Counts::new_test(48, 79, 0, 21, 0, 0, 0)));
 
-        lockdown.apply();
+        lockdown.apply(48);
         assert_eq!(lockdown.is_locked_down, true);
     }
 
     #[test]
     fn should_not_apply_lockdown_when_already_locked_down() {
         let config = LockdownConfig {
-            at_number_of_infections: 20,
+            at_number_of_infections: Some(20),
+            trigger: None,
             essential_workers_population: 0.1,
+            release: None,
+            relock: true,
+            tier: LockdownTier::full(),
         };
-        let mut lockdown = LockdownIntervention {
-            is_locked_down: false,
-            intervention: Some(config),
-            zero_infection_hour: 0,
-        };
+        let mut lockdown = LockdownIntervention::new(config);
 
         assert!(lockdown.should_apply(&Counts::new_test(48, 79, 0, 21, 0, 0, 0)));
-        lockdown.apply();
+        lockdown.apply(48);
         assert!(!lockdown.should_apply(&Counts::new_test(48, 75, 0, 25, 0, 0, 0)));
     }
 
     #[test]
     fn should_lift_lockdown_at_after_time_elapsed_and_infections_below_threshold() {
         let config = LockdownConfig {
-            at_number_of_infections: 20,
+            at_number_of_infections: Some(20),
+            trigger: None,
             essential_workers_population: 0.1,
+            release: None,
+            relock: true,
+            tier: LockdownTier::full(),
         };
-        let mut lockdown = LockdownIntervention {
-            is_locked_down: false,
-            intervention: Some(config),
-            zero_infection_hour: 0,
-        };
+        let mut lockdown = LockdownIntervention::new(config);
         assert!(lockdown.should_apply(&Counts::new_test(48, 79, 0, 21, 0, 0, 0)));
 
-        lockdown.apply();
+        lockdown.apply(48);
         let lockdown_until = 48 + (7 * 24);
         lockdown.set_zero_infection_hour(lockdown_until);
         for hr in 48..lockdown_until {
@@ -200,17 +423,17 @@ mod tests {
     #[test]
     fn should_extend_lockdown_until_infections_below_threshold() {
         let config = LockdownConfig {
-            at_number_of_infections: 20,
+            at_number_of_infections: Some(20),
+            trigger: None,
             essential_workers_population: 0.1,
+            release: None,
+            relock: true,
+            tier: LockdownTier::full(),
         };
-        let mut lockdown = LockdownIntervention {
-            is_locked_down: false,
-            intervention: Some(config),
-            zero_infection_hour: 0,
-        };
+        let mut lockdown = LockdownIntervention::new(config);
         assert!(lockdown.should_apply(&Counts::new_test(48, 79, 0, 21, 0, 0, 0)));
 
-        lockdown.apply();
+        lockdown.apply(48);
         let lockdown_until = 48 + (7 * 24);
         lockdown.set_zero_infection_hour(lockdown_until);
         for hr in 48..lockdown_until {
@@ -224,20 +447,178 @@ mod tests {
     #[test]
     fn should_not_reapply_lockdown() {
         let config = LockdownConfig {
-            at_number_of_infections: 20,
+            at_number_of_infections: Some(20),
+            trigger: None,
             essential_workers_population: 0.1,
+            release: None,
+            relock: true,
+            tier: LockdownTier::full(),
         };
-        let mut lockdown = LockdownIntervention {
-            is_locked_down: false,
-            intervention: Some(config),
-            zero_infection_hour: 0,
-        };
-        lockdown.apply();
+        let mut lockdown = LockdownIntervention::new(config);
+        lockdown.apply(48);
         lockdown.set_zero_infection_hour(28);
         assert!(lockdown.should_unlock(&Counts::new_test(532, 80, 0, 20, 0, 0, 0)));
         assert!(!lockdown.should_apply(&Counts::new_test(540, 70, 0, 30, 0, 0, 0)));
     }
 
+    fn lockdown_with(trigger: LockdownTrigger, release: LockdownRelease, relock: bool) -> LockdownIntervention {
+        LockdownIntervention::new(LockdownConfig {
+            at_number_of_infections: None,
+            trigger: Some(trigger),
+            essential_workers_population: 0.1,
+            release: Some(release),
+            relock,
+            tier: LockdownTier::full(),
+        })
+    }
+
+    fn run_day(lockdown: &mut LockdownIntervention, counts: Counts) -> (bool, bool) {
+        lockdown.update(&counts);
+        let applied = lockdown.should_apply(&counts);
+        if applied {
+            lockdown.apply(counts.get_hour());
+        }
+        let unlocked = lockdown.should_unlock(&counts);
+        if unlocked {
+            lockdown.unapply();
+        }
+        (applied, unlocked)
+    }
+
+    #[test]
+    fn should_lock_on_hospitalizations_and_release_after_staying_below_level() {
+        let release = LockdownRelease::BelowFor { level: LockdownTrigger::Hospitalized(5), days: 2 };
+        let mut lockdown = lockdown_with(LockdownTrigger::Hospitalized(10), release, true);
+
+        assert_eq!(run_day(&mut lockdown, Counts::new_test(24, 80, 0, 50, 10, 0, 0)), (false, false));
+        assert_eq!(run_day(&mut lockdown, Counts::new_test(48, 80, 0, 0, 11, 0, 0)), (true, false));
+        assert_eq!(run_day(&mut lockdown, Counts::new_test(72, 80, 0, 0, 4, 0, 0)), (false, false));
+        assert_eq!(run_day(&mut lockdown, Counts::new_test(96, 80, 0, 0, 6, 0, 0)), (false, false));
+        assert_eq!(run_day(&mut lockdown, Counts::new_test(120, 80, 0, 0, 4, 0, 0)), (false, false));
+        assert_eq!(run_day(&mut lockdown, Counts::new_test(144, 80, 0, 0, 3, 0, 0)), (false, false));
+        assert_eq!(run_day(&mut lockdown, Counts::new_test(168, 80, 0, 0, 2, 0, 0)), (false, true));
+        assert!(!lockdown.is_locked_down());
+
+        assert_eq!(run_day(&mut lockdown, Counts::new_test(192, 80, 0, 0, 12, 0, 0)), (true, false));
+    }
+
+    #[test]
+    fn should_lock_on_growth_rate_for_fixed_duration_only_once_without_relock() {
+        let mut lockdown = lockdown_with(LockdownTrigger::DailyGrowthRate(0.5), LockdownRelease::AfterDays(2), false);
+
+        assert_eq!(run_day(&mut lockdown, Counts::new_test(0, 80, 0, 10, 0, 0, 0)), (false, false));
+        assert_eq!(run_day(&mut lockdown, Counts::new_test(24, 80, 0, 14, 1, 0, 0)), (false, false));
+        assert_eq!(run_day(&mut lockdown, Counts::new_test(48, 80, 0, 20, 3, 0, 0)), (true, false));
+        assert_eq!(run_day(&mut lockdown, Counts::new_test(72, 80, 0, 40, 3, 0, 0)), (false, false));
+        assert_eq!(run_day(&mut lockdown, Counts::new_test(96, 80, 0, 80, 3, 0, 0)), (false, true));
+        assert_eq!(run_day(&mut lockdown, Counts::new_test(120, 80, 0, 160, 3, 0, 0)), (false, false));
+        assert!(!lockdown.is_locked_down());
+    }
+
+    #[test]
+    fn should_read_legacy_and_trigger_lockdown_configs() {
+        let legacy: LockdownConfig = serde_json::from_str(
+            r#"{"at_number_of_infections": 100, "essential_workers_population": 0.1}"#).unwrap();
+        let circuit_breaker: LockdownConfig = serde_json::from_str(r#"{"trigger": {"DailyGrowthRate": 0.2},
+            "release": {"BelowFor": {"level": {"Infected": 50}, "days": 14}}, "relock": false,
+            "essential_workers_population": 0.1}"#).unwrap();
+
+        assert_eq!(legacy.trigger(), LockdownTrigger::Infected(100));
+        assert_eq!(legacy.release, None);
+        assert!(legacy.relock);
+        assert_eq!(circuit_breaker.trigger(), LockdownTrigger::DailyGrowthRate(0.2));
+        assert_eq!(circuit_breaker.release,
+                   Some(LockdownRelease::BelowFor { level: LockdownTrigger::Infected(50), days: 14 }));
+        assert!(!circuit_breaker.relock);
+    }
+
+    #[test]
+    fn should_reject_lockdowns_without_exactly_one_trigger() {
+        let both = serde_json::from_str::<LockdownConfig>(r#"{"at_number_of_infections": 100,
+            "trigger": {"Infected": 100}, "essential_workers_population": 0.1}"#);
+        let neither = serde_json::from_str::<LockdownConfig>(r#"{"essential_workers_population": 0.1}"#);
+
+        for error in [both.unwrap_err().to_string(), neither.unwrap_err().to_string()].iter() {
+            assert!(error.contains("exactly one of trigger and at_number_of_infections"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn should_exempt_the_essential_workers_of_each_lockdown() {
+        let mut few = lockdown_with(LockdownTrigger::Infected(10), LockdownRelease::AfterDays(7), true);
+        few.intervention.as_mut().unwrap().essential_workers_population = 0.05;
+        let many = lockdown_with(LockdownTrigger::Infected(50), LockdownRelease::AfterDays(7), true);
+        let lockdowns = vec![few, many];
+
+        assert_eq!(LockdownIntervention::essential_workers_population(&lockdowns), 0.1);
+        assert_eq!(lockdowns[0].essential_workers_exempt(&lockdowns), 0.5);
+        assert_eq!(lockdowns[1].essential_workers_exempt(&lockdowns), 1.0);
+    }
+
+    #[test]
+    fn should_find_strictest_tier_in_force() {
+        let tier = |level: i32, skip_work: f64| LockdownTier { level, skip_work, stay_home: 0.0 };
+        let mut offices = lockdown_with(LockdownTrigger::Infected(10), LockdownRelease::AfterDays(7), true);
+        offices.intervention.as_mut().unwrap().tier = tier(1, 0.5);
+        let mut essential_only = lockdown_with(LockdownTrigger::Infected(50), LockdownRelease::AfterDays(7), true);
+        essential_only.intervention.as_mut().unwrap().tier = tier(2, 1.0);
+        let mut lockdowns = vec![offices, essential_only];
+
+        assert_eq!(LockdownIntervention::active_tier(&lockdowns), None);
+        lockdowns[0].apply(24);
+        assert_eq!(LockdownIntervention::active_tier(&lockdowns), Some(tier(1, 0.5)));
+        lockdowns[1].apply(48);
+        assert_eq!(LockdownIntervention::active_tier(&lockdowns), Some(tier(2, 1.0)));
+        assert_eq!(lockdowns[1].json_data(), r#"{"status": "locked_down", "level": 2}"#);
+        lockdowns[1].unapply();
+        assert_eq!(LockdownIntervention::active_tier(&lockdowns), Some(tier(1, 0.5)));
+    }
+
+    #[test]
+    fn should_draw_lockdown_restriction_of_tier() {
+        let mut rng = rand::thread_rng();
+        let essential_only = LockdownTier { level: 2, skip_work: 1.0, stay_home: 0.0 };
+        let open = LockdownTier { level: 0, skip_work: 0.0, stay_home: 0.0 };
+
+        for _ in 0..10 {
+            assert_eq!(LockdownTier::full().restriction(&mut rng), LockdownRestriction::StaysHome);
+            assert_eq!(essential_only.restriction(&mut rng), LockdownRestriction::SkipsWork);
+            assert_eq!(open.restriction(&mut rng), LockdownRestriction::None);
+        }
+    }
+
+    #[test]
+    fn should_draw_restrictions_in_the_proportions_of_tier() {
+        let mut rng = Pcg64::seed_from_u64(7);
+        let tier = LockdownTier { level: 1, skip_work: 0.5, stay_home: 0.2 };
+        let draws = 10000;
+        let mut stays_home = 0;
+        let mut skips_work = 0;
+        for _ in 0..draws {
+            match tier.restriction(&mut rng) {
+                LockdownRestriction::StaysHome => stays_home += 1,
+                LockdownRestriction::SkipsWork => skips_work += 1,
+                LockdownRestriction::None => {}
+            }
+        }
+
+        assert!((stays_home as f64 / draws as f64 - 0.2).abs() < 0.02, "{}", stays_home);
+        assert!(((stays_home + skips_work) as f64 / draws as f64 - 0.5).abs() < 0.02, "{}", skips_work);
+    }
+
+    #[test]
+    fn should_reject_tiers_staying_home_more_than_skipping_work() {
+        let lockdown = |tier: &str| serde_json::from_str::<LockdownConfig>(&format!(
+            r#"{{"at_number_of_infections": 100, "essential_workers_population": 0.1, "tier": {}}}"#, tier));
+
+        assert!(lockdown(r#"{"level": 1, "skip_work": 0.5, "stay_home": 0.5}"#).is_ok());
+        for tier in &[r#"{"level": 1, "skip_work": 0.2, "stay_home": 0.5}"#, r#"{"level": 1, "skip_work": 1.5}"#,
+            r#"{"level": 1, "stay_home": -0.1}"#] {
+            let error = lockdown(tier).unwrap_err().to_string();
+            assert!(error.contains("0 <= stay_home <= skip_work <= 1"), "{}", error);
+        }
+    }
+
     #[test]
     fn should_return_intervention_name_as_lockdown() {
         let lockdown_intervention = get_test_lockdown_intervention(false);
@@ -249,7 +630,7 @@ mod tests {
     fn should_return_json_data_with_lockdown_state_as_locked_down_when_city_is_locked_down() {
         let lockdown_intervention = get_test_lockdown_intervention(true);
 
-        assert_eq!(lockdown_intervention.json_data(), r#"{"status": "locked_down"}"#)
+        assert_eq!(lockdown_intervention.json_data(), r#"{"status": "locked_down", "level": 1}"#)
     }
 
     #[test]
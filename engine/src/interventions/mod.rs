@@ -20,22 +20,35 @@
 use crate::interventions::vaccination::{VaccinateConfig, VaccinateIntervention};
 use crate::interventions::lockdown::{LockdownConfig, LockdownIntervention};
 use crate::interventions::hospital::{BuildNewHospitalConfig, BuildNewHospital};
+use crate::interventions::school_closure::{CloseSchoolsConfig, CloseSchoolsIntervention};
+use crate::interventions::vaccination_campaign::{VaccinationCampaign, VaccinationCampaignConfig};
+use crate::interventions::test_trace_isolate::{TestTraceIsolate, TestTraceIsolateConfig};
 
 pub mod hospital;
 pub mod lockdown;
+pub mod school_closure;
+pub mod test_trace_isolate;
 pub mod vaccination;
+pub mod vaccination_campaign;
 pub mod intervention_type;
 
-#[derive(Debug, PartialEq, Serialize, Deserialize, Copy, Clone)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 #[serde(rename = "Intervention")]
 pub enum InterventionConfig {
     Vaccinate(VaccinateConfig),
     Lockdown(LockdownConfig),
     BuildNewHospital(BuildNewHospitalConfig),
+    CloseSchools(CloseSchoolsConfig),
+    VaccinationCampaign(VaccinationCampaignConfig),
+    TestTraceIsolate(TestTraceIsolateConfig),
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Interventions {
     pub vaccinate: VaccinateIntervention,
-    pub lockdown: LockdownIntervention,
+    pub lockdowns: Vec<LockdownIntervention>,
     pub build_new_hospital: BuildNewHospital,
+    pub close_schools: CloseSchoolsIntervention,
+    pub vaccination_campaign: VaccinationCampaign,
+    pub test_trace_isolate: TestTraceIsolate,
 }
@@ -0,0 +1,165 @@
+/*
+ * EpiRust
+ * Copyright (c) 2020  ThoughtWorks, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use crate::config::Config;
+use crate::constants;
+use crate::interventions::InterventionConfig::CloseSchools;
+use crate::interventions::intervention_type::InterventionType;
+use crate::listeners::events::counts::Counts;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Copy, Clone)]
+pub enum CloseSchoolsTrigger {
+    AtHour(i32),
+    AtNumberOfInfections(i32),
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Copy, Clone)]
+pub enum ReopenSchoolsCondition {
+    AfterDays(i32),
+    InfectionsBelow(i32),
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Copy, Clone)]
+pub struct CloseSchoolsConfig {
+    pub close: CloseSchoolsTrigger,
+    pub reopen: ReopenSchoolsCondition,
+}
+
+/// Keeps students, and one caregiver in each of their households, at home while schools are closed.
+/// Schools are closed and reopened at the start of a day.
+#[derive(Serialize, Deserialize)]
+pub struct CloseSchoolsIntervention {
+    closed_at: Option<i32>,
+    intervention: Option<CloseSchoolsConfig>,
+}
+
+impl CloseSchoolsIntervention {
+    pub fn get_close_schools_intervention(config: &Config) -> Option<CloseSchoolsConfig> {
+        config.get_interventions().iter().filter_map(|i| {
+            match i {
+                CloseSchools(x) => Some(x),
+                _ => None
+            }
+        }).next().copied()
+    }
+
+    pub fn init(config: &Config) -> CloseSchoolsIntervention {
+        CloseSchoolsIntervention {
+            closed_at: None,
+            intervention: CloseSchoolsIntervention::get_close_schools_intervention(config),
+        }
+    }
+
+    pub fn should_apply(&self, counts: &Counts) -> bool {
+        let triggered = match self.intervention.map(|i| i.close) {
+            // the first start of a day from that hour
+            Some(CloseSchoolsTrigger::AtHour(hour)) => {
+                hour <= counts.get_hour() && counts.get_hour() < hour + constants::HOURS_IN_A_DAY
+            }
+            Some(CloseSchoolsTrigger::AtNumberOfInfections(infections)) => counts.get_infected() > infections,
+            None => false
+        };
+        !self.is_closed() && counts.get_hour() % constants::HOURS_IN_A_DAY == 0 && triggered
+    }
+
+    pub fn should_reopen(&self, counts: &Counts) -> bool {
+        let closed_at = match self.closed_at {
+            Some(hour) => hour,
+            None => return false
+        };
+        let reopen = match self.intervention.map(|i| i.reopen) {
+            Some(ReopenSchoolsCondition::AfterDays(days)) => {
+                counts.get_hour() >= closed_at + days * constants::HOURS_IN_A_DAY
+            }
+            Some(ReopenSchoolsCondition::InfectionsBelow(infections)) => counts.get_infected() < infections,
+            None => false
+        };
+        counts.get_hour() > closed_at && counts.get_hour() % constants::HOURS_IN_A_DAY == 0 && reopen
+    }
+
+    pub fn apply(&mut self, hour: i32) {
+        if self.intervention.is_none() {
+            panic!("Tried to close schools when intervention is not present");
+        }
+        self.closed_at = Some(hour);
+    }
+
+    pub fn unapply(&mut self) {
+        self.closed_at = None;
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed_at.is_some()
+    }
+}
+
+impl InterventionType for CloseSchoolsIntervention {
+    fn name(&self) -> String {
+        "close_schools".to_string()
+    }
+
+    fn json_data(&self) -> String {
+        if self.is_closed() {
+            r#"{"status": "schools_closed"}"#.to_string()
+        } else {
+            r#"{"status": "schools_reopened"}"#.to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close_schools(close: CloseSchoolsTrigger, reopen: ReopenSchoolsCondition) -> CloseSchoolsIntervention {
+        CloseSchoolsIntervention { closed_at: None, intervention: Some(CloseSchoolsConfig { close, reopen }) }
+    }
+
+    #[test]
+    fn should_close_schools_at_start_of_day_after_threshold() {
+        let mut intervention = close_schools(CloseSchoolsTrigger::AtNumberOfInfections(20),
+                                             ReopenSchoolsCondition::InfectionsBelow(5));
+
+        assert!(!intervention.should_apply(&Counts::new_test(24, 80, 0, 20, 0, 0, 0)));
+        assert!(!intervention.should_apply(&Counts::new_test(30, 79, 0, 21, 0, 0, 0)));
+        assert!(intervention.should_apply(&Counts::new_test(48, 79, 0, 21, 0, 0, 0)));
+
+        intervention.apply(48);
+        assert!(!intervention.should_apply(&Counts::new_test(72, 79, 0, 21, 0, 0, 0)));
+        assert!(!intervention.should_reopen(&Counts::new_test(72, 79, 0, 5, 0, 0, 0)));
+        assert!(intervention.should_reopen(&Counts::new_test(96, 79, 0, 4, 0, 0, 0)));
+    }
+
+    #[test]
+    fn should_reopen_schools_after_days_and_close_only_once_at_hour() {
+        let mut intervention = close_schools(CloseSchoolsTrigger::AtHour(100), ReopenSchoolsCondition::AfterDays(2));
+
+        assert!(!intervention.should_apply(&Counts::new_test(96, 80, 0, 20, 0, 0, 0)));
+        assert!(intervention.should_apply(&Counts::new_test(120, 80, 0, 20, 0, 0, 0)));
+
+        intervention.apply(120);
+        assert_eq!(intervention.json_data(), r#"{"status": "schools_closed"}"#);
+        assert!(!intervention.should_reopen(&Counts::new_test(144, 80, 0, 20, 0, 0, 0)));
+        assert!(intervention.should_reopen(&Counts::new_test(168, 80, 0, 20, 0, 0, 0)));
+
+        intervention.unapply();
+        assert_eq!(intervention.json_data(), r#"{"status": "schools_reopened"}"#);
+        assert!(!intervention.should_apply(&Counts::new_test(192, 80, 0, 20, 0, 0, 0)));
+    }
+}
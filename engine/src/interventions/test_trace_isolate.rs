@@ -0,0 +1,220 @@
+/*
+ * EpiRust
+ * Copyright (c) 2020  ThoughtWorks, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use std::collections::VecDeque;
+
+use fnv::FnvHashSet;
+use rand::Rng;
+use uuid::Uuid;
+
+use crate::agent::Citizen;
+use crate::config::Config;
+use crate::constants;
+use crate::interventions::InterventionConfig;
+use crate::interventions::intervention_type::InterventionType;
+use crate::listeners::events::counts::Counts;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Copy, Clone)]
+pub struct TestTraceIsolateConfig {
+    pub start_hour: i32,
+    pub tests_per_day: i32,
+    /// The chance of an infected citizen testing positive
+    pub sensitivity: f64,
+    /// The chance of a positive citizen recalling each of their contacts
+    pub contact_recall: f64,
+}
+
+/// Who met an infectious neighbour on which day, over the last `QUARANTINE_DAYS`, for positives to recall. A contact is
+/// recorded once a day.
+#[derive(Serialize, Deserialize, Default)]
+pub struct TracingLog {
+    recording: bool,
+    day: i32,
+    today: FnvHashSet<(Uuid, Uuid)>,
+    contacts: VecDeque<(i32, Uuid, Uuid)>,
+}
+
+impl TracingLog {
+    fn new(recording: bool) -> TracingLog {
+        TracingLog { recording, ..TracingLog::default() }
+    }
+
+    pub fn record(&mut self, hour: i32, citizen: Uuid, neighbour: Uuid) {
+        if !self.recording {
+            return;
+        }
+        let day = hour / constants::HOURS_IN_A_DAY;
+        if day != self.day {
+            self.day = day;
+            self.today.clear();
+            while self.contacts.front().map_or(false, |(met_on, _, _)| *met_on <= day - constants::QUARANTINE_DAYS) {
+                self.contacts.pop_front();
+            }
+        }
+        if self.today.insert((citizen, neighbour)) {
+            self.contacts.push_back((day, citizen, neighbour));
+        }
+    }
+
+    /// The citizens who met any of the `neighbours`
+    pub fn contacts_of<'a>(&'a self, neighbours: &'a FnvHashSet<Uuid>) -> impl Iterator<Item=Uuid> + 'a {
+        self.contacts.iter()
+            .filter(move |(_, _, neighbour)| neighbours.contains(neighbour))
+            .map(|(_, citizen, _)| *citizen)
+    }
+}
+
+/// Tests up to `tests_per_day` symptomatic citizens every day from `start_hour`. Citizens who test positive, and the
+/// contacts they recall, are quarantined at home for `QUARANTINE_DAYS`.
+#[derive(Serialize, Deserialize)]
+pub struct TestTraceIsolate {
+    intervention: Option<TestTraceIsolateConfig>,
+    tracing_log: TracingLog,
+    tests_today: usize,
+    positives_today: usize,
+    traced_today: usize,
+}
+
+impl TestTraceIsolate {
+    pub fn get_test_trace_isolate(config: &Config) -> Option<TestTraceIsolateConfig> {
+        config.get_interventions().iter().filter_map(|i| {
+            match i {
+                InterventionConfig::TestTraceIsolate(x) => Some(x),
+                _ => None
+            }
+        }).next().copied()
+    }
+
+    pub fn init(config: &Config) -> TestTraceIsolate {
+        let intervention = TestTraceIsolate::get_test_trace_isolate(config);
+        TestTraceIsolate {
+            intervention,
+            tracing_log: TracingLog::new(intervention.is_some()),
+            tests_today: 0,
+            positives_today: 0,
+            traced_today: 0,
+        }
+    }
+
+    pub fn should_test(&self, counts: &Counts) -> bool {
+        match self.intervention {
+            Some(i) => {
+                let hour = counts.get_hour();
+                hour >= i.start_hour && (hour - i.start_hour) % constants::HOURS_IN_A_DAY == 0
+            }
+            None => false
+        }
+    }
+
+    /// Symptomatic citizens who are neither in hospital nor already quarantined
+    pub fn can_be_tested(citizen: &Citizen) -> bool {
+        citizen.state_machine.is_symptomatic() && !citizen.is_hospitalized() && !citizen.is_quarantined()
+    }
+
+    pub fn tests_per_day(&self) -> usize {
+        self.intervention.map_or(0, |i| i.tests_per_day.max(0) as usize)
+    }
+
+    pub fn test(&self, rng: &mut impl rand::RngCore) -> bool {
+        self.intervention.map_or(false, |i| rng.gen_bool(i.sensitivity))
+    }
+
+    pub fn recalls_contact(&self, rng: &mut impl rand::RngCore) -> bool {
+        self.intervention.map_or(false, |i| rng.gen_bool(i.contact_recall))
+    }
+
+    pub fn quarantine_ends_at(hour: i32) -> i32 {
+        hour + constants::QUARANTINE_DAYS * constants::HOURS_IN_A_DAY
+    }
+
+    pub fn get_tracing_log(&self) -> &TracingLog {
+        &self.tracing_log
+    }
+
+    pub fn get_tracing_log_mut(&mut self) -> &mut TracingLog {
+        &mut self.tracing_log
+    }
+
+    pub fn tested(&mut self, tests: usize, positives: usize, traced: usize) {
+        self.tests_today = tests;
+        self.positives_today = positives;
+        self.traced_today = traced;
+    }
+}
+
+impl InterventionType for TestTraceIsolate {
+    fn name(&self) -> String {
+        "test_trace_isolate".to_string()
+    }
+
+    fn json_data(&self) -> String {
+        format!(r#"{{"tests": {}, "positives": {}, "traced": {}}}"#, self.tests_today, self.positives_today,
+                self.traced_today)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_find_contacts_met_in_last_quarantine_days() {
+        let mut log = TracingLog::new(true);
+        let (a, b, c, infected) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        log.record(10, a, infected);
+        log.record(11, a, infected);
+        log.record(12, b, c);
+        log.record(24 * 10, b, infected);
+        log.record(24 * 14 + 5, c, infected);
+
+        let neighbours: FnvHashSet<Uuid> = vec![infected].into_iter().collect();
+        assert_eq!(log.contacts_of(&neighbours).collect::<Vec<_>>(), vec![b, c]);
+    }
+
+    #[test]
+    fn should_not_record_contacts_without_intervention() {
+        let mut log = TracingLog::default();
+        let (a, infected) = (Uuid::new_v4(), Uuid::new_v4());
+        log.record(10, a, infected);
+
+        let neighbours: FnvHashSet<Uuid> = vec![infected].into_iter().collect();
+        assert_eq!(log.contacts_of(&neighbours).count(), 0);
+    }
+
+    #[test]
+    fn should_test_every_day_from_start_hour() {
+        let config = TestTraceIsolateConfig { start_hour: 48, tests_per_day: 10, sensitivity: 0.8, contact_recall: 0.5 };
+        let mut tti = TestTraceIsolate {
+            intervention: Some(config),
+            tracing_log: TracingLog::new(true),
+            tests_today: 0,
+            positives_today: 0,
+            traced_today: 0,
+        };
+
+        assert!(!tti.should_test(&Counts::new_test(24, 80, 0, 20, 0, 0, 0)));
+        assert!(tti.should_test(&Counts::new_test(48, 80, 0, 20, 0, 0, 0)));
+        assert!(!tti.should_test(&Counts::new_test(60, 80, 0, 20, 0, 0, 0)));
+        assert!(tti.should_test(&Counts::new_test(72, 80, 0, 20, 0, 0, 0)));
+        assert_eq!(tti.tests_per_day(), 10);
+
+        tti.tested(10, 6, 12);
+        assert_eq!(tti.json_data(), r#"{"tests": 10, "positives": 6, "traced": 12}"#);
+    }
+}
@@ -37,6 +37,7 @@ impl VaccinateConfig {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct VaccinateIntervention {
     intervention: HashMap<i32, f64>,
 }
@@ -0,0 +1,216 @@
+/*
+ * EpiRust
+ * Copyright (c) 2020  ThoughtWorks, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use crate::agent::{Citizen, WorkStatus};
+use crate::config::Config;
+use crate::constants;
+use crate::interventions::InterventionConfig;
+use crate::interventions::intervention_type::InterventionType;
+use crate::listeners::events::counts::Counts;
+use crate::vaccine::Vaccine;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub enum PriorityGroup {
+    HospitalStaff,
+    EssentialWorkers,
+    /// Citizens of a csv population whose age band starts between `from` and `to`
+    Ages { from: i32, to: i32 },
+}
+
+impl PriorityGroup {
+    fn contains(&self, citizen: &Citizen) -> bool {
+        match self {
+            PriorityGroup::HospitalStaff => match citizen.get_work_status() {
+                WorkStatus::HospitalStaff { .. } => true,
+                _ => false
+            },
+            PriorityGroup::EssentialWorkers => citizen.is_essential_worker(),
+            PriorityGroup::Ages { from, to } => match citizen.get_age_band_start() {
+                Some(age) => *from <= age && age <= *to,
+                None => false
+            },
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct VaccinationCampaignConfig {
+    pub start_hour: i32,
+    pub doses_per_day: i32,
+    #[serde(default)]
+    pub priority_groups: Vec<PriorityGroup>,
+    /// One of the `vaccines` of the config, or a perfect vaccine
+    #[serde(default)]
+    pub vaccine: Option<String>,
+}
+
+/// Gives up to `doses_per_day` doses every day from `start_hour`: first the doses that are due to complete a
+/// course, then to susceptible citizens going through the priority groups in order before everyone else
+#[derive(Serialize, Deserialize)]
+pub struct VaccinationCampaign {
+    intervention: Option<VaccinationCampaignConfig>,
+    vaccine: Vaccine,
+    doses_today: usize,
+    total_doses: usize,
+}
+
+impl VaccinationCampaign {
+    pub fn get_vaccination_campaign(config: &Config) -> Option<VaccinationCampaignConfig> {
+        config.get_interventions().iter().filter_map(|i| {
+            match i {
+                InterventionConfig::VaccinationCampaign(x) => Some(x),
+                _ => None
+            }
+        }).next().cloned()
+    }
+
+    pub fn init(config: &Config) -> VaccinationCampaign {
+        let intervention = VaccinationCampaign::get_vaccination_campaign(config);
+        let vaccine = config.get_vaccine(intervention.as_ref().and_then(|i| i.vaccine.as_ref()));
+        VaccinationCampaign {
+            intervention,
+            vaccine,
+            doses_today: 0,
+            total_doses: 0,
+        }
+    }
+
+    pub fn should_vaccinate(&self, counts: &Counts) -> bool {
+        match &self.intervention {
+            Some(i) => {
+                let hour = counts.get_hour();
+                hour >= i.start_hour && (hour - i.start_hour) % constants::HOURS_IN_A_DAY == 0
+            }
+            None => false
+        }
+    }
+
+    /// Citizens due a dose come first, then the priority groups in order and everyone else after the last group.
+    /// Citizens waiting for their next dose, or who are not susceptible, don't get one.
+    pub fn priority_of(&self, citizen: &Citizen, hour: i32) -> Option<usize> {
+        match citizen.get_vaccination() {
+            Some(vaccination) if vaccination.is_dose_due(hour) && !citizen.state_machine.is_deceased() => Some(0),
+            Some(_) => None,
+            None if citizen.state_machine.is_susceptible() => {
+                let groups = self.priority_groups();
+                Some(1 + groups.iter().position(|group| group.contains(citizen)).unwrap_or(groups.len()))
+            }
+            None => None
+        }
+    }
+
+    pub fn number_of_priorities(&self) -> usize {
+        self.priority_groups().len() + 2
+    }
+
+    pub fn get_vaccine(&self) -> Vaccine {
+        self.vaccine
+    }
+
+    /// Splits the doses of the day over the citizens waiting in each priority
+    pub fn allocate_doses(&self, waiting_by_priority: &[usize]) -> Vec<usize> {
+        let mut doses_left = self.intervention.as_ref().map_or(0, |i| i.doses_per_day.max(0) as usize);
+        waiting_by_priority.iter().map(|waiting| {
+            let doses = doses_left.min(*waiting);
+            doses_left -= doses;
+            doses
+        }).collect()
+    }
+
+    pub fn doses_given(&mut self, doses: usize) {
+        self.doses_today = doses;
+        self.total_doses += doses;
+    }
+
+    fn priority_groups(&self) -> &[PriorityGroup] {
+        self.intervention.as_ref().map_or(&[], |i| &i.priority_groups)
+    }
+}
+
+impl InterventionType for VaccinationCampaign {
+    fn name(&self) -> String {
+        "vaccination_campaign".to_string()
+    }
+
+    fn json_data(&self) -> String {
+        format!(r#"{{"doses": {}, "total_doses": {}}}"#, self.doses_today, self.total_doses)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::thread_rng;
+
+    use crate::geography::{Area, Point};
+
+    use super::*;
+
+    fn campaign(priority_groups: Vec<PriorityGroup>) -> VaccinationCampaign {
+        let config = VaccinationCampaignConfig { start_hour: 100, doses_per_day: 10, priority_groups, vaccine: None };
+        VaccinationCampaign { intervention: Some(config), vaccine: Vaccine::perfect(), doses_today: 0, total_doses: 0 }
+    }
+
+    #[test]
+    fn should_give_due_doses_before_first_doses() {
+        let mut rng = thread_rng();
+        let area = Area::new(Point::new(0, 0), Point::new(2, 2));
+        let two_doses = Vaccine { doses: 2, days_between_doses: 21, ..Vaccine::perfect() };
+        let mut vaccinated = Citizen::new(area, area, Point::new(0, 0), false, false, WorkStatus::NA, &mut rng);
+        vaccinated.vaccinate(two_doses, 100);
+        let campaign = campaign(vec![]);
+
+        assert_eq!(campaign.priority_of(&vaccinated, 100 + 20 * 24), None);
+        assert_eq!(campaign.priority_of(&vaccinated, 100 + 21 * 24), Some(0));
+
+        vaccinated.vaccinate(two_doses, 100 + 21 * 24);
+        assert_eq!(campaign.priority_of(&vaccinated, 100 + 50 * 24), None);
+    }
+
+    #[test]
+    fn should_vaccinate_every_day_from_start_hour() {
+        let campaign = campaign(vec![]);
+
+        assert!(!campaign.should_vaccinate(&Counts::new_test(76, 80, 0, 20, 0, 0, 0)));
+        assert!(campaign.should_vaccinate(&Counts::new_test(100, 80, 0, 20, 0, 0, 0)));
+        assert!(!campaign.should_vaccinate(&Counts::new_test(120, 80, 0, 20, 0, 0, 0)));
+        assert!(campaign.should_vaccinate(&Counts::new_test(124, 80, 0, 20, 0, 0, 0)));
+    }
+
+    #[test]
+    fn should_give_doses_to_priority_groups_first() {
+        let mut rng = thread_rng();
+        let area = Area::new(Point::new(0, 0), Point::new(2, 2));
+        let essential = Citizen::new(area, area, Point::new(0, 0), false, true, WorkStatus::Essential, &mut rng);
+        let hospital_staff = Citizen::new(area, area, Point::new(0, 0), false, true,
+                                          WorkStatus::HospitalStaff { work_start_at: 0 }, &mut rng);
+        let other = Citizen::new(area, area, Point::new(0, 0), false, false, WorkStatus::NA, &mut rng);
+        let mut campaign = campaign(vec![PriorityGroup::HospitalStaff, PriorityGroup::EssentialWorkers]);
+
+        assert_eq!(campaign.number_of_priorities(), 4);
+        assert_eq!(campaign.priority_of(&hospital_staff, 100), Some(1));
+        assert_eq!(campaign.priority_of(&essential, 100), Some(2));
+        assert_eq!(campaign.priority_of(&other, 100), Some(3));
+        assert_eq!(campaign.allocate_doses(&[0, 4, 8, 20]), vec![0, 4, 6, 0]);
+        assert_eq!(campaign.allocate_doses(&[2, 0, 3, 20]), vec![2, 0, 3, 5]);
+
+        campaign.doses_given(10);
+        campaign.doses_given(7);
+        assert_eq!(campaign.json_data(), r#"{"doses": 7, "total_doses": 17}"#);
+    }
+}
@@ -0,0 +1,113 @@
+/*
+ * EpiRust
+ * Copyright (c) 2020  ThoughtWorks, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use std::any::Any;
+use std::fs::File;
+use std::path::PathBuf;
+
+use csv::Writer;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use uuid::Uuid;
+
+use crate::environment;
+use crate::geography::Point;
+use crate::listeners::listener::Listener;
+
+#[derive(Serialize)]
+struct Contact {
+    hour: i32,
+    citizen_a: Uuid,
+    citizen_b: Uuid,
+    x: i32,
+    y: i32,
+}
+
+/// Writes the daily contacts between citizens as a gzipped csv edge list, as they are reported
+pub struct ContactLogWriter {
+    writer: Option<Writer<GzEncoder<File>>>,
+}
+
+impl ContactLogWriter {
+    pub fn new(output_file_name: String) -> ContactLogWriter {
+        let mut output_path = environment::output_dir();
+        output_path.push(&output_file_name);
+        ContactLogWriter::create(&output_path)
+    }
+
+    fn create(output_path: &PathBuf) -> ContactLogWriter {
+        let writer = match File::create(output_path) {
+            Ok(file) => Some(Writer::from_writer(GzEncoder::new(file, Compression::default()))),
+            Err(e) => {
+                error!("Failed to create contact log {:?}: {}", output_path, e);
+                None
+            }
+        };
+        ContactLogWriter { writer }
+    }
+}
+
+impl Listener for ContactLogWriter {
+    fn simulation_ended(&mut self) {
+        if let Some(writer) = self.writer.take() {
+            let result = writer.into_inner()
+                .map_err(|e| e.to_string())
+                .and_then(|encoder| encoder.finish().map_err(|e| e.to_string()));
+            if let Err(e) = result {
+                error!("Failed to write contact log: {}", e);
+            }
+        }
+    }
+
+    fn citizen_contact(&mut self, hr: i32, a: Uuid, b: Uuid, location: &Point) {
+        if let Some(writer) = self.writer.as_mut() {
+            let contact = Contact { hour: hr, citizen_a: a, citizen_b: b, x: location.x, y: location.y };
+            if let Err(e) = writer.serialize(contact) {
+                error!("Error occurred while serializing! {:?}", e);
+            }
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::io::Read;
+
+    use flate2::read::GzDecoder;
+
+    use super::*;
+
+    #[test]
+    fn should_write_contacts_as_gzipped_csv() {
+        let output_path = env::temp_dir().join("test_contacts.csv.gz");
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut contact_log = ContactLogWriter::create(&output_path);
+        contact_log.citizen_contact(5, a, b, &Point::new(2, 3));
+        contact_log.simulation_ended();
+
+        let mut contents = String::new();
+        GzDecoder::new(File::open(output_path).unwrap()).read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, format!("hour,citizen_a,citizen_b,x,y\n5,{},{},2,3\n", a, b));
+    }
+}
@@ -21,10 +21,12 @@ extern crate csv;
 
 use std::error::Error;
 
+use chrono::NaiveDate;
 use csv::Writer;
 
 use std::any::Any;
-use crate::listeners::events::counts::Counts;
+use crate::calendar::Calendar;
+use crate::listeners::events::counts::{Counts, StrainCounts};
 use crate::listeners::listener::Listener;
 use crate::environment;
 use std::path::PathBuf;
@@ -33,14 +35,74 @@ use serde::Serialize;
 pub struct CsvListener {
     output_file_name: String,
     counts: Vec<Counts>,
+    calendar: Option<Calendar>,
+    /// The names of the compartments of the compartment model, whose counts replace those of the built-in states
+    compartments: Option<Vec<String>>,
+    /// The file for the counts of each strain, and their names
+    strains: Option<(String, Vec<String>)>,
 }
 
 impl CsvListener {
-    pub fn new(output_file_name: String) -> CsvListener {
-        CsvListener { output_file_name, counts: Vec::new() }
+    pub fn new(output_file_name: String, calendar: Option<Calendar>) -> CsvListener {
+        CsvListener { output_file_name, counts: Vec::new(), calendar, compartments: None, strains: None }
+    }
+
+    pub fn with_compartments(self, names: Vec<String>) -> CsvListener {
+        CsvListener { compartments: Some(names), ..self }
+    }
+
+    pub fn with_strains(self, file_name: String, names: Vec<String>) -> CsvListener {
+        CsvListener { strains: Some((file_name, names)), ..self }
+    }
+
+    /// A column for each compartment, followed by the counts that don't depend on the states
+    fn write_compartments(&self, output_path: &PathBuf, names: &[String]) -> Result<(), Box<dyn Error>> {
+        let mut wtr = Writer::from_path(output_path)?;
+        let mut header: Vec<&str> = vec!["hour"];
+        if self.calendar.is_some() {
+            header.insert(0, "date");
+        }
+        header.extend(names.iter().map(String::as_str));
+        header.extend(Counts::default().other_counts().iter().map(|(name, _)| *name));
+        wtr.write_record(&header)?;
+        for counts in self.counts.iter() {
+            let mut record = vec![counts.get_hour().to_string()];
+            if let Some(calendar) = &self.calendar {
+                record.insert(0, calendar.date_at(counts.get_hour()).to_string());
+            }
+            record.extend((0..names.len()).map(|i| counts.get_compartments().get(i).unwrap_or(&0).to_string()));
+            record.extend(counts.other_counts().iter().map(|(_, count)| count.to_string()));
+            wtr.write_record(&record)?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+
+    /// One row for each strain at each hour
+    fn write_strains(&self, file_name: &str, names: &[String]) -> Result<(), Box<dyn Error>> {
+        let mut output_path = environment::output_dir();
+        output_path.push(file_name);
+        let rows: Vec<(StrainHour, StrainCounts)> = self.counts.iter()
+            .flat_map(|counts| names.iter().enumerate().map(move |(i, name)| {
+                let strain_counts = counts.get_strains().get(i).copied().unwrap_or_default();
+                (StrainHour { hour: counts.get_hour(), strain: name }, strain_counts)
+            }))
+            .collect();
+        write(&output_path, &rows)
     }
 }
 
+#[derive(Serialize)]
+struct StrainHour<'a> {
+    hour: i32,
+    strain: &'a str,
+}
+
+#[derive(Serialize)]
+struct Date {
+    date: NaiveDate,
+}
+
 pub fn write(file_path: &PathBuf, data: &Vec<impl Serialize>) -> Result<(), Box<dyn Error>> {
     let mut wtr = Writer::from_path(file_path)?;
 
@@ -67,11 +129,48 @@ impl Listener for CsvListener {
         let mut output_path = environment::output_dir();
         output_path.push(&self.output_file_name);
 
-        write(&output_path, &self.counts)
-            .expect("Failed to write to file");
+        let result = match (&self.compartments, &self.calendar) {
+            (Some(names), _) => self.write_compartments(&output_path, names),
+            (None, Some(calendar)) => {
+                let dated_counts: Vec<(Date, Counts)> = self.counts.iter()
+                    .map(|counts| (Date { date: calendar.date_at(counts.get_hour()) }, counts.clone()))
+                    .collect();
+                write(&output_path, &dated_counts)
+            }
+            (None, None) => write(&output_path, &self.counts)
+        };
+        result.expect("Failed to write to file");
+        if let Some((file_name, names)) = &self.strains {
+            self.write_strains(file_name, names).expect("Failed to write strain counts");
+        }
     }
 
     fn as_any(&self) -> &dyn Any {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_write_a_column_for_each_compartment_instead_of_the_states() {
+        let output_path = std::env::temp_dir().join("epirust_test_compartment_counts.csv");
+        let mut listener = CsvListener::new(output_path.to_str().unwrap().to_string(), None)
+            .with_compartments(vec!["S".to_string(), "I".to_string(), "R".to_string()]);
+        let mut counts = Counts::new(0, 0, 0);
+        counts.update_compartment(0, 9);
+        counts.update_compartment(1, 1);
+        counts.update_reinfections(2);
+        listener.counts_updated(counts);
+        listener.simulation_ended();
+
+        let output = std::fs::read_to_string(&output_path).unwrap();
+        std::fs::remove_file(&output_path).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], "hour,S,I,R,reinfections,tested,tested_positive,quarantined,general_beds_occupied,\
+                              icu_beds_occupied,unmet_bed_demand,critical");
+        assert_eq!(lines[1], "0,9,1,0,2,0,0,0,0,0,0,0");
+    }
+}
@@ -18,34 +18,156 @@
  */
 
 use std::any::Any;
+use std::path::PathBuf;
+
+use fnv::FnvHashMap;
+use plotters::prelude::*;
+
+use crate::constants;
+use crate::environment;
+use crate::geography::{Area, building_containing, Grid, Point};
 use crate::listeners::listener::Listener;
-use crate::geography::Point;
 use crate::listeners::events::counts::Counts;
-use fnv::FnvHashMap;
 
+#[derive(Serialize, Debug, PartialEq)]
+struct HotspotAtArea {
+    from_hour: i32,
+    to_hour: i32,
+    area: &'static str,
+    x_start: i32,
+    y_start: i32,
+    x_end: i32,
+    y_end: i32,
+    infections: i32,
+}
+
+/// Counts the infections in each cell, over windows of `window_hours` or the whole simulation. When the simulation
+/// ends, they are added up for each house, office and classroom, and written as a csv and a heatmap of each window.
+/// Infections outside of those buildings are counted by cell.
 pub struct Hotspot {
-    disease_hotspot_tracker: FnvHashMap<Point, i32>
+    disease_hotspot_tracker: FnvHashMap<(i32, Point), i32>,
+    window_hours: Option<i32>,
+    last_hour: i32,
+    output_file_format: String,
+    grid_size: i32,
+    buildings: Vec<(&'static str, Area, i32)>,
 }
 
 impl Hotspot {
-    pub fn new() -> Hotspot {
+    pub fn new(output_file_format: String, window_hours: Option<i32>) -> Hotspot {
         let disease_hotspot_tracker = FnvHashMap::default();
-        Hotspot{disease_hotspot_tracker}
+        Hotspot {
+            disease_hotspot_tracker,
+            window_hours,
+            last_hour: 0,
+            output_file_format,
+            grid_size: 0,
+            buildings: Vec::new(),
+        }
+    }
+
+    fn area_of(&self, cell: &Point) -> (&'static str, Area) {
+        self.buildings.iter()
+            .find_map(|(name, zone, size)| building_containing(zone, *size, cell).map(|building| (*name, building)))
+            .unwrap_or(("cell", Area::new(*cell, *cell)))
+    }
+
+    fn window_of(&self, hour: i32) -> i32 {
+        self.window_hours.map_or(0, |window_hours| hour / window_hours)
+    }
+
+    fn hours_of(&self, window: i32) -> (i32, i32) {
+        match self.window_hours {
+            Some(window_hours) => (window * window_hours, (window + 1) * window_hours),
+            None => (0, self.last_hour + 1)
+        }
+    }
+
+    fn hotspots(&self) -> Vec<HotspotAtArea> {
+        let mut by_area: FnvHashMap<(i32, &'static str, Area), i32> = FnvHashMap::default();
+        for ((window, cell), infections) in self.disease_hotspot_tracker.iter() {
+            let (name, area) = self.area_of(cell);
+            *by_area.entry((*window, name, area)).or_insert(0) += infections;
+        }
+        let mut hotspots: Vec<HotspotAtArea> = by_area.into_iter().map(|((window, name, area), infections)| {
+            let (from_hour, to_hour) = self.hours_of(window);
+            HotspotAtArea {
+                from_hour,
+                to_hour,
+                area: name,
+                x_start: area.start_offset.x,
+                y_start: area.start_offset.y,
+                x_end: area.end_offset.x,
+                y_end: area.end_offset.y,
+                infections,
+            }
+        }).collect();
+        hotspots.sort_by_key(|h| (h.from_hour, -h.infections, h.x_start, h.y_start));
+        hotspots
+    }
+
+    /// Yellow for the fewest infections in the window, to red for the most
+    fn draw(&self, output_path: &PathBuf, hotspots: &[&HotspotAtArea]) {
+        let mut draw_backend = BitMapBackend::new(output_path, (self.grid_size as u32, self.grid_size as u32));
+        let most_infections = hotspots.iter().map(|h| h.infections).max().unwrap_or(1);
+        let mut draw = || -> Result<(), String> {
+            draw_backend.draw_rect((0, 0), (self.grid_size, self.grid_size), &plotters::style::WHITE, true)
+                .map_err(|e| format!("{:?}", e))?;
+            for hotspot in hotspots {
+                let heat = hotspot.infections as f64 / most_infections as f64;
+                let colour = RGBColor(255, (255.0 * (1.0 - heat)) as u8, 0);
+                draw_backend.draw_rect((hotspot.x_start, hotspot.y_start), (hotspot.x_end, hotspot.y_end), &colour, true)
+                    .map_err(|e| format!("{:?}", e))?;
+            }
+            draw_backend.present().map_err(|e| format!("{:?}", e))
+        };
+        if let Err(e) = draw() {
+            error!("Failed to draw hotspots: {}", e);
+        }
     }
 }
 
 impl Listener for Hotspot {
-    fn counts_updated(&mut self, _counts: Counts) {
+    fn counts_updated(&mut self, counts: Counts) {
+        self.last_hour = counts.get_hour();
     }
 
     fn simulation_ended(&mut self) {
+        let hotspots = self.hotspots();
+        let mut output_path = environment::output_dir();
+        output_path.push(format!("{}_hotspots.csv", self.output_file_format));
+        match crate::listeners::csv_service::write(&output_path, &hotspots) {
+            Ok(_) => {}
+            Err(e) => { error!("Failed to serialize hotspots: {}", e) }
+        }
+
+        let mut windows: Vec<i32> = hotspots.iter().map(|h| h.from_hour).collect();
+        windows.dedup();
+        for from_hour in windows {
+            let file_name = match self.window_hours {
+                Some(_) => format!("{}_hotspots_{}.png", self.output_file_format, from_hour),
+                None => format!("{}_hotspots.png", self.output_file_format)
+            };
+            let mut output_path = environment::output_dir();
+            output_path.push(file_name);
+            let in_window: Vec<&HotspotAtArea> = hotspots.iter().filter(|h| h.from_hour == from_hour).collect();
+            self.draw(&output_path, &in_window);
+        }
     }
 
-    fn citizen_got_infected(&mut self, cell: &Point) {
-        let counter = self.disease_hotspot_tracker.entry(*cell).or_insert(0);
+    fn citizen_got_infected(&mut self, hr: i32, cell: &Point) {
+        let window = self.window_of(hr);
+        let counter = self.disease_hotspot_tracker.entry((window, *cell)).or_insert(0);
         *counter += 1;
     }
 
+    fn grid_updated(&mut self, grid: &Grid) {
+        self.grid_size = grid.grid_size;
+        self.buildings = vec![("house", grid.housing_area, constants::HOME_SIZE),
+                              ("office", grid.work_area, constants::OFFICE_SIZE),
+                              ("classroom", grid.school_area, constants::CLASSROOM_SIZE)];
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -54,33 +176,56 @@ impl Listener for Hotspot {
 #[cfg(test)]
 mod tests{
     use crate::listeners::disease_tracker::Hotspot;
-    use crate::geography::Point;
+    use crate::geography::{define_geography, Point};
     use crate::listeners::listener::Listener;
 
     #[test]
     fn should_initialize(){
-        let tracker = Hotspot::new();
+        let tracker = Hotspot::new("test".to_string(), None);
         assert_eq!(tracker.disease_hotspot_tracker.len(), 0);
     }
 
     #[test]
     fn should_add_new_entry(){
-        let mut tracker = Hotspot::new();
+        let mut tracker = Hotspot::new("test".to_string(), None);
         let current_point = Point::new(0, 1);
 
-        tracker.citizen_got_infected(&current_point);
+        tracker.citizen_got_infected(10, &current_point);
 
-        assert_eq!(*tracker.disease_hotspot_tracker.get(&current_point).unwrap(), 1);
+        assert_eq!(*tracker.disease_hotspot_tracker.get(&(0, current_point)).unwrap(), 1);
     }
 
     #[test]
     fn should_update_tracker(){
-        let mut tracker = Hotspot::new();
+        let mut tracker = Hotspot::new("test".to_string(), None);
         let current_point = Point::new(0, 1);
 
-        tracker.citizen_got_infected(&current_point);
-        tracker.citizen_got_infected(&current_point);
+        tracker.citizen_got_infected(10, &current_point);
+        tracker.citizen_got_infected(500, &current_point);
+
+        assert_eq!(*tracker.disease_hotspot_tracker.get(&(0, current_point)).unwrap(), 2);
+    }
+
+    #[test]
+    fn should_add_up_infections_of_each_building_in_each_window(){
+        let grid = define_geography(100);
+        let mut tracker = Hotspot::new("test".to_string(), Some(24 * 7));
+        tracker.grid_updated(&grid);
+        let transport_cell = grid.transport_area.start_offset;
+
+        tracker.citizen_got_infected(10, &Point::new(0, 0));
+        tracker.citizen_got_infected(20, &Point::new(1, 1));
+        tracker.citizen_got_infected(30, &Point::new(2, 2));
+        tracker.citizen_got_infected(40, &transport_cell);
+        tracker.citizen_got_infected(24 * 7, &Point::new(1, 0));
 
-        assert_eq!(*tracker.disease_hotspot_tracker.get(&current_point).unwrap(), 2);
+        let hotspots = tracker.hotspots();
+        let summary: Vec<(i32, i32, &str, i32, i32, i32)> = hotspots.iter()
+            .map(|h| (h.from_hour, h.to_hour, h.area, h.x_start, h.y_end, h.infections))
+            .collect();
+        assert_eq!(summary, vec![(0, 168, "house", 0, 1, 2),
+                                 (0, 168, "house", 2, 3, 1),
+                                 (0, 168, "cell", transport_cell.x, transport_cell.y, 1),
+                                 (168, 336, "house", 0, 1, 1)]);
     }
 }
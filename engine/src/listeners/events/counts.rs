@@ -17,7 +17,21 @@
  *
  */
 
-#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+use crate::disease_state_machine::State;
+use crate::hospital_beds::Bed;
+
+/// The citizens in each state who were last infected with a strain
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Default)]
+pub struct StrainCounts {
+    exposed: i32,
+    infected: i32,
+    hospitalized: i32,
+    recovered: i32,
+    deceased: i32,
+}
+
+/// The `vaccinated_` counts are the vaccinated citizens among each of the compartments
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
 pub struct Counts {
     hour: i32,
     susceptible: i32,
@@ -26,16 +40,55 @@ pub struct Counts {
     hospitalized: i32,
     recovered: i32,
     deceased: i32,
+    #[serde(default)]
+    vaccinated_susceptible: i32,
+    #[serde(default)]
+    vaccinated_exposed: i32,
+    #[serde(default)]
+    vaccinated_infected: i32,
+    #[serde(default)]
+    vaccinated_hospitalized: i32,
+    #[serde(default)]
+    vaccinated_recovered: i32,
+    #[serde(default)]
+    vaccinated_deceased: i32,
+    /// Infections after the first one, so far
+    #[serde(default)]
+    reinfections: i32,
+    /// Tests performed so far
+    #[serde(default)]
+    tested: i32,
+    /// Positive tests so far
+    #[serde(default)]
+    tested_positive: i32,
+    #[serde(default)]
+    quarantined: i32,
+    #[serde(default)]
+    general_beds_occupied: i32,
+    #[serde(default)]
+    icu_beds_occupied: i32,
+    /// Citizens needing a hospital bed who did not get one
+    #[serde(default)]
+    unmet_bed_demand: i32,
+    /// Critical cases, in intensive care or not, among the infected and hospitalized
+    #[serde(default)]
+    critical: i32,
+    /// Citizens in each compartment of the compartment model, if there is one. Written in place of the states
+    #[serde(skip)]
+    compartments: Vec<i32>,
+    /// By strain, the wild type first. Written to a separate csv
+    #[serde(skip)]
+    strains: Vec<StrainCounts>,
 }
 
 impl Counts {
     #[cfg(test)]
     pub fn new_test(hour: i32, susceptible: i32, exposed: i32, infected: i32, hospitalized: i32, recovered: i32, deceased: i32) -> Counts {
-        Counts { hour, susceptible, exposed, infected, hospitalized, recovered, deceased }
+        Counts { hour, susceptible, exposed, infected, hospitalized, recovered, deceased, ..Counts::default() }
     }
 
     pub fn new(susceptible: i32, exposed: i32, infected: i32) -> Counts {
-        Counts { hour: 0, susceptible, exposed, infected, hospitalized: 0, recovered: 0, deceased: 0 }
+        Counts { susceptible, exposed, infected, ..Counts::default() }
     }
 
     pub fn get_susceptible(&self) -> i32 {
@@ -90,6 +143,84 @@ impl Counts {
         self.hospitalized += count;
     }
 
+    pub fn update_vaccinated(&mut self, state: &State, hospitalized: bool, count: i32) {
+        match state {
+            State::Susceptible { .. } => self.vaccinated_susceptible += count,
+            State::Exposed { .. } => self.vaccinated_exposed += count,
+            State::Infected { .. } if hospitalized => self.vaccinated_hospitalized += count,
+            State::Infected { .. } => self.vaccinated_infected += count,
+            State::Recovered { .. } => self.vaccinated_recovered += count,
+            State::Deceased { .. } => self.vaccinated_deceased += count,
+        }
+    }
+
+    pub fn update_reinfections(&mut self, count: i32) {
+        self.reinfections += count;
+    }
+
+    pub fn update_tested(&mut self, tests: i32, positives: i32) {
+        self.tested += tests;
+        self.tested_positive += positives;
+    }
+
+    pub fn update_quarantined(&mut self, count: i32) {
+        self.quarantined += count;
+    }
+
+    pub fn update_critical(&mut self, count: i32) {
+        self.critical += count;
+    }
+
+    pub fn update_compartment(&mut self, compartment: usize, count: i32) {
+        if self.compartments.len() <= compartment {
+            self.compartments.resize(compartment + 1, 0);
+        }
+        self.compartments[compartment] += count;
+    }
+
+    pub fn get_compartments(&self) -> &[i32] {
+        &self.compartments
+    }
+
+    /// The counts that don't follow the states of the built-in disease course, by column name
+    pub fn other_counts(&self) -> [(&'static str, i32); 8] {
+        [("reinfections", self.reinfections), ("tested", self.tested), ("tested_positive", self.tested_positive),
+            ("quarantined", self.quarantined), ("general_beds_occupied", self.general_beds_occupied),
+            ("icu_beds_occupied", self.icu_beds_occupied), ("unmet_bed_demand", self.unmet_bed_demand),
+            ("critical", self.critical)]
+    }
+
+    pub fn update_strain(&mut self, strain: usize, state: &State, hospitalized: bool, count: i32) {
+        if self.strains.len() <= strain {
+            self.strains.resize(strain + 1, StrainCounts::default());
+        }
+        let counts = &mut self.strains[strain];
+        match state {
+            State::Susceptible { .. } => {}
+            State::Exposed { .. } => counts.exposed += count,
+            State::Infected { .. } if hospitalized => counts.hospitalized += count,
+            State::Infected { .. } => counts.infected += count,
+            State::Recovered { .. } => counts.recovered += count,
+            State::Deceased { .. } => counts.deceased += count,
+        }
+    }
+
+    pub fn get_strains(&self) -> &[StrainCounts] {
+        &self.strains
+    }
+
+    /// Adds a citizen in `bed`, or needing one when `turned_away`
+    pub fn update_beds(&mut self, bed: Option<Bed>, turned_away: bool) {
+        match bed {
+            Some(Bed::General) => self.general_beds_occupied += 1,
+            Some(Bed::Icu) => self.icu_beds_occupied += 1,
+            None => {}
+        }
+        if turned_away {
+            self.unmet_bed_demand += 1;
+        }
+    }
+
     pub fn increment_hour(&mut self) {
         self.hour += 1;
     }
@@ -101,6 +232,20 @@ impl Counts {
         self.hospitalized = 0;
         self.recovered = 0;
         self.deceased = 0;
+        self.vaccinated_susceptible = 0;
+        self.vaccinated_exposed = 0;
+        self.vaccinated_infected = 0;
+        self.vaccinated_hospitalized = 0;
+        self.vaccinated_recovered = 0;
+        self.vaccinated_deceased = 0;
+        self.reinfections = 0;
+        self.quarantined = 0;
+        self.general_beds_occupied = 0;
+        self.icu_beds_occupied = 0;
+        self.unmet_bed_demand = 0;
+        self.critical = 0;
+        self.compartments.iter_mut().for_each(|count| *count = 0);
+        self.strains.iter_mut().for_each(|counts| *counts = StrainCounts::default());
     }
 
     pub fn total(&self) -> i32 {
@@ -122,7 +267,9 @@ impl Counts {
 
 #[cfg(test)]
 mod tests {
-    use crate::listeners::events::counts::Counts;
+    use crate::disease_state_machine::{InfectionSeverity, State};
+    use crate::listeners::events::counts::{Counts, StrainCounts};
+    use crate::hospital_beds::Bed;
 
     #[test]
     fn should_create_counts() {
@@ -226,4 +373,69 @@ mod tests {
         assert_eq!(counts.deceased, 0);
         assert_eq!(counts.hour, 1);
     }
+
+    #[test]
+    fn should_update_vaccinated_in_state() {
+        let mut counts = Counts::new(100, 1, 0);
+        counts.update_vaccinated(&State::Susceptible {}, false, 2);
+        counts.update_vaccinated(&State::Infected { symptoms: false, severity: InfectionSeverity::Mild }, true, 1);
+        counts.update_vaccinated(&State::Susceptible {}, false, -1);
+        assert_eq!(counts.susceptible, 100);
+        assert_eq!(counts.vaccinated_susceptible, 1);
+        assert_eq!(counts.vaccinated_hospitalized, 1);
+        assert_eq!(counts.vaccinated_infected, 0);
+
+        counts.clear();
+        assert_eq!(counts, Counts::default());
+    }
+
+    #[test]
+    fn should_keep_tests_so_far_when_cleared() {
+        let mut counts = Counts::new(100, 1, 0);
+        counts.update_tested(10, 4);
+        counts.update_tested(5, 1);
+        counts.update_quarantined(3);
+        counts.clear();
+        assert_eq!(counts.tested, 15);
+        assert_eq!(counts.tested_positive, 5);
+        assert_eq!(counts.quarantined, 0);
+    }
+
+    #[test]
+    fn should_count_occupied_beds_and_unmet_demand() {
+        let mut counts = Counts::new(100, 1, 0);
+        counts.update_beds(Some(Bed::General), false);
+        counts.update_beds(Some(Bed::Icu), false);
+        counts.update_beds(None, true);
+        counts.update_beds(None, false);
+        assert_eq!(counts.general_beds_occupied, 1);
+        assert_eq!(counts.icu_beds_occupied, 1);
+        assert_eq!(counts.unmet_bed_demand, 1);
+
+        counts.clear();
+        assert_eq!(counts.unmet_bed_demand, 0);
+    }
+
+    #[test]
+    fn should_count_citizens_by_compartment() {
+        let mut counts = Counts::new(100, 1, 0);
+        counts.update_compartment(2, 3);
+        counts.update_compartment(0, 1);
+        assert_eq!(counts.get_compartments(), &[1, 0, 3]);
+
+        counts.clear();
+        assert_eq!(counts.get_compartments(), &[0, 0, 0]);
+    }
+
+    #[test]
+    fn should_count_citizens_by_strain() {
+        let mut counts = Counts::new(100, 1, 0);
+        counts.update_strain(1, &State::Infected { symptoms: true, severity: InfectionSeverity::Mild }, false, 1);
+        counts.update_strain(1, &State::Infected { symptoms: true, severity: InfectionSeverity::Mild }, true, 1);
+        counts.update_strain(0, &State::Susceptible {}, false, 1);
+        counts.update_strain(0, &State::Recovered {}, false, 2);
+
+        assert_eq!(counts.get_strains()[0], StrainCounts { recovered: 2, ..StrainCounts::default() });
+        assert_eq!(counts.get_strains()[1], StrainCounts { infected: 1, hospitalized: 1, ..StrainCounts::default() });
+    }
 }
@@ -0,0 +1,32 @@
+/*
+ * EpiRust
+ * Copyright (c) 2020  ThoughtWorks, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use uuid::Uuid;
+
+use crate::geography::{Point, Setting};
+
+/// A susceptible citizen exposed to the disease by an infectious neighbour
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Exposure {
+    pub hour: i32,
+    pub infector: Uuid,
+    pub infectee: Uuid,
+    pub location: Point,
+    pub setting: Setting,
+}
@@ -18,4 +18,5 @@
  */
 
 pub mod counts;
-pub mod citizen_state;
\ No newline at end of file
+pub mod citizen_state;
+pub mod exposure;
\ No newline at end of file
@@ -38,10 +38,15 @@ pub struct EventsKafkaProducer {
     enable_citizen_state_messages: bool,
     count_updated_topic: String,
     citizen_states_topic: String,
+    /// Counts are published by compartment with a compartment model
+    compartment_names: Vec<String>,
+    /// Counts are published by strain when there are variants
+    strain_names: Vec<String>,
 }
 
 impl EventsKafkaProducer {
-    pub fn new(sim_id: String, population_size: usize, enable_citizen_state_messages: bool) -> EventsKafkaProducer {
+    pub fn new(sim_id: String, population_size: usize, enable_citizen_state_messages: bool,
+               compartment_names: Vec<String>, strain_names: Vec<String>) -> EventsKafkaProducer {
         let count_updated_topic = "counts_updated".to_string();
         let citizen_states_topic = "citizen_states_updated".to_string();
         let kafka_url = environment::kafka_url();
@@ -55,6 +60,8 @@ impl EventsKafkaProducer {
             enable_citizen_state_messages,
             count_updated_topic,
             citizen_states_topic,
+            compartment_names,
+            strain_names,
         }
     }
 
@@ -71,7 +78,23 @@ impl EventsKafkaProducer {
 
 impl Listener for EventsKafkaProducer {
     fn counts_updated(&mut self, counts: Counts) {
-        let message = serde_json::to_string(&counts).expect("Failed to serialize counts");
+        let mut message = serde_json::to_value(&counts).expect("Failed to serialize counts");
+        if !self.compartment_names.is_empty() {
+            let compartments: serde_json::Map<String, serde_json::Value> = self.compartment_names.iter().enumerate()
+                .map(|(i, name)| (name.clone(), serde_json::Value::from(counts.get_compartments().get(i).copied().unwrap_or(0))))
+                .collect();
+            message["compartments"] = serde_json::Value::Object(compartments);
+        }
+        if !self.strain_names.is_empty() {
+            let strains: serde_json::Map<String, serde_json::Value> = self.strain_names.iter().enumerate()
+                .map(|(i, name)| {
+                    let strain_counts = counts.get_strains().get(i).copied().unwrap_or_default();
+                    (name.clone(), serde_json::to_value(strain_counts).expect("Failed to serialize strain counts"))
+                })
+                .collect();
+            message["strains"] = serde_json::Value::Object(strains);
+        }
+        let message = message.to_string();
         let record: FutureRecord<String, String> = FutureRecord::to(&self.count_updated_topic)
             .key(&self.sim_id)
             .payload(&message);
@@ -105,7 +128,7 @@ impl Listener for EventsKafkaProducer {
         self.citizen_states_buffer.update(citizen, location);
     }
 
-    fn grid_updated(&self, grid: &Grid) {
+    fn grid_updated(&mut self, grid: &Grid) {
         if self.enable_citizen_state_messages {
             let message = serde_json::to_string(grid);
             match message {
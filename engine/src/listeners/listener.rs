@@ -19,18 +19,24 @@
 
 use std::any::Any;
 
+use uuid::Uuid;
+
 use crate::agent::Citizen;
 use crate::geography::{Point, Grid};
 use crate::listeners::events::counts::Counts;
+use crate::listeners::events::exposure::Exposure;
 use crate::interventions::intervention_type::InterventionType;
 use crate::travel_plan::TravellersByRegion;
 
 pub trait Listener {
     fn counts_updated(&mut self, _counts: Counts) {}
     fn simulation_ended(&mut self) {}
-    fn citizen_got_infected(&mut self, _cell: &Point) {}
+    fn citizen_got_infected(&mut self, _hr: i32, _cell: &Point) {}
     fn citizen_state_updated(&mut self, _hr: i32, _citizen: &Citizen, _location: &Point) {}
-    fn grid_updated(&self, _grid: &Grid) {}
+    fn citizen_contact(&mut self, _hr: i32, _a: Uuid, _b: Uuid, _location: &Point) {}
+    fn citizen_exposed(&mut self, _exposure: &Exposure) {}
+    fn infection_ended(&mut self, _hr: i32, _citizen: Uuid) {}
+    fn grid_updated(&mut self, _grid: &Grid) {}
     fn intervention_applied(&mut self, _at_hour: i32, _intervention: &dyn InterventionType) {}
     fn outgoing_travellers_added(&mut self, _hr: i32, _travellers: &Vec<TravellersByRegion>) {}
     fn as_any(&self) -> &dyn Any;
@@ -48,15 +54,15 @@ impl Listeners {
 
 impl Listeners {
     pub fn counts_updated(&mut self, counts: Counts) {
-        self.listeners.iter_mut().for_each(|listener| { listener.counts_updated(counts) });
+        self.listeners.iter_mut().for_each(|listener| { listener.counts_updated(counts.clone()) });
     }
 
     pub fn simulation_ended(&mut self) {
         self.listeners.iter_mut().for_each(|listener| { listener.simulation_ended() });
     }
 
-    pub fn citizen_got_infected(&mut self, cell: &Point) {
-        self.listeners.iter_mut().for_each(|listener| { listener.citizen_got_infected(cell) });
+    pub fn citizen_got_infected(&mut self, hr: i32, cell: &Point) {
+        self.listeners.iter_mut().for_each(|listener| { listener.citizen_got_infected(hr, cell) });
     }
 
     pub fn citizen_state_updated(&mut self, hr: i32, citizen: &Citizen, location: &Point) {
@@ -65,8 +71,20 @@ impl Listeners {
         })
     }
 
-    pub fn grid_updated(&self, grid: &Grid) {
-        self.listeners.iter().for_each(|l| { l.grid_updated(grid) })
+    pub fn citizen_contact(&mut self, hr: i32, a: Uuid, b: Uuid, location: &Point) {
+        self.listeners.iter_mut().for_each(|listener| listener.citizen_contact(hr, a, b, location));
+    }
+
+    pub fn citizen_exposed(&mut self, exposure: &Exposure) {
+        self.listeners.iter_mut().for_each(|listener| listener.citizen_exposed(exposure));
+    }
+
+    pub fn infection_ended(&mut self, hr: i32, citizen: Uuid) {
+        self.listeners.iter_mut().for_each(|listener| listener.infection_ended(hr, citizen));
+    }
+
+    pub fn grid_updated(&mut self, grid: &Grid) {
+        self.listeners.iter_mut().for_each(|l| { l.grid_updated(grid) })
     }
 
     pub fn intervention_applied(&mut self,
@@ -114,7 +132,7 @@ mod tests {
             self.calls_simulation_ended += 1;
         }
 
-        fn citizen_got_infected(&mut self, _cell: &Point) {
+        fn citizen_got_infected(&mut self, _hr: i32, _cell: &Point) {
             self.calls_citizen_got_infected += 1;
         }
 
@@ -133,7 +151,7 @@ mod tests {
 
 
         listeners.counts_updated(Counts::new(10, 1, 0));
-        listeners.citizen_got_infected(&Point::new(1, 1));
+        listeners.citizen_got_infected(1, &Point::new(1, 1));
         listeners.simulation_ended();
 
         for i in 0..=1 {
@@ -19,8 +19,11 @@
 
 pub mod events_kafka_producer;
 pub mod csv_service;
+pub mod contact_log;
 pub mod disease_tracker;
 pub mod listener;
 pub mod events;
 pub mod travel_counter;
+pub mod transmission_tree;
 pub mod intervention_reporter;
+pub mod reproduction_number;
@@ -0,0 +1,245 @@
+/*
+ * EpiRust
+ * Copyright (c) 2020  ThoughtWorks, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use std::any::Any;
+
+use fnv::FnvHashMap;
+use rdkafka::ClientConfig;
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use uuid::Uuid;
+
+use crate::constants;
+use crate::environment;
+use crate::listeners::events::counts::Counts;
+use crate::listeners::events::exposure::Exposure;
+use crate::listeners::listener::Listener;
+
+const DOUBLING_TIME_WINDOW_DAYS: usize = 7;
+const RT_UPDATED_TOPIC: &str = "rt_updated";
+
+#[derive(Serialize, Debug, PartialEq)]
+struct ReproductionNumberAtDay {
+    day: i32,
+    exposures: i32,
+    rt: Option<f64>,
+    doubling_time_days: Option<f64>,
+}
+
+struct Cohort {
+    exposed: i32,
+    secondary_exposures: i32,
+    /// Exposed citizens whose infection has not ended yet
+    active: i32,
+}
+
+/// Estimates the reproduction number of each day as the mean number of citizens exposed by the ones who were exposed
+/// that day, so the last days are underestimated until their cohorts have stopped being infectious. Starting
+/// infections, and citizens exposed in another engine, are not in any cohort, and citizens who leave for another engine
+/// stop counting for theirs.
+pub struct ReproductionNumber {
+    output_file_name: String,
+    cohort_of: FnvHashMap<Uuid, usize>,
+    cohorts: Vec<Cohort>,
+    /// The days whose estimates have been published
+    published: usize,
+    kafka: Option<(String, FutureProducer)>,
+}
+
+impl ReproductionNumber {
+    pub fn new(output_file_name: String) -> ReproductionNumber {
+        ReproductionNumber {
+            output_file_name,
+            cohort_of: FnvHashMap::default(),
+            cohorts: Vec::new(),
+            published: 0,
+            kafka: None,
+        }
+    }
+
+    /// Also publishes the estimate of each day on the `rt_updated` topic, once the infections of its cohort have all
+    /// ended, and the ones left when the simulation ends
+    pub fn publishing_to_kafka(output_file_name: String, sim_id: String) -> ReproductionNumber {
+        let producer = ClientConfig::new()
+            .set("bootstrap.servers", environment::kafka_url().as_str())
+            .create()
+            .expect("Could not create Kafka Producer");
+        ReproductionNumber { kafka: Some((sim_id, producer)), ..ReproductionNumber::new(output_file_name) }
+    }
+
+    fn estimates(&self) -> Vec<ReproductionNumberAtDay> {
+        let mut exposed_so_far = Vec::with_capacity(self.cohorts.len());
+        self.cohorts.iter().enumerate().map(|(day, cohort)| {
+            let total = exposed_so_far.last().copied().unwrap_or(0) + cohort.exposed;
+            exposed_so_far.push(total);
+            let rt = if cohort.exposed > 0 {
+                Some(cohort.secondary_exposures as f64 / cohort.exposed as f64)
+            } else {
+                None
+            };
+            let doubling_time_days = match day.checked_sub(DOUBLING_TIME_WINDOW_DAYS) {
+                Some(start) if exposed_so_far[start] > 0 && total > exposed_so_far[start] => {
+                    let growth = (total as f64 / exposed_so_far[start] as f64).ln();
+                    Some(DOUBLING_TIME_WINDOW_DAYS as f64 * 2_f64.ln() / growth)
+                }
+                _ => None
+            };
+            ReproductionNumberAtDay { day: day as i32, exposures: cohort.exposed, rt, doubling_time_days }
+        }).collect()
+    }
+
+    /// The estimates not published yet of the days up to the one ending at `hour`, in order, as long as their cohorts
+    /// are no longer infectious
+    fn completed(&mut self, hour: i32) -> Vec<ReproductionNumberAtDay> {
+        let days_over = ((hour + 1) / constants::HOURS_IN_A_DAY) as usize;
+        let completed = self.cohorts.iter().take(days_over).skip(self.published)
+            .take_while(|cohort| cohort.active == 0)
+            .count();
+        if completed == 0 {
+            return vec![];
+        }
+        let estimates = self.estimates().drain(self.published..self.published + completed).collect();
+        self.published += completed;
+        estimates
+    }
+
+    fn publish(&self, messages: impl Iterator<Item=String>) {
+        if let Some((sim_id, producer)) = &self.kafka {
+            for message in messages {
+                let record: FutureRecord<String, String> = FutureRecord::to(RT_UPDATED_TOPIC)
+                    .key(sim_id)
+                    .payload(&message);
+                producer.send(record, 0);
+            }
+        }
+    }
+
+    fn publish_estimates(&self, estimates: &[ReproductionNumberAtDay]) {
+        self.publish(estimates.iter()
+            .map(|estimate| serde_json::to_string(estimate).expect("Failed to serialize reproduction number")));
+    }
+}
+
+impl Listener for ReproductionNumber {
+    fn counts_updated(&mut self, counts: Counts) {
+        if self.kafka.is_some() && (counts.get_hour() + 1) % constants::HOURS_IN_A_DAY == 0 {
+            let completed = self.completed(counts.get_hour());
+            self.publish_estimates(&completed);
+        }
+    }
+
+    fn simulation_ended(&mut self) {
+        let estimates = self.estimates();
+        let mut output_path = environment::output_dir();
+        output_path.push(&self.output_file_name);
+        match crate::listeners::csv_service::write(&output_path, &estimates) {
+            Ok(_) => {}
+            Err(e) => { error!("Failed to serialize reproduction number: {}", e) }
+        }
+        self.publish_estimates(&estimates[self.published.min(estimates.len())..]);
+        self.publish(std::iter::once(r#"{"simulation_ended": true}"#.to_string()));
+    }
+
+    fn citizen_exposed(&mut self, exposure: &Exposure) {
+        if let Some(cohort) = self.cohort_of.get(&exposure.infector) {
+            self.cohorts[*cohort].secondary_exposures += 1;
+        }
+        let day = (exposure.hour / constants::HOURS_IN_A_DAY) as usize;
+        while self.cohorts.len() <= day {
+            self.cohorts.push(Cohort { exposed: 0, secondary_exposures: 0, active: 0 });
+        }
+        self.cohorts[day].exposed += 1;
+        self.cohorts[day].active += 1;
+        self.cohort_of.insert(exposure.infectee, day);
+    }
+
+    fn infection_ended(&mut self, _hr: i32, citizen: Uuid) {
+        if let Some(cohort) = self.cohort_of.remove(&citizen) {
+            self.cohorts[cohort].active -= 1;
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::geography::{Point, Setting};
+
+    use super::*;
+
+    fn exposure(hour: i32, infector: Uuid, infectee: Uuid) -> Exposure {
+        Exposure { hour, infector, infectee, location: Point::new(0, 0), setting: Setting::Home }
+    }
+
+    #[test]
+    fn should_estimate_rt_of_cohort_exposed_each_day() {
+        let mut rt = ReproductionNumber::new("test_rt.csv".to_string());
+        let (root, a, b, c, d) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        rt.citizen_exposed(&exposure(10, root, a));
+        rt.citizen_exposed(&exposure(20, root, b));
+        rt.citizen_exposed(&exposure(50, a, c));
+        rt.citizen_exposed(&exposure(60, a, d));
+        rt.citizen_exposed(&exposure(70, c, Uuid::new_v4()));
+
+        let estimates = rt.estimates();
+        assert_eq!(estimates.len(), 3);
+        assert_eq!(estimates[0], ReproductionNumberAtDay { day: 0, exposures: 2, rt: Some(1.0), doubling_time_days: None });
+        assert_eq!(estimates[1].rt, None);
+        assert_eq!(estimates[2].exposures, 3);
+        assert_eq!(estimates[2].rt, Some(1.0 / 3.0));
+    }
+
+    #[test]
+    fn should_estimate_doubling_time_over_a_week() {
+        let mut rt = ReproductionNumber::new("test_rt.csv".to_string());
+        for day in 0..=7 {
+            let exposures = if day == 0 { 2 } else if day == 7 { 6 } else { 0 };
+            for _ in 0..exposures {
+                rt.citizen_exposed(&exposure(day * 24, Uuid::new_v4(), Uuid::new_v4()));
+            }
+        }
+
+        let estimates = rt.estimates();
+        assert_eq!(estimates[6].doubling_time_days, None);
+        assert!((estimates[7].doubling_time_days.unwrap() - 3.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn should_complete_days_in_order_once_their_cohorts_are_no_longer_infectious() {
+        let mut rt = ReproductionNumber::new("test_rt.csv".to_string());
+        let (root, a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        rt.citizen_exposed(&exposure(10, root, a));
+        rt.citizen_exposed(&exposure(30, a, b));
+        rt.citizen_exposed(&exposure(40, a, c));
+
+        assert!(rt.completed(23).is_empty());
+        rt.infection_ended(44, b);
+        rt.infection_ended(44, c);
+        assert!(rt.completed(47).is_empty());
+
+        rt.infection_ended(50, a);
+        rt.infection_ended(50, a);
+        let completed = rt.completed(71);
+        assert_eq!(completed.iter().map(|estimate| estimate.day).collect::<Vec<_>>(), vec![0, 1]);
+        assert_eq!(completed[0].rt, Some(2.0));
+        assert!(rt.completed(95).is_empty());
+    }
+}
@@ -0,0 +1,104 @@
+/*
+ * EpiRust
+ * Copyright (c) 2020  ThoughtWorks, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use std::any::Any;
+
+use uuid::Uuid;
+
+use crate::environment;
+use crate::geography::Setting;
+use crate::listeners::events::exposure::Exposure;
+use crate::listeners::listener::Listener;
+
+#[derive(Serialize, Debug, PartialEq)]
+struct Transmission {
+    hour: i32,
+    infector: Uuid,
+    infectee: Uuid,
+    x: i32,
+    y: i32,
+    setting: Setting,
+}
+
+impl Transmission {
+    fn from(exposure: &Exposure) -> Transmission {
+        Transmission {
+            hour: exposure.hour,
+            infector: exposure.infector,
+            infectee: exposure.infectee,
+            x: exposure.location.x,
+            y: exposure.location.y,
+            setting: exposure.setting,
+        }
+    }
+}
+
+/// Writes every exposure, with who it came from, when and where. Citizens infected at the start of the simulation,
+/// or in another engine, are the roots of the tree.
+pub struct TransmissionTree {
+    transmissions: Vec<Transmission>,
+    output_file_name: String,
+}
+
+impl TransmissionTree {
+    pub fn new(output_file_name: String) -> TransmissionTree {
+        TransmissionTree {
+            transmissions: Vec::new(),
+            output_file_name,
+        }
+    }
+}
+
+impl Listener for TransmissionTree {
+    fn simulation_ended(&mut self) {
+        let mut output_path = environment::output_dir();
+        output_path.push(&self.output_file_name);
+        match crate::listeners::csv_service::write(&output_path, &self.transmissions) {
+            Ok(_) => {}
+            Err(e) => { error!("Failed to serialize transmission tree: {}", e) }
+        }
+    }
+
+    fn citizen_exposed(&mut self, exposure: &Exposure) {
+        self.transmissions.push(Transmission::from(exposure));
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::geography::Point;
+
+    use super::*;
+
+    #[test]
+    fn should_add_exposures() {
+        let mut tree = TransmissionTree::new("test_transmission_tree.csv".to_string());
+        let (infector, infectee) = (Uuid::new_v4(), Uuid::new_v4());
+        let exposure = Exposure { hour: 30, infector, infectee, location: Point::new(4, 5), setting: Setting::Work };
+
+        tree.citizen_exposed(&exposure);
+
+        let expected = Transmission { hour: 30, infector, infectee, x: 4, y: 5, setting: Setting::Work };
+        assert_eq!(tree.transmissions, vec![expected]);
+    }
+}
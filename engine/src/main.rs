@@ -34,8 +34,10 @@ use crate::kafka_consumer::KafkaConsumer;
 mod constants;
 
 mod agent;
+mod checkpoint;
 mod epidemiology_simulation;
 mod allocation_map;
+mod dense_grid;
 mod geography;
 mod disease;
 mod listeners;
@@ -51,6 +53,15 @@ mod environment;
 mod disease_state_machine;
 mod travel_plan;
 mod travellers_consumer;
+mod tiles;
+mod contact_recorder;
+mod schedule;
+mod calendar;
+mod vaccine;
+mod hospital_beds;
+mod duration;
+mod compartment_model;
+mod strain;
 
 const STANDALONE_SIM_ID: &str = "0";
 
@@ -77,6 +88,20 @@ async fn main() {
             .help("An identifier for the engine. Needed in daemon mode when running a larger simulation \
             distributed across multiple engines.")
             .takes_value(true))
+        .arg(Arg::with_name("seed")
+            .long("seed")
+            .short("s")
+            .value_name("SEED")
+            .help("Seed for the random number generator, to reproduce a simulation run. \
+            Overrides the seed in the config file")
+            .takes_value(true))
+        .arg(Arg::with_name("resume")
+            .long("resume")
+            .short("r")
+            .value_name("CHECKPOINT")
+            .help("Resume a simulation from a checkpoint file. Use the same config the simulation was started with. \
+            The outputs of the resumed run only cover the hours after the checkpoint")
+            .takes_value(true))
         .get_matches();
 
     let daemon = matches.is_present("daemon");
@@ -109,10 +134,23 @@ async fn main() {
         //     _ => panic!("Cannot run for {} agents", input_count)
         // };
 
-        let config = config::read(config_file.to_string()).expect("Failed to read config file");
+        let mut config = config::read(config_file.to_string()).expect("Failed to read config file");
+        if let Some(seed) = matches.value_of("seed") {
+            config.set_seed(seed.parse().expect("Seed should be a non-negative integer"));
+        }
 
-        let mut epidemiology = epidemiology_simulation::Epidemiology::new(&config, STANDALONE_SIM_ID.to_string());
-        epidemiology.run(&config, &run_mode).await;
+        match matches.value_of("resume") {
+            Some(checkpoint_file) => {
+                let checkpoint = checkpoint::read(checkpoint_file).expect("Failed to read checkpoint file");
+                let (mut epidemiology, counts, interventions) = epidemiology_simulation::Epidemiology::from_checkpoint(
+                    &config, STANDALONE_SIM_ID.to_string(), checkpoint);
+                epidemiology.resume(&config, &run_mode, counts, interventions).await;
+            }
+            None => {
+                let mut epidemiology = epidemiology_simulation::Epidemiology::new(&config, STANDALONE_SIM_ID.to_string());
+                epidemiology.run(&config, &run_mode).await;
+            }
+        }
         info!("Done");
     }
 }
@@ -0,0 +1,297 @@
+/*
+ * EpiRust
+ * Copyright (c) 2020  ThoughtWorks, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use serde::{de, Deserialize, Deserializer};
+
+use crate::agent::WorkStatus;
+use crate::calendar::Calendar;
+use crate::constants;
+
+/// What a citizen does during a time window. Citizens go to the place of an activity in its first hour,
+/// and move around there for the rest of the window.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Copy, Clone)]
+pub enum Activity {
+    /// Goes to bed at home, and neither moves nor gets exposed
+    Sleep,
+    /// Stays put, and neither moves nor gets exposed
+    Rest,
+    /// Takes public transport, if the citizen uses it
+    Travel,
+    Work,
+    Home,
+    /// Goes out to the housing area
+    Housing,
+    /// Hospital staff only: goes to the hospital, unless on their quarantine rotation
+    Shift,
+    /// Hospital staff only: quarantined until the next shift
+    OffShift,
+    /// Students only: goes to their classroom
+    School,
+}
+
+impl Activity {
+    /// The only work status that can be scheduled for this activity, if it is restricted to one
+    fn restricted_to(&self) -> Option<&'static str> {
+        match self {
+            Activity::Shift | Activity::OffShift => Some("HospitalStaff"),
+            Activity::School => Some("Student"),
+            _ => None
+        }
+    }
+}
+
+/// An activity from `start` to `end`, both inclusive. A window with `end` before `start` runs past midnight.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Copy, Clone)]
+pub struct TimeWindow {
+    activity: Activity,
+    start: i32,
+    end: i32,
+}
+
+impl TimeWindow {
+    fn new(activity: Activity, start: i32, end: i32) -> TimeWindow {
+        TimeWindow { activity, start, end }
+    }
+
+    fn contains(&self, hour: i32) -> bool {
+        if self.start <= self.end {
+            self.start <= hour && hour <= self.end
+        } else {
+            hour >= self.start || hour <= self.end
+        }
+    }
+}
+
+/// The activities of a day, on the given days of the cycle (every day if none are given)
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct Routine {
+    #[serde(default)]
+    days: Vec<i32>,
+    activities: Vec<TimeWindow>,
+}
+
+impl Routine {
+    fn every_day(activities: Vec<TimeWindow>) -> Routine {
+        Routine { days: vec![], activities }
+    }
+
+    fn applies_on(&self, day: i32) -> bool {
+        self.days.is_empty() || self.days.contains(&day)
+    }
+
+    fn validate(&self, cycle_days: i32, work_status: &str) -> Result<(), String> {
+        if let Some(day) = self.days.iter().find(|day| **day < 0 || **day >= cycle_days) {
+            return Err(format!("{} routine is scheduled on day {}, but the cycle has {} days", work_status, day, cycle_days));
+        }
+        for (i, window) in self.activities.iter().enumerate() {
+            if !(0..constants::HOURS_IN_A_DAY).contains(&window.start) || !(0..constants::HOURS_IN_A_DAY).contains(&window.end) {
+                return Err(format!("{} routine has {:?} at hours {}-{}, outside of a day", work_status,
+                                   window.activity, window.start, window.end));
+            }
+            let overlapping = self.activities[..i].iter()
+                .find(|other| (0..constants::HOURS_IN_A_DAY).any(|hour| window.contains(hour) && other.contains(hour)));
+            if let Some(other) = overlapping {
+                return Err(format!("{} routine has overlapping {:?} and {:?}", work_status, other.activity, window.activity));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The daily routines of citizens by work status, repeating every `cycle_days` days. On each day a citizen follows the
+/// first routine of their work status that applies; hours outside its windows are spent moving around.
+/// Hours `ROUTINE_START_TIME` and `ROUTINE_END_TIME` are kept for hospitalisation and deaths, whatever the schedule says.
+/// With a calendar, the cycle counts from a Monday, and `Work` and `School` become an outing to the housing area on
+/// non-working days.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Schedule {
+    #[serde(skip)]
+    calendar: Option<Calendar>,
+    cycle_days: i32,
+    #[serde(rename = "Normal")]
+    normal: Vec<Routine>,
+    #[serde(rename = "Essential")]
+    essential: Vec<Routine>,
+    #[serde(rename = "HospitalStaff")]
+    hospital_staff: Vec<Routine>,
+    #[serde(rename = "Student")]
+    student: Vec<Routine>,
+    #[serde(rename = "NA")]
+    na: Vec<Routine>,
+}
+
+impl Schedule {
+    /// The activity at the given hour of the simulation, and whether it starts in this hour
+    pub fn activity_at(&self, work_status: &WorkStatus, simulation_hour: i32) -> Option<(Activity, bool)> {
+        let days = match &self.calendar {
+            Some(calendar) => calendar.days_since_monday(simulation_hour),
+            None => simulation_hour / constants::HOURS_IN_A_DAY
+        };
+        let day = days % self.cycle_days;
+        let hour = simulation_hour % constants::HOURS_IN_A_DAY;
+        let window = self.routines_for(work_status).iter()
+            .find(|routine| routine.applies_on(day))
+            .and_then(|routine| routine.activities.iter().find(|window| window.contains(hour)))?;
+        let activity = match (window.activity, &self.calendar) {
+            (Activity::Work, Some(calendar)) | (Activity::School, Some(calendar))
+            if !calendar.is_working_day(simulation_hour) => Activity::Housing,
+            (activity, _) => activity
+        };
+        Some((activity, window.start == hour))
+    }
+
+    pub fn with_calendar(self, calendar: Option<Calendar>) -> Schedule {
+        Schedule { calendar, ..self }
+    }
+
+    fn routines_for(&self, work_status: &WorkStatus) -> &Vec<Routine> {
+        match work_status {
+            WorkStatus::Normal {} => &self.normal,
+            WorkStatus::Essential {} => &self.essential,
+            WorkStatus::HospitalStaff { .. } => &self.hospital_staff,
+            WorkStatus::Student {} => &self.student,
+            WorkStatus::NA {} => &self.na,
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.cycle_days < 1 {
+            return Err(format!("Schedule cycle must be at least a day, but is {} days", self.cycle_days));
+        }
+        let by_status = [("Normal", &self.normal), ("Essential", &self.essential),
+            ("HospitalStaff", &self.hospital_staff), ("Student", &self.student), ("NA", &self.na)];
+        for (work_status, routines) in by_status.iter() {
+            for routine in routines.iter() {
+                routine.validate(self.cycle_days, work_status)?;
+                let misplaced = routine.activities.iter()
+                    .find(|window| window.activity.restricted_to().map_or(false, |only| only != *work_status));
+                if let Some(window) = misplaced {
+                    return Err(format!("{:?} is only for {}, but is in the {} routine", window.activity,
+                                       window.activity.restricted_to().unwrap(), work_status));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        let working_day = Routine::every_day(vec![
+            TimeWindow::new(Activity::Sleep, 1, 6),
+            TimeWindow::new(Activity::Travel, 7, 7),
+            TimeWindow::new(Activity::Work, 8, 16),
+            TimeWindow::new(Activity::Travel, 17, 17),
+            TimeWindow::new(Activity::Home, 18, 22),
+        ]);
+        let hospital_shift = Routine::every_day(vec![
+            TimeWindow::new(Activity::Rest, 1, 6),
+            TimeWindow::new(Activity::Shift, 8, 17),
+            TimeWindow::new(Activity::OffShift, 18, 22),
+        ]);
+        let school_day = Routine::every_day(vec![
+            TimeWindow::new(Activity::Sleep, 1, 6),
+            TimeWindow::new(Activity::School, 8, 14),
+            TimeWindow::new(Activity::Home, 15, 22),
+        ]);
+        let non_working_day = Routine::every_day(vec![
+            TimeWindow::new(Activity::Sleep, 1, 6),
+            TimeWindow::new(Activity::Housing, 8, 11),
+            TimeWindow::new(Activity::Home, 12, 22),
+        ]);
+        Schedule {
+            calendar: None,
+            cycle_days: 1,
+            normal: vec![working_day.clone()],
+            essential: vec![working_day],
+            hospital_staff: vec![hospital_shift],
+            student: vec![school_day],
+            na: vec![non_working_day],
+        }
+    }
+}
+
+/// Rejects schedules that can't be followed, wherever the config comes from
+pub fn deserialize_schedule<'de, D>(deserializer: D) -> Result<Schedule, D::Error> where D: Deserializer<'de> {
+    let schedule = Schedule::deserialize(deserializer)?;
+    schedule.validate().map_err(de::Error::custom)?;
+    Ok(schedule)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_follow_routine_of_the_day() {
+        let schedule: Schedule = serde_json::from_str(r#"{
+            "cycle_days": 7,
+            "Normal": [
+                {"days": [5, 6], "activities": [{"activity": "Housing", "start": 10, "end": 14}]},
+                {"activities": [{"activity": "Work", "start": 20, "end": 4}]}
+            ]
+        }"#).unwrap();
+        schedule.validate().unwrap();
+
+        assert_eq!(schedule.activity_at(&WorkStatus::Normal {}, 20), Some((Activity::Work, true)));
+        assert_eq!(schedule.activity_at(&WorkStatus::Normal {}, 24 + 3), Some((Activity::Work, false)));
+        assert_eq!(schedule.activity_at(&WorkStatus::Normal {}, 12), None);
+        assert_eq!(schedule.activity_at(&WorkStatus::Normal {}, 5 * 24 + 10), Some((Activity::Housing, true)));
+        assert_eq!(schedule.activity_at(&WorkStatus::Normal {}, 5 * 24 + 20), None);
+        assert_eq!(schedule.activity_at(&WorkStatus::Normal {}, 7 * 24 + 20), Some((Activity::Work, true)));
+        assert_eq!(schedule.activity_at(&WorkStatus::NA {}, 8), Some((Activity::Housing, true)));
+    }
+
+    #[test]
+    fn should_follow_calendar_weeks() {
+        let calendar: Calendar = serde_json::from_str(r#"{"start_date": "2020-03-06"}"#).unwrap();
+        let schedule: Schedule = serde_json::from_str(r#"{
+            "cycle_days": 7,
+            "Normal": [
+                {"days": [6], "activities": [{"activity": "Sleep", "start": 1, "end": 12}]},
+                {"activities": [{"activity": "Work", "start": 8, "end": 16}]}
+            ]
+        }"#).unwrap();
+        let schedule = schedule.with_calendar(Some(calendar));
+
+        assert_eq!(schedule.activity_at(&WorkStatus::Normal {}, 8), Some((Activity::Work, true)));
+        assert_eq!(schedule.activity_at(&WorkStatus::Normal {}, 24 + 8), Some((Activity::Housing, true)));
+        assert_eq!(schedule.activity_at(&WorkStatus::Normal {}, 2 * 24 + 8), Some((Activity::Sleep, false)));
+        assert_eq!(schedule.activity_at(&WorkStatus::Essential {}, 2 * 24 + 8), Some((Activity::Housing, true)));
+        assert_eq!(schedule.activity_at(&WorkStatus::Normal {}, 3 * 24 + 8), Some((Activity::Work, true)));
+    }
+
+    #[test]
+    fn should_reject_invalid_schedules() {
+        let invalid = [
+            r#"{"cycle_days": 0}"#,
+            r#"{"Normal": [{"days": [1], "activities": []}]}"#,
+            r#"{"Normal": [{"activities": [{"activity": "Work", "start": 8, "end": 24}]}]}"#,
+            r#"{"NA": [{"activities": [{"activity": "Home", "start": 22, "end": 2}, {"activity": "Sleep", "start": 1, "end": 6}]}]}"#,
+            r#"{"Essential": [{"activities": [{"activity": "Shift", "start": 8, "end": 17}]}]}"#,
+            r#"{"NA": [{"activities": [{"activity": "School", "start": 8, "end": 12}]}]}"#,
+        ];
+        for schedule in invalid.iter() {
+            let schedule: Schedule = serde_json::from_str(schedule).unwrap();
+            assert!(schedule.validate().is_err(), "{:?} should be invalid", schedule);
+        }
+        assert_eq!(Schedule::default().validate(), Ok(()));
+    }
+}
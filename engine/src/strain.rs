@@ -0,0 +1,152 @@
+/*
+ * EpiRust
+ * Copyright (c) 2020  ThoughtWorks, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use serde::{de, Deserialize, Deserializer};
+
+use crate::disease::{deserialize_disease, Disease};
+
+/// The strain of the `disease` in the config, which disease overrides apply to
+pub const WILD_TYPE: &str = "wild_type";
+/// Citizens keep a bit for each strain they recovered from, the wild type included
+const MAX_STRAINS: usize = 64;
+
+/// A variant of the disease, seeded by exposing `introductions` susceptible citizens at the hour `introduced_at`
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct Strain {
+    pub name: String,
+    #[serde(deserialize_with = "deserialize_disease")]
+    pub disease: Disease,
+    #[serde(deserialize_with = "deserialize_introduced_at")]
+    pub introduced_at: i32,
+    pub introductions: i32,
+}
+
+/// The chance of a citizen who recovered from the strain `from` not being infected by an exposure to `to`. Recovery
+/// protects fully against the same strain, and not at all against the others unless listed
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct CrossImmunity {
+    pub from: String,
+    pub to: String,
+    pub protection: f64,
+}
+
+/// Hour 0 is the starting state, and is never simulated
+fn deserialize_introduced_at<'de, D>(deserializer: D) -> Result<i32, D::Error>
+    where
+        D: Deserializer<'de>,
+{
+    match i32::deserialize(deserializer)? {
+        hour if hour < 1 => Err(de::Error::custom(format!("Strains can only be introduced from hour 1, not {}", hour))),
+        hour => Ok(hour)
+    }
+}
+
+fn names_of(strains: &[Strain]) -> Vec<&str> {
+    std::iter::once(WILD_TYPE).chain(strains.iter().map(|strain| strain.name.as_str())).collect()
+}
+
+/// Checks the strains and cross immunity of a config
+pub fn validate(strains: &[Strain], cross_immunity: &[CrossImmunity]) -> Result<(), String> {
+    let names = names_of(strains);
+    if names.len() > MAX_STRAINS {
+        return Err(format!("There can be at most {} strains besides the wild type", MAX_STRAINS - 1));
+    }
+    for (i, name) in names.iter().enumerate() {
+        if names[..i].contains(name) {
+            return Err(format!("Strain {} is declared twice", name));
+        }
+    }
+    for entry in cross_immunity {
+        for name in &[&entry.from, &entry.to] {
+            if !names.contains(&name.as_str()) {
+                return Err(format!("Cross immunity refers to unknown strain {}", name));
+            }
+        }
+        if !(0.0..=1.0).contains(&entry.protection) {
+            return Err(format!("Cross immunity from {} to {} is not between 0 and 1", entry.from, entry.to));
+        }
+    }
+    Ok(())
+}
+
+/// Cross immunity by the index of the strain recovered from and then of the one exposed to, the wild type first
+pub fn cross_immunity_matrix(strains: &[Strain], cross_immunity: &[CrossImmunity]) -> Vec<Vec<f64>> {
+    let names = names_of(strains);
+    let index_of = |name: &str| names.iter().position(|other| *other == name)
+        .unwrap_or_else(|| panic!("Cross immunity refers to unknown strain {}", name));
+
+    let mut matrix: Vec<Vec<f64>> = (0..names.len())
+        .map(|from| (0..names.len()).map(|to| if from == to { 1.0 } else { 0.0 }).collect())
+        .collect();
+    for entry in cross_immunity {
+        matrix[index_of(&entry.from)][index_of(&entry.to)] = entry.protection;
+    }
+    matrix
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variant(name: &str) -> Strain {
+        Strain {
+            name: name.to_string(),
+            disease: Disease::new(5, 20, 40, 9, 12, 0.05, 0.5, 0.035, 0.3, 0.3, 48, 48),
+            introduced_at: 240,
+            introductions: 10,
+        }
+    }
+
+    #[test]
+    fn should_build_cross_immunity_matrix() {
+        let cross_immunity = vec![CrossImmunity { from: WILD_TYPE.to_string(), to: "delta".to_string(), protection: 0.4 }];
+        let matrix = cross_immunity_matrix(&[variant("delta")], &cross_immunity);
+
+        assert_eq!(matrix, vec![vec![1.0, 0.4], vec![0.0, 1.0]]);
+    }
+
+    #[test]
+    fn should_reject_strain_introduced_before_the_first_hour() {
+        let strain = |hour: i32| format!(r#"{{"name": "delta", "introduced_at": {}, "introductions": 10,
+            "disease": {{"Preset": "sars", "catalogue": "config/diseases.yaml"}}}}"#, hour);
+
+        assert_eq!(serde_json::from_str::<Strain>(&strain(1)).unwrap().introduced_at, 1);
+        let error = serde_json::from_str::<Strain>(&strain(0)).unwrap_err().to_string();
+        assert!(error.contains("only be introduced from hour 1, not 0"), "{}", error);
+    }
+
+    #[test]
+    fn should_reject_unknown_strain() {
+        let cross_immunity = vec![CrossImmunity { from: "omicron".to_string(), to: "delta".to_string(), protection: 0.4 }];
+        assert_eq!(validate(&[variant("delta")], &cross_immunity),
+                   Err("Cross immunity refers to unknown strain omicron".to_string()));
+    }
+
+    #[test]
+    fn should_reject_strains_declared_twice_and_protection_out_of_range() {
+        let protection = |protection: f64| vec![CrossImmunity { from: WILD_TYPE.to_string(), to: "delta".to_string(), protection }];
+
+        assert!(validate(&[variant("delta")], &protection(0.4)).is_ok());
+        assert_eq!(validate(&[variant("delta"), variant("delta")], &[]), Err("Strain delta is declared twice".to_string()));
+        assert_eq!(validate(&[variant(WILD_TYPE)], &[]), Err("Strain wild_type is declared twice".to_string()));
+        assert_eq!(validate(&[variant("delta")], &protection(1.2)),
+                   Err("Cross immunity from wild_type to delta is not between 0 and 1".to_string()));
+        assert!(validate(&vec![variant("delta"); 64], &[]).unwrap_err().contains("at most 63 strains"));
+    }
+}
@@ -0,0 +1,71 @@
+/*
+ * EpiRust
+ * Copyright (c) 2020  ThoughtWorks, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use crate::agent::Citizen;
+use crate::allocation_map::AgentLocationMap;
+use crate::geography::Point;
+
+/// Splits the grid into square tiles, so that citizens in different tiles can be simulated in parallel.
+/// The layout depends only on the grid size, never on the number of threads.
+pub struct Tiles {
+    tile_size: i32,
+    tiles_per_side: i32,
+}
+
+impl Tiles {
+    pub fn new(grid_size: i32, tile_size: i32) -> Tiles {
+        // areas extend up to and including grid_size
+        Tiles { tile_size, tiles_per_side: grid_size / tile_size + 1 }
+    }
+
+    pub fn count(&self) -> usize {
+        (self.tiles_per_side * self.tiles_per_side) as usize
+    }
+
+    pub fn tile_of(&self, point: &Point) -> usize {
+        let column = (point.x / self.tile_size).min(self.tiles_per_side - 1);
+        let row = (point.y / self.tile_size).min(self.tiles_per_side - 1);
+        (column * self.tiles_per_side + row) as usize
+    }
+
+    /// Citizens grouped by tile, in the order they appear in the map
+    pub fn split<'a>(&self, map: &'a AgentLocationMap) -> Vec<Vec<(&'a Point, &'a Citizen)>> {
+        let mut tiles = vec![Vec::new(); self.count()];
+        for (point, citizen) in map.iter() {
+            tiles[self.tile_of(point)].push((point, citizen));
+        }
+        tiles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_find_tile_of_point() {
+        let tiles = Tiles::new(100, 32);
+
+        assert_eq!(tiles.count(), 16);
+        assert_eq!(tiles.tile_of(&Point::new(0, 0)), 0);
+        assert_eq!(tiles.tile_of(&Point::new(0, 33)), 1);
+        assert_eq!(tiles.tile_of(&Point::new(33, 0)), 4);
+        assert_eq!(tiles.tile_of(&Point::new(100, 100)), 15);
+    }
+}
@@ -22,6 +22,7 @@ use crate::agent::Citizen;
 use crate::geography::Point;
 use uuid::Uuid;
 use crate::disease_state_machine::DiseaseStateMachine;
+use crate::vaccine::Vaccination;
 
 #[derive(Debug, Deserialize, PartialEq, Clone)]
 pub struct TravelPlan {
@@ -202,7 +203,7 @@ impl TravellersByRegion {
 pub struct Traveller {
     pub id: Uuid,
     pub immunity: i32,
-    pub vaccinated: bool,
+    pub vaccination: Option<Vaccination>,
     pub uses_public_transport: bool,
     pub working: bool,
     pub state_machine: DiseaseStateMachine,
@@ -214,7 +215,7 @@ impl Traveller {
         Traveller {
             id: Uuid::new_v4(),
             immunity: 0,
-            vaccinated: false,
+            vaccination: None,
             uses_public_transport: false,
             working: false,
             state_machine: DiseaseStateMachine::new()
@@ -227,7 +228,7 @@ impl From<&Citizen> for Traveller {
         Traveller {
             id: citizen.id,
             immunity: citizen.get_immunity(),
-            vaccinated: citizen.is_vaccinated(),
+            vaccination: citizen.get_vaccination(),
             uses_public_transport: citizen.uses_public_transport,
             working: citizen.is_working(),
             state_machine: citizen.state_machine
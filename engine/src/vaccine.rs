@@ -0,0 +1,192 @@
+/*
+ * EpiRust
+ * Copyright (c) 2020  ThoughtWorks, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use std::collections::HashMap;
+
+use serde::{de, Deserialize, Deserializer};
+
+use crate::constants;
+
+/// A vaccine product. Efficacies are for the full course of `doses`, and wane by half every `half_life_days` after
+/// the last dose.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Copy, Clone)]
+pub struct Vaccine {
+    pub infection_efficacy: f64,
+    pub severity_efficacy: f64,
+    #[serde(default)]
+    pub half_life_days: Option<f64>,
+    #[serde(default = "Vaccine::single_dose")]
+    pub doses: i32,
+    #[serde(default)]
+    pub days_between_doses: i32,
+    /// The share of the efficacy before the course is complete
+    #[serde(default = "Vaccine::full_protection")]
+    pub first_dose_protection: f64,
+}
+
+impl Vaccine {
+    fn single_dose() -> i32 {
+        1
+    }
+
+    fn full_protection() -> f64 {
+        1.0
+    }
+
+    /// Blocks all infections, for good, after a single dose
+    pub fn perfect() -> Vaccine {
+        Vaccine {
+            infection_efficacy: 1.0,
+            severity_efficacy: 1.0,
+            half_life_days: None,
+            doses: 1,
+            days_between_doses: 0,
+            first_dose_protection: 1.0,
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let efficacies = [("infection_efficacy", self.infection_efficacy),
+            ("severity_efficacy", self.severity_efficacy),
+            ("first_dose_protection", self.first_dose_protection)];
+        if let Some((name, value)) = efficacies.iter().find(|(_, value)| !(0.0..=1.0).contains(value)) {
+            return Err(format!("{} must be between 0 and 1, not {}", name, value));
+        }
+        match self.half_life_days {
+            Some(days) if !(days > 0.0 && days.is_finite()) => {
+                Err(format!("half_life_days must be positive, not {}", days))
+            }
+            _ if self.doses < 1 => Err(format!("A vaccine needs at least one dose, not {}", self.doses)),
+            _ => Ok(())
+        }
+    }
+}
+
+pub fn deserialize_vaccines<'de, D>(deserializer: D) -> Result<HashMap<String, Vaccine>, D::Error>
+    where
+        D: Deserializer<'de>,
+{
+    let vaccines = HashMap::<String, Vaccine>::deserialize(deserializer)?;
+    for (name, vaccine) in &vaccines {
+        vaccine.validate().map_err(|e| de::Error::custom(format!("Vaccine {}: {}", name, e)))?;
+    }
+    Ok(vaccines)
+}
+
+/// The doses of a vaccine a citizen has had
+#[derive(Debug, PartialEq, Serialize, Deserialize, Copy, Clone)]
+pub struct Vaccination {
+    vaccine: Vaccine,
+    doses: i32,
+    last_dose_at: i32,
+}
+
+impl Vaccination {
+    pub fn new(vaccine: Vaccine, hour: i32) -> Vaccination {
+        Vaccination { vaccine, doses: 1, last_dose_at: hour }
+    }
+
+    pub fn add_dose(&mut self, hour: i32) {
+        self.doses += 1;
+        self.last_dose_at = hour;
+    }
+
+    pub fn is_dose_due(&self, hour: i32) -> bool {
+        self.doses < self.vaccine.doses
+            && hour >= self.last_dose_at + self.vaccine.days_between_doses * constants::HOURS_IN_A_DAY
+    }
+
+    /// The chance of an exposure being blocked
+    pub fn infection_protection(&self, hour: i32) -> f64 {
+        self.protection(self.vaccine.infection_efficacy, hour)
+    }
+
+    /// The chance of an infection that would have been severe staying mild
+    pub fn severity_protection(&self, hour: i32) -> f64 {
+        self.protection(self.vaccine.severity_efficacy, hour)
+    }
+
+    fn protection(&self, efficacy: f64, hour: i32) -> f64 {
+        let course = if self.doses >= self.vaccine.doses { 1.0 } else { self.vaccine.first_dose_protection };
+        let waning = match self.vaccine.half_life_days {
+            Some(half_life) => {
+                let days = (hour - self.last_dose_at) as f64 / constants::HOURS_IN_A_DAY as f64;
+                0.5_f64.powf(days / half_life)
+            }
+            None => 1.0
+        };
+        efficacy * course * waning
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Vaccines {
+        #[serde(deserialize_with = "deserialize_vaccines")]
+        #[allow(dead_code)]
+        vaccines: HashMap<String, Vaccine>,
+    }
+
+    #[test]
+    fn should_protect_fully_with_perfect_vaccine() {
+        let vaccination = Vaccination::new(Vaccine::perfect(), 10);
+
+        assert_eq!(vaccination.infection_protection(10_000), 1.0);
+        assert_eq!(vaccination.severity_protection(10_000), 1.0);
+        assert!(!vaccination.is_dose_due(10_000));
+    }
+
+    #[test]
+    fn should_protect_partially_until_second_dose_and_wane_after_it() {
+        let vaccine: Vaccine = serde_json::from_str(r#"{"infection_efficacy": 0.8, "severity_efficacy": 0.9,
+            "half_life_days": 100, "doses": 2, "days_between_doses": 21, "first_dose_protection": 0.5}"#).unwrap();
+        let mut vaccination = Vaccination::new(vaccine, 0);
+
+        assert_eq!(vaccination.infection_protection(0), 0.4);
+        assert!(!vaccination.is_dose_due(20 * 24));
+        assert!(vaccination.is_dose_due(21 * 24));
+
+        vaccination.add_dose(21 * 24);
+        assert!(!vaccination.is_dose_due(500 * 24));
+        assert_eq!(vaccination.infection_protection(21 * 24), 0.8);
+        assert_eq!(vaccination.severity_protection(121 * 24), 0.45);
+    }
+
+    #[test]
+    fn should_reject_vaccines_that_can_not_be_given() {
+        let vaccines = |vaccine: &str| {
+            serde_json::from_str::<Vaccines>(&format!(r#"{{"vaccines": {{"v": {}}}}}"#, vaccine))
+        };
+        let error_of = |vaccine: &str| vaccines(vaccine).err().unwrap().to_string();
+
+        assert!(vaccines(r#"{"infection_efficacy": 1.0, "severity_efficacy": 0.0, "half_life_days": 0.5}"#).is_ok());
+        assert!(error_of(r#"{"infection_efficacy": 1.2, "severity_efficacy": 0.9}"#)
+            .contains("Vaccine v: infection_efficacy must be between 0 and 1, not 1.2"));
+        assert!(error_of(r#"{"infection_efficacy": 0.8, "severity_efficacy": -0.1}"#).contains("severity_efficacy"));
+        assert!(error_of(r#"{"infection_efficacy": 0.8, "severity_efficacy": 0.9, "first_dose_protection": 2}"#)
+            .contains("first_dose_protection"));
+        assert!(error_of(r#"{"infection_efficacy": 0.8, "severity_efficacy": 0.9, "half_life_days": 0}"#)
+            .contains("half_life_days must be positive, not 0"));
+        assert!(error_of(r#"{"infection_efficacy": 0.8, "severity_efficacy": 0.9, "doses": 0}"#)
+            .contains("at least one dose"));
+    }
+}